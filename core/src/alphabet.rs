@@ -0,0 +1,304 @@
+use std::{
+    ops::Range,
+    simd::{
+        LaneCount, Mask, Simd, SupportedLaneCount,
+        cmp::{SimdPartialEq, SimdPartialOrd},
+    },
+};
+
+use crate::{HashWord, const_vec::ConstVec};
+
+/// Simple insertion sort
+const fn sort_bytes<const N: usize>(mut bytes: [u8; N]) -> [u8; N] {
+    let mut i = 1;
+    while i < bytes.len() {
+        let mut j = i;
+        while j > 0 && bytes[j - 1] > bytes[j] {
+            bytes.swap(j, j - 1);
+            j -= 1;
+        }
+        i += 1;
+    }
+
+    bytes
+}
+
+/// Compile-time preprocessed alphabet.
+///
+/// Stores the sorted bytes as well as the the contiguous ranges making up this alphabet.
+///
+/// Because this is all computed at compile-time, the optimizer can generate highly optimized code
+/// for [`Self::contains`], [`Self::simd_prefilter`] and unroll loops that iterate on
+/// [`Self::simd_groups`].
+#[derive(Debug, Clone)]
+pub struct Alphabet<const N: usize> {
+    bytes: [u8; N],
+    ranges: ConstVec<Range<u32>, N>,
+}
+
+impl<const N: usize> Alphabet<N> {
+    pub const fn new(bytes: &'static [u8; N]) -> Self {
+        // avoid sorting if not necessary
+        if bytes.len() > u8::MAX as usize + 1 {
+            panic!("duplicate character in alphabet");
+        }
+
+        let sorted = sort_bytes(*bytes);
+
+        let mut i = 1;
+        while i < bytes.len() {
+            if bytes[i] == bytes[i - 1] {
+                panic!("duplicate character in alphabet");
+            }
+            i += 1;
+        }
+
+        Self {
+            ranges: Self::compute_ranges(&sorted),
+            bytes: sorted,
+        }
+    }
+
+    /// Runtime counterpart of [`Self::new`], for alphabets loaded rather than written out.
+    pub fn try_from_array(bytes: [u8; N]) -> Result<Self, DuplicateChar> {
+        let sorted = sort_bytes(bytes);
+        if let Some(w) = sorted.windows(2).find(|w| w[0] == w[1]) {
+            return Err(DuplicateChar(w[0]));
+        }
+        Ok(Self {
+            ranges: Self::compute_ranges(&sorted),
+            bytes: sorted,
+        })
+    }
+
+    const fn compute_ranges(sorted: &[u8; N]) -> ConstVec<Range<u32>, N> {
+        const U8_SIZE: u32 = u8::MAX as u32 + 1;
+
+        let mut ranges = ConstVec::new();
+
+        if sorted.is_empty() {
+            return ranges;
+        }
+
+        ranges.push(sorted[0] as u32..U8_SIZE);
+
+        let mut i = 1;
+        while i < sorted.len() {
+            if sorted[i] as u32 != sorted[i - 1] as u32 + 1 {
+                ranges.index_mut(ranges.len() - 1).end = sorted[i - 1] as u32 + 1;
+                ranges.push(sorted[i] as u32..U8_SIZE)
+            }
+            i += 1;
+        }
+
+        ranges.index_mut(ranges.len() - 1).end = sorted[sorted.len() - 1] as u32 + 1;
+        ranges
+    }
+
+    pub const fn bytes(&self) -> &[u8; N] {
+        &self.bytes
+    }
+
+    #[inline(always)]
+    pub const fn contains(&self, char: u32) -> bool {
+        if self.ranges.is_empty() {
+            return false;
+        }
+
+        let mut i = self.ranges.len() - 1;
+        loop {
+            if char >= self.ranges.index(i).end {
+                return false;
+            }
+            if char >= self.ranges.index(i).start {
+                return true;
+            }
+            if i == 0 {
+                return false;
+            }
+            i -= 1;
+        }
+    }
+
+    /// Quickly eliminate vectors for which none of the elements are in this alphabet.
+    #[inline(always)]
+    pub fn simd_prefilter<const L: usize>(&self, chars: Simd<u32, L>) -> bool
+    where
+        LaneCount<L>: SupportedLaneCount,
+        Simd<u32, L>: SimdPartialEq<Mask = Mask<i32, L>>,
+    {
+        if self.ranges.is_empty() {
+            return false;
+        }
+
+        let alphabet_end = self.ranges[self.ranges.len() - 1].end;
+        chars.simd_lt(Simd::splat(alphabet_end)).any()
+    }
+
+    /// Split the characters of the alphabet into `floor(N/L)` SIMD vectors and a remainder
+    /// array of less `N % L` elements.
+    pub const fn simd_chunks<const L: usize>(&self) -> (ConstVec<Simd<u32, L>, N>, ConstVec<u32, L>)
+    where
+        LaneCount<L>: SupportedLaneCount,
+    {
+        let mut simd = ConstVec::new();
+
+        let mut i = 0;
+        loop {
+            let mut chunk = [0u32; L];
+            let mut j = 0;
+
+            while j < L && i < self.bytes.len() {
+                chunk[j] = self.bytes[i] as u32;
+                j += 1;
+                i += 1;
+            }
+
+            if j < L {
+                return (simd, ConstVec::from_slice_range(&chunk, 0..j));
+            } else {
+                simd.push(Simd::from_array(chunk));
+            }
+        }
+    }
+}
+
+/// A duplicate character was found while building an alphabet.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DuplicateChar(pub u8);
+
+impl std::fmt::Display for DuplicateChar {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "duplicate character '{}' in alphabet",
+            self.0.escape_ascii()
+        )
+    }
+}
+
+impl std::error::Error for DuplicateChar {}
+
+/// Runtime-preprocessed alphabet.
+///
+/// Runtime equivalent of [`Alphabet`] for alphabets that aren't known at compile time. The
+/// widened character table, ranges and membership bitmap are computed once on construction so
+/// that the hot loop doesn't do any more work than with a const [`Alphabet`].
+///
+/// Unlike [`Alphabet`], it remembers the order its characters were given in, which the CPU
+/// search explores them in.
+#[derive(Debug, Clone)]
+pub struct DynAlphabet {
+    bytes: Vec<u8>,
+    order: Vec<u8>,
+    /// Widened characters, in reverse order since the DFS pushes them on its stack in turn and
+    /// pops the last one first.
+    pub(crate) wide: Vec<u32>,
+    pub(crate) wide64: Vec<u64>,
+    ranges: Vec<Range<u32>>,
+    bitmap: [u64; 4],
+}
+
+impl DynAlphabet {
+    pub fn new(bytes: &[u8]) -> Result<Self, DuplicateChar> {
+        let mut sorted = bytes.to_vec();
+        sorted.sort_unstable();
+
+        if let Some(w) = sorted.windows(2).find(|w| w[0] == w[1]) {
+            return Err(DuplicateChar(w[0]));
+        }
+
+        let mut ranges: Vec<Range<u32>> = Vec::new();
+        let mut bitmap = [0u64; 4];
+        for &b in &sorted {
+            let b = b as u32;
+            bitmap[(b / 64) as usize] |= 1 << (b % 64);
+            match ranges.last_mut() {
+                Some(r) if r.end == b => r.end += 1,
+                _ => ranges.push(b..b + 1),
+            }
+        }
+
+        Ok(Self {
+            wide: bytes.iter().rev().map(|&b| b as u32).collect(),
+            wide64: bytes.iter().rev().map(|&b| b as u64).collect(),
+            order: bytes.to_vec(),
+            bytes: sorted,
+            ranges,
+            bitmap,
+        })
+    }
+
+    /// Characters in ascending order.
+    pub fn bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+
+    /// Characters in the order they were given in.
+    pub fn order(&self) -> &[u8] {
+        &self.order
+    }
+
+    pub fn ranges(&self) -> &[Range<u32>] {
+        &self.ranges
+    }
+
+    #[inline(always)]
+    pub fn contains<H: HashWord>(&self, char: H) -> bool {
+        char.to_char()
+            .is_some_and(|c| (self.bitmap[(c / 64) as usize] >> (c % 64)) & 1 != 0)
+    }
+
+    /// Quickly eliminate vectors for which none of the elements are in this alphabet.
+    #[inline(always)]
+    pub fn simd_prefilter<H: HashWord, const L: usize>(&self, chars: Simd<H, L>) -> bool
+    where
+        LaneCount<L>: SupportedLaneCount,
+        Simd<H, L>: SimdPartialOrd + SimdPartialEq<Mask = Mask<H::Mask, L>>,
+    {
+        match self.ranges.last() {
+            Some(last) => chars.simd_lt(Simd::splat(H::from_u32(last.end))).any(),
+            None => false,
+        }
+    }
+
+    /// Split the characters of the alphabet into `floor(N/L)` SIMD-sized chunks and a remainder
+    /// slice of `N % L` elements.
+    ///
+    /// This borrows from the widened character tables built on construction, so unlike
+    /// [`Alphabet::simd_chunks`] it is free to call for any lane count and hash type.
+    #[inline(always)]
+    pub fn simd_chunks<H: HashWord, const L: usize>(&self) -> (&[[H; L]], &[H])
+    where
+        LaneCount<L>: SupportedLaneCount,
+    {
+        H::widened(self).as_chunks()
+    }
+
+    /// Like [`DynAlphabet::simd_chunks`], but for an alphabet known to have `N` characters so
+    /// that loops over the chunks get a constant trip count and can be unrolled. `N = 0` stands
+    /// for an alphabet of any size.
+    ///
+    /// # Panics
+    /// If `N` is not 0 and the alphabet doesn't have `N` characters.
+    #[inline(always)]
+    pub fn simd_chunks_sized<H: HashWord, const N: usize, const L: usize>(
+        &self,
+    ) -> (&[[H; L]], &[H])
+    where
+        LaneCount<L>: SupportedLaneCount,
+    {
+        let wide = H::widened(self);
+        if N == 0 {
+            return wide.as_chunks();
+        }
+        let sized: &[H; N] = wide.try_into().expect("alphabet size doesn't match N");
+        sized.as_chunks()
+    }
+}
+
+impl<const N: usize> From<&Alphabet<N>> for DynAlphabet {
+    fn from(value: &Alphabet<N>) -> Self {
+        Self::new(value.bytes()).unwrap()
+    }
+}
@@ -0,0 +1,114 @@
+use std::fmt::Display;
+
+use crate::{HashFlavor, alphabet::DynAlphabet};
+
+/// What to search for: every string `m` of `min_len` to `max_len` characters, all from
+/// `alphabet` except the last one which is from `final_alphabet`, such that
+///
+/// ```text
+/// hash.hash(prefix|m|suffix) is in targets
+/// ```
+#[derive(Debug, Clone)]
+pub struct SearchConfig {
+    pub prefix: Vec<u8>,
+    pub suffix: Vec<u8>,
+    /// Target hashes, widened to `u64`s. There must be at least one.
+    pub targets: Vec<u64>,
+    pub hash: HashFlavor,
+    pub alphabet: DynAlphabet,
+    pub final_alphabet: DynAlphabet,
+    pub min_len: usize,
+    pub max_len: usize,
+}
+
+/// What to search for by meeting in the middle: every string `m` with one character of each of
+/// `positions`, such that
+///
+/// ```text
+/// hash.hash(prefix|m|suffix) is in targets
+/// ```
+#[derive(Debug, Clone)]
+pub struct MitmConfig {
+    pub prefix: Vec<u8>,
+    pub suffix: Vec<u8>,
+    /// Target hashes, widened to `u64`s. There must be at least one.
+    pub targets: Vec<u64>,
+    pub hash: HashFlavor,
+    pub positions: Vec<DynAlphabet>,
+}
+
+impl MitmConfig {
+    /// Number of positions in the front half, whose combinations are hashed into a table, such
+    /// that the larger of the two halves has as few combinations as possible. Ties go to the
+    /// smaller front half, which sizes the table.
+    pub fn split(&self) -> usize {
+        let larger = |k| combinations(&self.positions[..k]).max(combinations(&self.positions[k..]));
+        (0..=self.positions.len())
+            .min_by(|&a, &b| larger(a).total_cmp(&larger(b)))
+            .unwrap()
+    }
+}
+
+/// Number of strings with one character of each of `positions`.
+pub fn combinations(positions: &[DynAlphabet]) -> f64 {
+    positions.iter().map(|p| p.bytes().len() as f64).product()
+}
+
+/// A backend could not run a search.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BackendError(pub String);
+
+impl Display for BackendError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for BackendError {}
+
+/// An implementation of the collision search.
+///
+/// Backends are interchangeable: given the same [`SearchConfig`], any backend which
+/// [supports](Backend::supports) it returns the same set of matches, possibly in a different
+/// order.
+pub trait Backend {
+    /// Short name of the backend, as accepted by `--backend`.
+    fn name(&self) -> &'static str;
+
+    /// Length of the shortest middle this backend can find. Shorter lengths have to be
+    /// searched with another backend.
+    fn min_len(&self) -> usize {
+        0
+    }
+
+    /// Description of the device this backend runs on, for reports.
+    fn device(&self) -> String {
+        self.name().to_owned()
+    }
+
+    /// Check whether this backend can run `cfg`, returning the reason if it can't.
+    fn supports(&self, cfg: &SearchConfig) -> Result<(), BackendError>;
+
+    /// Run the search described by `cfg`, returning the middle `m` of every match.
+    fn search(&mut self, cfg: &SearchConfig) -> Result<Vec<Vec<u8>>, BackendError>;
+
+    /// Check whether this backend can run the meet-in-the-middle search `cfg`, returning the
+    /// reason if it can't. Backends can't by default.
+    fn supports_mitm(&self, cfg: &MitmConfig) -> Result<(), BackendError> {
+        let _ = cfg;
+        Err(BackendError(format!(
+            "the {} backend can't meet in the middle",
+            self.name()
+        )))
+    }
+
+    /// Run the meet-in-the-middle search described by `cfg`, returning the middle `m` of every
+    /// match.
+    fn search_mitm(&mut self, cfg: &MitmConfig) -> Result<Vec<Vec<u8>>, BackendError> {
+        self.supports_mitm(cfg)?;
+        Err(BackendError(format!(
+            "the {} backend can't meet in the middle",
+            self.name()
+        )))
+    }
+}
@@ -0,0 +1,57 @@
+//! Bloom filter letting searches for large target sets skip most of their exact lookups.
+
+/// Number of targets from which searches prefilter candidates with a [`BloomFilter`]. Below
+/// this, the sorted target list fits in cache and a binary search through it is cheap enough.
+pub const BLOOM_MIN_TARGETS: usize = 1 << 12;
+
+/// Filter bits per key, giving a false positive rate of about 1.5%.
+const BITS_PER_KEY: usize = 16;
+
+/// A blocked Bloom filter over `u64` keys.
+///
+/// Each key sets two bits of a single 64-bit word, so that lookups only ever touch one cache
+/// line. The OpenCL kernel implements the same lookup over [`BloomFilter::words`].
+#[derive(Debug, Clone)]
+pub struct BloomFilter {
+    words: Vec<u64>,
+}
+
+impl BloomFilter {
+    /// An empty filter sized for `capacity` keys.
+    pub fn with_capacity(capacity: usize) -> Self {
+        let len = (capacity * BITS_PER_KEY).div_ceil(64).next_power_of_two();
+        Self {
+            words: vec![0; len],
+        }
+    }
+
+    pub fn insert(&mut self, key: u64) {
+        let (word, bits) = self.locate(key);
+        self.words[word] |= bits;
+    }
+
+    /// Whether `key` may have been inserted. This is always true if it was.
+    #[inline(always)]
+    pub fn may_contain(&self, key: u64) -> bool {
+        let (word, bits) = self.locate(key);
+        self.words[word] & bits == bits
+    }
+
+    /// Words of the filter, a power of two of them.
+    pub fn words(&self) -> &[u64] {
+        &self.words
+    }
+
+    /// Index of the word holding the bits of `key`, and the mask of these bits.
+    #[inline(always)]
+    fn locate(&self, key: u64) -> (usize, u64) {
+        // finalizer of MurmurHash3, so that nearby keys spread over the whole filter
+        let mut h = key;
+        h ^= h >> 33;
+        h = h.wrapping_mul(0xff51afd7ed558ccd);
+        h ^= h >> 33;
+
+        let word = h as usize & (self.words.len() - 1);
+        (word, 1 << ((h >> 52) & 63) | 1 << (h >> 58))
+    }
+}
@@ -21,6 +21,12 @@ impl<T: Clone, const N: usize> Clone for ConstVec<T, N> {
 
 impl<T: Copy, const N: usize> Copy for ConstVec<T, N> {}
 
+impl<T, const N: usize> Default for ConstVec<T, N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl<T, const N: usize> ConstVec<T, N> {
     pub const fn new() -> Self {
         Self {
@@ -0,0 +1,59 @@
+//! Substrings which the searched characters of names must contain.
+
+/// A substring to look for, matched with the Knuth-Morris-Pratt algorithm so that searches can
+/// follow how much of it a partial string ends with, and tell how many more characters it needs
+/// at least.
+#[derive(Debug, Clone)]
+pub struct Substring {
+    needle: Vec<u8>,
+    /// Length of the longest proper prefix of `needle[..=i]` which is also a suffix of it.
+    failure: Vec<usize>,
+}
+
+impl Substring {
+    pub fn new(needle: &[u8]) -> Self {
+        let mut failure = vec![0; needle.len()];
+        let mut k = 0;
+        for i in 1..needle.len() {
+            while k > 0 && needle[i] != needle[k] {
+                k = failure[k - 1];
+            }
+            if needle[i] == needle[k] {
+                k += 1;
+            }
+            failure[i] = k;
+        }
+        Self {
+            needle: needle.to_owned(),
+            failure,
+        }
+    }
+
+    /// State after `text` following `state`: the length of the longest prefix of the substring
+    /// the characters so far end with, or its full length once it was found. The initial state
+    /// is 0.
+    pub fn advance(&self, mut state: usize, text: &[u8]) -> usize {
+        for &c in text {
+            if state == self.needle.len() {
+                break;
+            }
+            while state > 0 && c != self.needle[state] {
+                state = self.failure[state - 1];
+            }
+            if c == self.needle[state] {
+                state += 1;
+            }
+        }
+        state
+    }
+
+    /// Minimum number of characters to add after `state` for the substring to be found.
+    pub fn missing(&self, state: usize) -> usize {
+        self.needle.len() - state
+    }
+
+    /// Whether `text` contains the substring.
+    pub fn is_in(&self, text: &[u8]) -> bool {
+        self.advance(0, text) == self.needle.len()
+    }
+}
@@ -0,0 +1,92 @@
+//! Estimates of the number of matches in a search space, sampled from the space rather than
+//! derived from its size alone. Hashes of short names or of names from small alphabets are far
+//! from uniformly distributed, so the analytic estimate can be off by orders of magnitude.
+
+use crate::{HashFlavor, alphabet::DynAlphabet, rng::SplitMix64};
+
+/// Number of hits the samples are expected to score against the targets, which sets how many
+/// leading bits of the hashes are compared.
+const EXPECTED_HITS: f64 = 1000.0;
+
+/// Estimate of the number of matches among the names of a search space.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct DensityEstimate {
+    /// Number of names in the space.
+    pub candidates: f64,
+    /// Expected number of matches if the hashes of the names were uniformly distributed.
+    pub analytic: f64,
+    /// Expected number of matches measured from the samples.
+    pub sampled: f64,
+    pub samples: usize,
+    /// Number of pairs of a sample and a target whose hashes share their leading `bits`.
+    pub hits: usize,
+    pub bits: u32,
+}
+
+impl DensityEstimate {
+    /// Add the estimate of a disjoint space, so that `self` covers both.
+    pub fn add(&mut self, other: &Self) {
+        self.candidates += other.candidates;
+        self.analytic += other.analytic;
+        self.sampled += other.sampled;
+        self.samples += other.samples;
+        self.hits += other.hits;
+        self.bits = self.bits.max(other.bits);
+    }
+}
+
+/// Estimate how many names `prefix|m|suffix` hash to one of `targets`, where `m` has one
+/// character of each of `positions`, from `samples` names drawn at random with `rng`.
+///
+/// Full collisions are far too rare to be sampled, so the samples are only compared to the
+/// targets on the leading bits of their hashes, as many as leave about [`EXPECTED_HITS`] hits.
+/// The remaining bits are assumed to match uniformly.
+pub fn estimate(
+    prefix: &[u8],
+    positions: &[&DynAlphabet],
+    suffix: &[u8],
+    targets: &[u64],
+    flavor: &HashFlavor,
+    samples: usize,
+    rng: &mut SplitMix64,
+) -> DensityEstimate {
+    let width = 8 * flavor.width.bytes() as u32;
+    let candidates: f64 = positions.iter().map(|p| p.bytes().len() as f64).product();
+    let analytic = candidates * targets.len() as f64 / 2f64.powi(width as i32);
+
+    let bits = (samples as f64 * targets.len() as f64 / EXPECTED_HITS)
+        .log2()
+        .floor()
+        .clamp(0.0, width as f64) as u32;
+    let key = |hash: u64| hash.checked_shr(width - bits).unwrap_or(0);
+    let mut keys: Vec<_> = targets.iter().map(|&t| key(t)).collect();
+    keys.sort_unstable();
+
+    let mut name = prefix.to_vec();
+    let mut hits = 0;
+    for _ in 0..samples {
+        name.truncate(prefix.len());
+        name.extend(positions.iter().map(|p| {
+            let chars = p.bytes();
+            chars[(rng.next_u64() % chars.len() as u64) as usize]
+        }));
+        name.extend_from_slice(suffix);
+
+        let key = key(flavor.hash(&name));
+        let start = keys.partition_point(|&k| k < key);
+        hits += keys[start..].iter().take_while(|&&k| k == key).count();
+    }
+
+    let sampled = match samples {
+        0 => analytic,
+        _ => candidates * hits as f64 / samples as f64 / 2f64.powi((width - bits) as i32),
+    };
+    DensityEstimate {
+        candidates,
+        analytic,
+        sampled,
+        samples,
+        hits,
+        bits,
+    }
+}
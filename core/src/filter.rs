@@ -0,0 +1,113 @@
+/// Post-filters rejecting low-diversity matches such as `aaaaaaa1`.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct MatchFilter {
+    /// Minimum number of distinct characters in the middle of a match.
+    pub min_distinct: usize,
+    /// Minimum Shannon entropy of the middle of a match, in bits per character.
+    pub min_entropy: f64,
+}
+
+impl MatchFilter {
+    pub fn accepts(&self, middle: &[u8]) -> bool {
+        distinct_chars(middle) >= self.min_distinct && entropy(middle) >= self.min_entropy
+    }
+}
+
+/// Rules on the characters of names which real file names follow, such as not starting with `_`
+/// or not repeating a character three times in a row.
+#[derive(Debug, Default, Clone)]
+pub struct CharRules {
+    /// Longest allowed run of consecutive ASCII digits.
+    pub max_digit_run: Option<usize>,
+    /// Longest allowed run of the same character.
+    pub max_repeat: Option<usize>,
+    /// Characters names may not start with.
+    pub no_leading: Vec<u8>,
+    /// Whether the letters of each word, a run of ASCII letters, must be all lowercase or all
+    /// uppercase.
+    pub consistent_case: bool,
+}
+
+/// State of [`CharRules`] after some characters which follow them.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct RulesState {
+    /// Last character, unless there were none.
+    last: Option<u8>,
+    /// Number of times `last` was repeated in a row.
+    repeat: usize,
+    /// Number of digits in a row at the end.
+    digits: usize,
+    /// Whether the word at the end is uppercase, unless the text doesn't end with a letter.
+    upper: Option<bool>,
+}
+
+impl CharRules {
+    /// Whether every string follows the rules.
+    pub fn is_empty(&self) -> bool {
+        self.max_digit_run.is_none()
+            && self.max_repeat.is_none()
+            && self.no_leading.is_empty()
+            && !self.consistent_case
+    }
+
+    /// State after `text` following `state`, or `None` if the characters break a rule, which
+    /// no continuation can fix. The initial state is [`RulesState::default`].
+    pub fn advance(&self, mut state: RulesState, text: &[u8]) -> Option<RulesState> {
+        for &c in text {
+            if state.last.is_none() && self.no_leading.contains(&c) {
+                return None;
+            }
+            state.repeat = match state.last == Some(c) {
+                true => state.repeat + 1,
+                false => 1,
+            };
+            state.digits = match c.is_ascii_digit() {
+                true => state.digits + 1,
+                false => 0,
+            };
+            let upper = c.is_ascii_alphabetic().then(|| c.is_ascii_uppercase());
+            if self.consistent_case && state.upper.zip(upper).is_some_and(|(a, b)| a != b) {
+                return None;
+            }
+            state.upper = upper;
+            state.last = Some(c);
+            if self.max_repeat.is_some_and(|max| state.repeat > max)
+                || self.max_digit_run.is_some_and(|max| state.digits > max)
+            {
+                return None;
+            }
+        }
+        Some(state)
+    }
+
+    pub fn accepts(&self, text: &[u8]) -> bool {
+        self.advance(RulesState::default(), text).is_some()
+    }
+}
+
+/// Number of distinct bytes in `bytes`.
+pub fn distinct_chars(bytes: &[u8]) -> usize {
+    let mut seen = [false; 256];
+    bytes
+        .iter()
+        .filter(|&&b| !std::mem::replace(&mut seen[b as usize], true))
+        .count()
+}
+
+/// Shannon entropy of the byte distribution of `bytes`, in bits per byte.
+pub fn entropy(bytes: &[u8]) -> f64 {
+    let mut counts = [0usize; 256];
+    for &b in bytes {
+        counts[b as usize] += 1;
+    }
+
+    let len = bytes.len() as f64;
+    counts
+        .iter()
+        .filter(|&&c| c != 0)
+        .map(|&c| {
+            let p = c as f64 / len;
+            -p * p.log2()
+        })
+        .sum()
+}
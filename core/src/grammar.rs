@@ -0,0 +1,214 @@
+use std::{collections::HashMap, str::FromStr};
+
+use crate::alphabet::DynAlphabet;
+
+/// One element of an alternative of a [`Rule`].
+#[derive(Debug, Clone)]
+pub enum Item {
+    /// Text that is always the same.
+    Literal(Vec<u8>),
+    /// Any character of the class.
+    Class(DynAlphabet),
+    /// Any expansion of the rule with this index.
+    Rule(usize),
+}
+
+#[derive(Debug, Clone)]
+pub struct Rule {
+    pub name: String,
+    /// Sequences of items the rule can expand to.
+    pub alternatives: Vec<Vec<Item>>,
+}
+
+/// Small grammar describing the structure of names, one rule per line:
+///
+/// ```text
+/// # map pieces, e.g. m10_00_00_00/m10_00_00_00_001.flver
+/// file := map "/" map "_" digit digit digit ".flver"
+/// map := "m" digit digit "_" digit digit "_00_00"
+/// digit := [0-9]
+/// ```
+///
+/// Names are expanded from the first rule. Items are `"literals"` (where `\` escapes the next
+/// character), character classes such as `[a-z_]`, and names of other rules. Alternatives are
+/// separated by `|`, and lines starting with `#` are comments. Rules can't refer to
+/// themselves, directly or not, so that the grammar only describes finitely many names.
+#[derive(Debug, Clone)]
+pub struct Grammar {
+    rules: Vec<Rule>,
+}
+
+impl Grammar {
+    pub fn rules(&self) -> &[Rule] {
+        &self.rules
+    }
+
+    /// The rule names are expanded from.
+    pub fn start(&self) -> &Rule {
+        &self.rules[0]
+    }
+
+    /// Check that no rule can expand to itself.
+    fn check_acyclic(&self) -> Result<(), String> {
+        #[derive(Clone, Copy, PartialEq)]
+        enum Visit {
+            New,
+            InProgress,
+            Done,
+        }
+
+        fn visit(rules: &[Rule], state: &mut [Visit], i: usize) -> Result<(), String> {
+            match state[i] {
+                Visit::Done => return Ok(()),
+                Visit::InProgress => {
+                    return Err(format!("rule `{}` refers to itself", rules[i].name));
+                }
+                Visit::New => state[i] = Visit::InProgress,
+            }
+            for item in rules[i].alternatives.iter().flatten() {
+                if let &Item::Rule(r) = item {
+                    visit(rules, state, r)?;
+                }
+            }
+            state[i] = Visit::Done;
+            Ok(())
+        }
+
+        let mut state = vec![Visit::New; self.rules.len()];
+        (0..self.rules.len()).try_for_each(|i| visit(&self.rules, &mut state, i))
+    }
+}
+
+impl FromStr for Grammar {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        // rule names are collected first, so that rules can refer to later ones
+        let mut bodies = Vec::new();
+        let mut indices = HashMap::new();
+        for (i, line) in s.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (name, body) = line
+                .split_once(":=")
+                .ok_or_else(|| format!("line {}: expected `name := items`", i + 1))?;
+            let name = name.trim();
+            if !is_identifier(name) {
+                return Err(format!("line {}: invalid rule name `{name}`", i + 1));
+            }
+            if indices.insert(name, bodies.len()).is_some() {
+                return Err(format!("line {}: rule `{name}` is already defined", i + 1));
+            }
+            bodies.push((i + 1, name, body));
+        }
+        if bodies.is_empty() {
+            return Err("grammar has no rules".into());
+        }
+
+        let rules = bodies
+            .iter()
+            .map(|&(line, name, body)| {
+                let alternatives =
+                    parse_body(body, &indices).map_err(|e| format!("line {line}: {e}"))?;
+                Ok(Rule {
+                    name: name.to_owned(),
+                    alternatives,
+                })
+            })
+            .collect::<Result<_, String>>()?;
+
+        let grammar = Self { rules };
+        grammar.check_acyclic()?;
+        Ok(grammar)
+    }
+}
+
+fn is_identifier(s: &str) -> bool {
+    let mut chars = s.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// Parse the alternatives on the right of `:=`.
+fn parse_body(body: &str, indices: &HashMap<&str, usize>) -> Result<Vec<Vec<Item>>, String> {
+    let mut alternatives = vec![Vec::new()];
+    let mut bytes = body.bytes().peekable();
+    while let Some(b) = bytes.next() {
+        let item = match b {
+            b if b.is_ascii_whitespace() => continue,
+            b'|' => {
+                alternatives.push(Vec::new());
+                continue;
+            }
+            b'"' => {
+                let mut literal = Vec::new();
+                loop {
+                    match bytes.next() {
+                        Some(b'"') => break,
+                        Some(b'\\') => literal.extend(bytes.next()),
+                        Some(b) => literal.push(b),
+                        None => return Err("unterminated literal".into()),
+                    }
+                }
+                Item::Literal(literal)
+            }
+            b'[' => {
+                let mut class = Vec::new();
+                loop {
+                    let first = match bytes.next() {
+                        Some(b']') => break,
+                        Some(b'\\') => bytes.next(),
+                        first => first,
+                    }
+                    .ok_or("unterminated character class")?;
+                    if bytes.next_if_eq(&b'-').is_none() {
+                        class.push(first);
+                        continue;
+                    }
+                    let last = match bytes.next() {
+                        Some(b'\\') => bytes.next(),
+                        last => last,
+                    }
+                    .ok_or("unterminated character class")?;
+                    if last < first {
+                        return Err(format!(
+                            "invalid range `{}-{}`",
+                            first.escape_ascii(),
+                            last.escape_ascii()
+                        ));
+                    }
+                    class.extend(first..=last);
+                }
+                if class.is_empty() {
+                    return Err("empty character class".into());
+                }
+                let class = DynAlphabet::new(&class).map_err(|e| {
+                    format!("duplicate character '{}' in class", e.0.escape_ascii())
+                })?;
+                Item::Class(class)
+            }
+            b if b.is_ascii_alphabetic() || b == b'_' => {
+                let mut name = vec![b];
+                while let Some(b) = bytes.next_if(|b| b.is_ascii_alphanumeric() || *b == b'_') {
+                    name.push(b);
+                }
+                let name = String::from_utf8(name).unwrap();
+                let &rule = indices
+                    .get(name.as_str())
+                    .ok_or_else(|| format!("unknown rule `{name}`"))?;
+                Item::Rule(rule)
+            }
+            b => return Err(format!("unexpected `{}`", b.escape_ascii())),
+        };
+        alternatives.last_mut().unwrap().push(item);
+    }
+
+    if alternatives.iter().any(Vec::is_empty) {
+        return Err("empty alternative (use `\"\"` for nothing)".into());
+    }
+    Ok(alternatives)
+}
@@ -0,0 +1,509 @@
+//! Hash math, alphabets and match types shared by the fs-hardblast binaries.
+
+#![feature(portable_simd)]
+
+pub mod alphabet;
+pub mod backend;
+pub mod bloom;
+pub mod const_vec;
+pub mod contains;
+pub mod density;
+pub mod filter;
+pub mod grammar;
+pub mod mask;
+pub mod matches;
+pub mod ngram;
+pub mod output;
+pub mod progress;
+pub mod regex;
+pub mod report;
+pub mod rng;
+#[cfg(feature = "serde")]
+pub mod serial;
+pub mod status;
+pub mod template;
+pub mod usage;
+
+use alphabet::DynAlphabet;
+use std::{
+    fmt::{self, Debug, Display, LowerHex, UpperHex},
+    simd::{SimdCast, SimdElement},
+    str::FromStr,
+};
+
+/// Integer type of the 32-bit hashes used before Elden Ring, which the OpenCL kernel works with.
+pub type Hash = u32;
+
+/// Note that this isn't the real FNV prime, but what FromSoft uses (133 for u64 hashes!).
+pub const FNV_PRIME: Hash = 37;
+
+/// Prime used by the 64-bit hashes of Elden Ring and later games.
+pub const FNV_PRIME_64: u64 = 133;
+
+pub const fn fnv_hash(data: &[u8]) -> Hash {
+    let mut hash: Hash = 0;
+    let mut i = 0;
+    while i < data.len() {
+        hash = hash.wrapping_mul(FNV_PRIME).wrapping_add(data[i] as Hash);
+        i += 1;
+    }
+    hash
+}
+
+/// 32-bit modular inverse of an odd `a` using 3 Newton-Raphson iterations :)
+///
+/// From <https://arxiv.org/abs/2204.04342>
+pub const fn minv32(a: u32) -> u32 {
+    assert!(!a.is_multiple_of(2));
+
+    let mut x = 3u32.wrapping_mul(a) ^ 2;
+    let mut y = 1u32.wrapping_sub(a.wrapping_mul(x));
+
+    x = x.wrapping_mul(y.wrapping_add(1));
+    y = y.wrapping_mul(y);
+    x = x.wrapping_mul(y.wrapping_add(1));
+    y = y.wrapping_mul(y);
+    x.wrapping_mul(y.wrapping_add(1))
+}
+
+/// 64-bit modular inverse of an odd `a`, using one more Newton-Raphson iteration than
+/// [`minv32`].
+pub const fn minv64(a: u64) -> u64 {
+    assert!(!a.is_multiple_of(2));
+
+    let mut x = 3u64.wrapping_mul(a) ^ 2;
+    let mut y = 1u64.wrapping_sub(a.wrapping_mul(x));
+
+    x = x.wrapping_mul(y.wrapping_add(1));
+    y = y.wrapping_mul(y);
+    x = x.wrapping_mul(y.wrapping_add(1));
+    y = y.wrapping_mul(y);
+    x = x.wrapping_mul(y.wrapping_add(1));
+    y = y.wrapping_mul(y);
+    x.wrapping_mul(y.wrapping_add(1))
+}
+
+// solving for the last character relies on the primes being invertible
+const _: () = assert!(FNV_PRIME.wrapping_mul(minv32(FNV_PRIME)) == 1);
+const _: () = assert!(FNV_PRIME_64.wrapping_mul(minv64(FNV_PRIME_64)) == 1);
+// as do searches with the real FNV primes, e.g. `--prime 0x01000193`
+const _: () = assert!(0x01000193u32.wrapping_mul(minv32(0x01000193)) == 1);
+const _: () = assert!(0x100000001b3u64.wrapping_mul(minv64(0x100000001b3)) == 1);
+
+/// Integer type a hash can be computed in.
+pub trait HashWord:
+    SimdElement + SimdCast + Eq + Debug + Display + LowerHex + UpperHex + Send + Sync + 'static
+{
+    const WIDTH: HashWidth;
+
+    fn from_u32(value: u32) -> Self;
+    /// Truncate a 64-bit hash to this type, returning `None` if it doesn't fit.
+    fn from_u64(value: u64) -> Option<Self>;
+    fn to_u64(self) -> u64;
+
+    fn wrapping_add(self, rhs: Self) -> Self;
+    fn wrapping_sub(self, rhs: Self) -> Self;
+    fn wrapping_mul(self, rhs: Self) -> Self;
+    fn wrapping_pow(self, exp: u32) -> Self;
+    fn xor(self, rhs: Self) -> Self;
+
+    /// Modular inverse of an odd value.
+    fn minv(self) -> Self;
+
+    /// Characters of `alphabet` widened to this type.
+    fn widened(alphabet: &DynAlphabet) -> &[Self];
+
+    /// The value as a character, if it fits in a byte.
+    #[inline(always)]
+    fn to_char(self) -> Option<u8> {
+        u8::try_from(self.to_u64()).ok()
+    }
+}
+
+macro_rules! impl_hash_word {
+    ($t:ty, $width:expr, $minv:path, $wide:ident) => {
+        impl HashWord for $t {
+            const WIDTH: HashWidth = $width;
+
+            #[inline(always)]
+            fn from_u32(value: u32) -> Self {
+                value as Self
+            }
+            #[inline(always)]
+            fn from_u64(value: u64) -> Option<Self> {
+                value.try_into().ok()
+            }
+            #[inline(always)]
+            fn to_u64(self) -> u64 {
+                self as u64
+            }
+            #[inline(always)]
+            fn wrapping_add(self, rhs: Self) -> Self {
+                self.wrapping_add(rhs)
+            }
+            #[inline(always)]
+            fn wrapping_sub(self, rhs: Self) -> Self {
+                self.wrapping_sub(rhs)
+            }
+            #[inline(always)]
+            fn wrapping_mul(self, rhs: Self) -> Self {
+                self.wrapping_mul(rhs)
+            }
+            #[inline(always)]
+            fn wrapping_pow(self, exp: u32) -> Self {
+                self.wrapping_pow(exp)
+            }
+            #[inline(always)]
+            fn xor(self, rhs: Self) -> Self {
+                self ^ rhs
+            }
+            fn minv(self) -> Self {
+                $minv(self)
+            }
+            #[inline(always)]
+            fn widened(alphabet: &DynAlphabet) -> &[Self] {
+                &alphabet.$wide
+            }
+        }
+    };
+}
+
+impl_hash_word!(u32, HashWidth::Bits32, minv32, wide);
+impl_hash_word!(u64, HashWidth::Bits64, minv64, wide64);
+
+/// Width of the hashes being searched, for code that handles both at runtime.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum HashWidth {
+    #[default]
+    Bits32,
+    Bits64,
+}
+
+impl HashWidth {
+    pub fn bytes(self) -> usize {
+        match self {
+            Self::Bits32 => size_of::<u32>(),
+            Self::Bits64 => size_of::<u64>(),
+        }
+    }
+
+    /// Number of hex digits needed to print any hash of this width.
+    pub fn hex_digits(self) -> usize {
+        2 * self.bytes()
+    }
+
+    /// Whether `hash` is a valid hash of this width.
+    pub fn fits(self, hash: u64) -> bool {
+        match self {
+            Self::Bits32 => u32::try_from(hash).is_ok(),
+            Self::Bits64 => true,
+        }
+    }
+}
+
+impl Display for HashWidth {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", 8 * self.bytes())
+    }
+}
+
+impl FromStr for HashWidth {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "32" => Ok(Self::Bits32),
+            "64" => Ok(Self::Bits64),
+            _ => Err(format!("unsupported hash width `{s}`, expected 32 or 64")),
+        }
+    }
+}
+
+/// How each byte is mixed into the hash.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum HashVariant {
+    /// `h = h * prime + byte`, as used by FromSoftware.
+    #[default]
+    MulAdd,
+    /// `h = (h ^ byte) * prime`, as used by FNV-1a.
+    Fnv1a,
+}
+
+impl Display for HashVariant {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::MulAdd => "mul-add",
+            Self::Fnv1a => "fnv1a",
+        })
+    }
+}
+
+impl FromStr for HashVariant {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "mul-add" => Ok(Self::MulAdd),
+            "fnv1a" => Ok(Self::Fnv1a),
+            _ => Err(format!(
+                "unknown hash variant `{s}`, expected mul-add or fnv1a"
+            )),
+        }
+    }
+}
+
+/// Parameters of a hash computed by mixing each byte of its input into `h` as described by
+/// `variant`, starting from `h = basis`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Fnv<H> {
+    pub variant: HashVariant,
+    pub prime: H,
+    pub basis: H,
+}
+
+impl<H: HashWord> Fnv<H> {
+    pub fn hash(&self, data: &[u8]) -> H {
+        self.extend(self.basis, data)
+    }
+
+    /// Hash of `base|data`, given `hash(base)`.
+    pub fn extend(&self, hash: H, data: &[u8]) -> H {
+        data.iter().fold(hash, |hash, &b| {
+            let b = H::from_u32(b as u32);
+            match self.variant {
+                HashVariant::MulAdd => hash.wrapping_mul(self.prime).wrapping_add(b),
+                HashVariant::Fnv1a => hash.xor(b).wrapping_mul(self.prime),
+            }
+        })
+    }
+
+    /// Hash of `base`, given `hash(base|data)`. Each byte is undone with the inverse of the
+    /// prime, which must be odd.
+    pub fn unextend(&self, hash: H, data: &[u8]) -> H {
+        let prime_inv = self.prime.minv();
+        data.iter().rev().fold(hash, |hash, &b| {
+            let b = H::from_u32(b as u32);
+            match self.variant {
+                HashVariant::MulAdd => hash.wrapping_sub(b).wrapping_mul(prime_inv),
+                HashVariant::Fnv1a => hash.wrapping_mul(prime_inv).xor(b),
+            }
+        })
+    }
+}
+
+/// Hash function picked at runtime: its width and variant along with the prime and offset
+/// basis, widened to `u64`s.
+///
+/// Defaults to the 32-bit FromSoftware hash.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HashFlavor {
+    pub width: HashWidth,
+    pub variant: HashVariant,
+    pub prime: u64,
+    pub basis: u64,
+}
+
+impl HashFlavor {
+    /// The hash FromSoftware uses at the given width: a prime of 37 for 32-bit hashes and 133
+    /// for 64-bit ones, starting from 0.
+    pub fn fromsoft(width: HashWidth) -> Self {
+        let prime = match width {
+            HashWidth::Bits32 => FNV_PRIME as u64,
+            HashWidth::Bits64 => FNV_PRIME_64,
+        };
+        Self {
+            width,
+            variant: HashVariant::MulAdd,
+            prime,
+            basis: 0,
+        }
+    }
+
+    /// Check that the prime and basis fit the width, and that the prime is odd so that the
+    /// last character can be solved for.
+    pub fn validate(&self) -> Result<(), String> {
+        if !self.width.fits(self.prime) || !self.width.fits(self.basis) {
+            return Err(format!(
+                "prime and offset basis must fit in a {}-bit hash",
+                self.width
+            ));
+        }
+        if self.prime.is_multiple_of(2) {
+            return Err(format!("prime {:#x} must be odd", self.prime));
+        }
+        Ok(())
+    }
+
+    /// The parameters of this hash as `H` integers, which must be as wide as the hash.
+    pub fn typed<H: HashWord>(&self) -> Fnv<H> {
+        assert_eq!(
+            H::WIDTH,
+            self.width,
+            "hash type doesn't match the hash width"
+        );
+        Fnv {
+            variant: self.variant,
+            prime: H::from_u64(self.prime).unwrap(),
+            basis: H::from_u64(self.basis).unwrap(),
+        }
+    }
+
+    /// Hash `data`, widened to a `u64`.
+    pub fn hash(&self, data: &[u8]) -> u64 {
+        match self.width {
+            HashWidth::Bits32 => self.typed::<u32>().hash(data).to_u64(),
+            HashWidth::Bits64 => self.typed::<u64>().hash(data).to_u64(),
+        }
+    }
+}
+
+impl Default for HashFlavor {
+    fn default() -> Self {
+        Self::fromsoft(HashWidth::Bits32)
+    }
+}
+
+/// Precomputed information about the hash of a suffix.
+///
+/// Used to efficiently compute the combined hash of `base|suffix` given `hash(base)`
+/// as well as efficiently finding a single character `x` such that
+/// `hash(base|x|suffix) == target_hash`.
+///
+/// `hash` and `mult` only apply to [`HashVariant::MulAdd`] hashes, for which
+/// `hash(base|suffix) == hash(base) * mult + hash`. The meaning of `target_shift` depends on the
+/// variant: `x` is `target_shift - hash(base) * prime` for mul-add hashes, and
+/// `target_shift ^ hash(base)` for FNV-1a ones.
+#[derive(Debug, Clone, Copy)]
+pub struct PrecomputedSuffix<H = Hash> {
+    pub hash: H,
+    pub mult: H,
+    pub target_shift: H,
+}
+
+impl<H: HashWord> PrecomputedSuffix<H> {
+    pub fn new(suffix: &[u8], target_hash: H, fnv: &Fnv<H>) -> Self {
+        let hash = fnv.extend(H::from_u32(0), suffix);
+        let mult = fnv.prime.wrapping_pow(suffix.len() as u32);
+        let target_shift = match fnv.variant {
+            HashVariant::MulAdd => target_hash.wrapping_sub(hash).wrapping_mul(mult.minv()),
+            // undo the suffix one byte at a time, then the multiplication following `x`
+            HashVariant::Fnv1a => {
+                let prime_inv = fnv.prime.minv();
+                suffix
+                    .iter()
+                    .rev()
+                    .fold(target_hash, |h, &b| {
+                        h.wrapping_mul(prime_inv).xor(H::from_u32(b as u32))
+                    })
+                    .wrapping_mul(prime_inv)
+            }
+        };
+
+        Self {
+            hash,
+            mult,
+            target_shift,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rng::SplitMix64;
+
+    /// Primes whose inverses the searches rely on: FromSoftware's, and the real FNV ones.
+    const PRIMES_32: [u32; 2] = [FNV_PRIME, 0x01000193];
+    const PRIMES_64: [u64; 2] = [FNV_PRIME_64, 0x100000001b3];
+
+    #[test]
+    #[cfg_attr(
+        debug_assertions,
+        ignore = "takes minutes unoptimized, run with --release"
+    )]
+    fn minv32_inverts_every_odd_value() {
+        let wrong = (1..=u32::MAX)
+            .step_by(2)
+            .filter(|&a| a.wrapping_mul(minv32(a)) != 1)
+            .count();
+        assert_eq!(wrong, 0);
+    }
+
+    #[test]
+    fn minv64_inverts_odd_values() {
+        let mut rng = SplitMix64::new(1);
+        let edges = [1, 3, u64::MAX, u64::MAX - 2, 1 << 63 | 1, u32::MAX as u64];
+        let random = (0..1 << 20).map(|_| rng.next_u64() | 1);
+        for a in PRIMES_64.into_iter().chain(edges).chain(random) {
+            assert_eq!(a.wrapping_mul(minv64(a)), 1, "wrong inverse of {a:#x}");
+        }
+    }
+
+    /// Check that the character solved for from [`PrecomputedSuffix::target_shift`] is the one
+    /// that was hashed, that [`Fnv::unextend`] undoes the suffix, and that `hash` and `mult`
+    /// combine like the suffix does.
+    fn check_round_trips<H: HashWord>(fnv: Fnv<H>, rng: &mut SplitMix64) {
+        let random_hash = |rng: &mut SplitMix64| H::from_u64(rng.next_u64() & mask::<H>()).unwrap();
+        for _ in 0..1 << 12 {
+            let suffix: Vec<u8> = (0..rng.next_u64() % 12)
+                .map(|_| rng.next_u64() as u8)
+                .collect();
+            let base = random_hash(rng);
+            let x = rng.next_u64() as u8;
+            let target = fnv.extend(fnv.extend(base, &[x]), &suffix);
+
+            let pre = PrecomputedSuffix::new(&suffix, target, &fnv);
+            let solved = match fnv.variant {
+                HashVariant::MulAdd => pre.target_shift.wrapping_sub(base.wrapping_mul(fnv.prime)),
+                HashVariant::Fnv1a => pre.target_shift.xor(base),
+            };
+            assert_eq!(solved.to_u64(), x as u64, "{fnv:?}, suffix {suffix:?}");
+            assert_eq!(
+                fnv.unextend(target, &suffix),
+                fnv.extend(base, &[x]),
+                "{fnv:?}, suffix {suffix:?}"
+            );
+
+            if fnv.variant == HashVariant::MulAdd {
+                let combined = base.wrapping_mul(pre.mult).wrapping_add(pre.hash);
+                assert_eq!(combined, fnv.extend(base, &suffix), "{fnv:?}");
+            }
+        }
+    }
+
+    fn mask<H: HashWord>() -> u64 {
+        match H::WIDTH {
+            HashWidth::Bits32 => u32::MAX as u64,
+            HashWidth::Bits64 => u64::MAX,
+        }
+    }
+
+    #[test]
+    fn target_shift_round_trips() {
+        let mut rng = SplitMix64::new(2);
+        for variant in [HashVariant::MulAdd, HashVariant::Fnv1a] {
+            for prime in PRIMES_32 {
+                let basis = rng.next_u64() as u32;
+                check_round_trips(
+                    Fnv {
+                        variant,
+                        prime,
+                        basis,
+                    },
+                    &mut rng,
+                );
+            }
+            for prime in PRIMES_64 {
+                let basis = rng.next_u64();
+                check_round_trips(
+                    Fnv {
+                        variant,
+                        prime,
+                        basis,
+                    },
+                    &mut rng,
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,161 @@
+use std::fmt;
+
+use crate::alphabet::DynAlphabet;
+
+/// One position of a [`Mask`].
+#[derive(Debug, Clone)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum Slot {
+    /// A character that is always the same.
+    Fixed(u8),
+    /// Any character of the alphabet.
+    Set(DynAlphabet),
+}
+
+impl Slot {
+    /// Characters the slot may hold, as an alphabet.
+    pub fn alphabet(&self) -> DynAlphabet {
+        match self {
+            Self::Fixed(c) => DynAlphabet::new(&[*c]).unwrap(),
+            Self::Set(set) => set.clone(),
+        }
+    }
+}
+
+/// Hashcat-style mask giving the characters allowed at each position of a name, e.g.
+/// `c?d?d?d?d_?l?l?l.anibnd.dcx`.
+///
+/// Supported placeholders are `?l` (`a-z`), `?u` (`A-Z`), `?d` (`0-9`), `?h` (`0-9a-f`), `?H`
+/// (`0-9A-F`), `?s` (printable ASCII symbols and space), `?a` (all of the above) and `?1` (a
+/// custom alphabet). `??` stands for a literal `?`, and any other character for itself.
+///
+/// `%` followed by a count and a placeholder letter repeats the placeholder, so that the numeric
+/// IDs of FromSoftware names read as in `c%4d` or `m%2d_%2d_%2d_%2d`. `%%` stands for a literal
+/// `%`.
+///
+/// Placeholders may be split into several gaps by fixed characters, as in
+/// `/chr/c?d?d?d?d/c?d?d?d?d_a.anibnd.dcx`.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Mask {
+    text: String,
+    slots: Vec<Slot>,
+}
+
+impl Mask {
+    pub fn slots(&self) -> &[Slot] {
+        &self.slots
+    }
+
+    /// Split the mask into the fixed characters before its first set, the slots from its first
+    /// set to its last one, and the fixed characters after its last set.
+    pub fn split(&self) -> (Vec<u8>, &[Slot], Vec<u8>) {
+        let is_set = |s: &Slot| matches!(s, Slot::Set(_));
+        let start = self
+            .slots
+            .iter()
+            .position(is_set)
+            .unwrap_or(self.slots.len());
+        let end = self.slots.iter().rposition(is_set).map_or(start, |i| i + 1);
+
+        let fixed = |slots: &[Slot]| {
+            slots
+                .iter()
+                .map(|s| match s {
+                    Slot::Fixed(c) => *c,
+                    Slot::Set(_) => unreachable!(),
+                })
+                .collect()
+        };
+        (
+            fixed(&self.slots[..start]),
+            &self.slots[start..end],
+            fixed(&self.slots[end..]),
+        )
+    }
+
+    /// Number of names matching the mask.
+    pub fn candidates(&self) -> u128 {
+        self.slots
+            .iter()
+            .map(|s| match s {
+                Slot::Fixed(_) => 1,
+                Slot::Set(set) => set.bytes().len() as u128,
+            })
+            .fold(1, u128::saturating_mul)
+    }
+
+    /// Parse a mask, where `?1` stands for the characters of `custom`.
+    pub fn parse(s: &str, custom: &DynAlphabet) -> Result<Self, String> {
+        const LOWER: &[u8] = b"abcdefghijklmnopqrstuvwxyz";
+        const UPPER: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ";
+        const DIGITS: &[u8] = b"0123456789";
+        const SYMBOLS: &[u8] = b" !\"#$%&'()*+,-./:;<=>?@[\\]^_`{|}~";
+
+        let placeholder = |c: u8| {
+            let set = match c {
+                b'l' => LOWER.to_vec(),
+                b'u' => UPPER.to_vec(),
+                b'd' => DIGITS.to_vec(),
+                b'h' => [DIGITS, &LOWER[..6]].concat(),
+                b'H' => [DIGITS, &UPPER[..6]].concat(),
+                b's' => SYMBOLS.to_vec(),
+                b'a' => [LOWER, UPPER, DIGITS, SYMBOLS].concat(),
+                b'1' => custom.bytes().to_vec(),
+                other => {
+                    return Err(format!(
+                        "unknown mask placeholder letter `{}`",
+                        other.escape_ascii()
+                    ));
+                }
+            };
+            Ok(Slot::Set(DynAlphabet::new(&set).unwrap()))
+        };
+
+        let mut slots = Vec::new();
+        let mut bytes = s.bytes().peekable();
+        while let Some(b) = bytes.next() {
+            match b {
+                b'?' => match bytes.next() {
+                    Some(b'?') => slots.push(Slot::Fixed(b'?')),
+                    Some(c) => slots.push(placeholder(c)?),
+                    None => return Err("mask ends with `?` (use `??` for a literal)".into()),
+                },
+                b'%' => {
+                    if bytes.next_if_eq(&b'%').is_some() {
+                        slots.push(Slot::Fixed(b'%'));
+                        continue;
+                    }
+                    let mut count = String::new();
+                    while let Some(d) = bytes.next_if(u8::is_ascii_digit) {
+                        count.push(d as char);
+                    }
+                    let (Ok(count), Some(c)) = (count.parse::<usize>(), bytes.next()) else {
+                        return Err(
+                            "`%` must be followed by a count and a placeholder letter, as in \
+                            `%4d` (use `%%` for a literal)"
+                                .into(),
+                        );
+                    };
+                    let slot = placeholder(c)?;
+                    slots.extend(std::iter::repeat_n(slot, count));
+                }
+                _ => slots.push(Slot::Fixed(b)),
+            }
+        }
+        Ok(Self {
+            text: s.to_owned(),
+            slots,
+        })
+    }
+}
+
+impl fmt::Display for Mask {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.text)
+    }
+}
@@ -0,0 +1,97 @@
+use std::simd::{LaneCount, Simd, SupportedLaneCount};
+
+/// A byte string of up to 8 characters, packed big-endian into an integer.
+#[derive(Debug, Clone, Copy)]
+pub struct Match {
+    pub bytes_be: u64,
+    pub len: usize,
+}
+
+impl Match {
+    pub fn from_bytes(bytes: &[u8]) -> Self {
+        assert!(bytes.len() <= 8, "match too long");
+        Self {
+            bytes_be: bytes.iter().fold(0, |acc, &b| (acc << 8) | b as u64),
+            len: bytes.len(),
+        }
+    }
+
+    pub fn bytes(&self) -> [u8; 8] {
+        self.bytes_be
+            .rotate_right(8 * self.len as u32)
+            .to_be_bytes()
+    }
+
+    /// Number of distinct characters among the last `count` characters packed into this match.
+    ///
+    /// DFS stack entries only hold `len - 2` characters, hence the explicit count.
+    pub fn distinct_packed(&self, count: usize) -> usize {
+        let bytes = self.bytes_be.to_le_bytes();
+        let packed = &bytes[..count];
+        (0..count)
+            .filter(|&i| !packed[..i].contains(&packed[i]))
+            .count()
+    }
+}
+
+/// Struct-of-arrays storage for [`Match`]es.
+///
+/// Storing the packed bytes and lengths separately (and the lengths as `u8`) roughly halves the
+/// memory traffic compared to a `Vec<Match>`, and lets a whole SIMD vector of packed bytes be
+/// copied in at once.
+#[derive(Debug, Default, Clone)]
+pub struct MatchVec {
+    bytes_be: Vec<u64>,
+    lens: Vec<u8>,
+}
+
+impl MatchVec {
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            bytes_be: Vec::with_capacity(capacity),
+            lens: Vec::with_capacity(capacity),
+        }
+    }
+
+    #[inline(always)]
+    pub fn push(&mut self, m: Match) {
+        self.bytes_be.push(m.bytes_be);
+        self.lens.push(m.len as u8);
+    }
+
+    #[inline(always)]
+    pub fn pop(&mut self) -> Option<Match> {
+        Some(Match {
+            bytes_be: self.bytes_be.pop()?,
+            len: self.lens.pop()? as usize,
+        })
+    }
+
+    /// Push `L` matches of the same length at once.
+    #[inline(always)]
+    pub fn extend_simd<const L: usize>(&mut self, bytes_be: Simd<u64, L>, len: usize)
+    where
+        LaneCount<L>: SupportedLaneCount,
+    {
+        self.bytes_be.extend_from_slice(bytes_be.as_array());
+        self.lens.resize(self.lens.len() + L, len as u8);
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = Match> + '_ {
+        self.bytes_be
+            .iter()
+            .zip(&self.lens)
+            .map(|(&bytes_be, &len)| Match {
+                bytes_be,
+                len: len as usize,
+            })
+    }
+}
+
+impl Extend<Match> for MatchVec {
+    fn extend<T: IntoIterator<Item = Match>>(&mut self, iter: T) {
+        for m in iter {
+            self.push(m);
+        }
+    }
+}
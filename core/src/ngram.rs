@@ -0,0 +1,122 @@
+//! Character n-gram model of known names, scoring how plausible partial strings are.
+
+use std::collections::HashMap;
+
+/// Highest supported order. Contexts and characters are packed into `u64` keys, one byte each.
+pub const MAX_ORDER: usize = 8;
+
+/// Character n-gram model with add-one smoothing.
+///
+/// The probability of each character is conditioned on the `order - 1` characters before it,
+/// where the start of a training name counts as a `\0` character.
+#[derive(Debug, Clone)]
+pub struct NgramModel {
+    order: usize,
+    /// `log2 P(c | context)` for every character seen after each context, keyed by
+    /// `context << 8 | c`.
+    log_probs: HashMap<u64, f32>,
+    /// `log2 P(c | context)` of the characters never seen after each context.
+    unseen_log_probs: HashMap<u64, f32>,
+    /// `log2 P(c | context)` for contexts never seen at all.
+    unknown_log_prob: f32,
+}
+
+impl NgramModel {
+    /// Train a model of the given order on `names`.
+    pub fn train<'a>(order: usize, names: impl IntoIterator<Item = &'a [u8]>) -> Self {
+        assert!(
+            (1..=MAX_ORDER).contains(&order),
+            "n-gram order must be between 1 and {MAX_ORDER}"
+        );
+
+        let mut counts: HashMap<u64, u32> = HashMap::new();
+        let mut context_counts: HashMap<u64, u32> = HashMap::new();
+        let mut vocab = [false; 256];
+        for name in names {
+            let mut context = 0;
+            for &c in name {
+                vocab[c as usize] = true;
+                *counts.entry(context << 8 | c as u64).or_default() += 1;
+                *context_counts.entry(context).or_default() += 1;
+                context = push_char(context, c, order);
+            }
+        }
+
+        // one more for the characters not seen at all
+        let vocab = vocab.iter().filter(|&&v| v).count() as f64 + 1.0;
+        let log_probs = counts
+            .iter()
+            .map(|(&key, &count)| {
+                let total = context_counts[&(key >> 8)] as f64;
+                (key, ((count as f64 + 1.0) / (total + vocab)).log2() as f32)
+            })
+            .collect();
+        let unseen_log_probs = context_counts
+            .iter()
+            .map(|(&context, &total)| (context, (1.0 / (total as f64 + vocab)).log2() as f32))
+            .collect();
+        Self {
+            order,
+            log_probs,
+            unseen_log_probs,
+            unknown_log_prob: (1.0 / vocab).log2() as f32,
+        }
+    }
+
+    pub fn order(&self) -> usize {
+        self.order
+    }
+
+    /// Mean log2-probability of the characters of `text`, each conditioned on the characters
+    /// before it in `context|text`. This is 0 for an empty `text`.
+    pub fn mean_log_prob(&self, context: &[u8], text: &[u8]) -> f64 {
+        if text.is_empty() {
+            return 0.0;
+        }
+
+        let mut context = self.context(context);
+        let mut total = 0.0;
+        for &c in text {
+            total += self.log_prob(context, c);
+            context = self.push(context, c);
+        }
+        total / text.len() as f64
+    }
+
+    /// The context the model conditions the character after `text` on.
+    pub fn context(&self, text: &[u8]) -> Context {
+        let start = text.len().saturating_sub(self.order - 1);
+        text[start..]
+            .iter()
+            .fold(Context(0), |context, &c| self.push(context, c))
+    }
+
+    /// The context following `context|c`.
+    pub fn push(&self, context: Context, c: u8) -> Context {
+        Context(push_char(context.0, c, self.order))
+    }
+
+    /// `log2 P(c | context)`.
+    pub fn log_prob(&self, context: Context, c: u8) -> f64 {
+        let log_prob = match self.log_probs.get(&(context.0 << 8 | c as u64)) {
+            Some(&log_prob) => log_prob,
+            None => *self
+                .unseen_log_probs
+                .get(&context.0)
+                .unwrap_or(&self.unknown_log_prob),
+        };
+        log_prob as f64
+    }
+}
+
+/// The last `order - 1` characters before a scored character, packed one byte each.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Context(u64);
+
+/// Append `c` to a packed context, keeping only the last `order - 1` characters.
+fn push_char(context: u64, c: u8, order: usize) -> u64 {
+    match order {
+        1 => 0,
+        _ => ((context << 8) | c as u64) & (u64::MAX >> (64 - 8 * (order - 1))),
+    }
+}
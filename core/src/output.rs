@@ -0,0 +1,18 @@
+use std::io::{self, Write};
+
+/// Write a match to stdout followed by a newline.
+///
+/// If `escape` is set, non-printable bytes are written as escape sequences so that alphabets
+/// containing control bytes can't garble the terminal. Otherwise, the bytes are written as-is,
+/// which is what we want when the output is redirected to a file.
+pub fn print_match(bytes: &[u8], escape: bool) {
+    let mut stdout = io::stdout().lock();
+    let result = if escape {
+        writeln!(stdout, "{}", bytes.escape_ascii())
+    } else {
+        stdout
+            .write_all(bytes)
+            .and_then(|_| stdout.write_all(b"\n"))
+    };
+    result.expect("failed to write match to stdout");
+}
@@ -0,0 +1,94 @@
+//! Throttled progress events, for hosts rendering the progress of a search without parsing its
+//! output.
+
+use std::{
+    fmt,
+    time::{Duration, Instant},
+};
+
+/// Progress of a search at some point of its run.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Progress {
+    /// Fraction of the chunks of the search that are complete, from 0 to 1.
+    pub fraction: f64,
+    /// Time since the [`ProgressReporter`] was created, which is when the search started.
+    pub elapsed: Duration,
+    /// Number of DFS nodes processed so far, on the CPU.
+    pub nodes: u64,
+    /// Number of DFS nodes processed per second, over the whole run.
+    pub throughput: f64,
+    /// Number of matches found so far, before filtering.
+    pub matches: usize,
+}
+
+/// Delivers [`Progress`] events to a callback, at most once per `min_interval` however often
+/// progress is reported, so that the callback's cost is bounded.
+pub struct ProgressReporter<'a> {
+    callback: Box<dyn FnMut(&Progress) + 'a>,
+    min_interval: Duration,
+    start: Instant,
+    last: Option<Instant>,
+    /// Whether progress was reported since the last delivered event.
+    pending: bool,
+}
+
+impl fmt::Debug for ProgressReporter<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ProgressReporter")
+            .field("min_interval", &self.min_interval)
+            .field("start", &self.start)
+            .field("last", &self.last)
+            .field("pending", &self.pending)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<'a> ProgressReporter<'a> {
+    /// Call `callback` with the progress of a search starting now, at most once per
+    /// `min_interval`.
+    pub fn new(callback: impl FnMut(&Progress) + 'a, min_interval: Duration) -> Self {
+        Self {
+            callback: Box::new(callback),
+            min_interval,
+            start: Instant::now(),
+            last: None,
+            pending: true,
+        }
+    }
+
+    /// Report progress, delivering it only if the last event was delivered at least
+    /// `min_interval` ago.
+    pub fn report(&mut self, fraction: f64, nodes: u64, matches: usize) {
+        let now = Instant::now();
+        if self
+            .last
+            .is_some_and(|last| now.duration_since(last) < self.min_interval)
+        {
+            self.pending = true;
+            return;
+        }
+        self.deliver(now, fraction, nodes, matches);
+    }
+
+    /// Report the final progress of the search, which is delivered unless the last event
+    /// already holds it.
+    pub fn finish(&mut self, fraction: f64, nodes: u64, matches: usize) {
+        if self.pending {
+            self.deliver(Instant::now(), fraction, nodes, matches);
+        }
+    }
+
+    fn deliver(&mut self, now: Instant, fraction: f64, nodes: u64, matches: usize) {
+        let elapsed = now.duration_since(self.start);
+        let progress = Progress {
+            fraction: fraction.clamp(0.0, 1.0),
+            elapsed,
+            nodes,
+            throughput: nodes as f64 / elapsed.as_secs_f64().max(f64::MIN_POSITIVE),
+            matches,
+        };
+        (self.callback)(&progress);
+        self.last = Some(now);
+        self.pending = false;
+    }
+}
@@ -0,0 +1,107 @@
+//! Regex constraints on the middle of names, compiled to a DFA so that searches can follow its
+//! state character by character and prune the branches which can no longer match.
+
+use std::{error::Error, fmt, str::FromStr};
+
+use regex_automata::{
+    Anchored, MatchKind,
+    dfa::{Automaton, StartKind, dense},
+    nfa::thompson,
+    util::{primitives::StateID, start, syntax},
+};
+
+/// Largest DFA a regex may compile to, in bytes.
+const DFA_SIZE_LIMIT: usize = 64 << 20;
+
+/// A regex which whole middles must match, e.g. `[a-z]+[0-9]{2}`.
+///
+/// Patterns match bytes rather than Unicode characters, and are anchored at both ends whether
+/// or not they start with `^` and end with `$`.
+#[derive(Clone)]
+pub struct NameRegex {
+    pattern: String,
+    dfa: dense::DFA<Vec<u32>>,
+    start: RegexState,
+}
+
+/// State of a [`NameRegex`] after some characters.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RegexState(StateID);
+
+impl NameRegex {
+    /// State before any character.
+    pub fn start(&self) -> RegexState {
+        self.start
+    }
+
+    /// State after `bytes` following `state`.
+    pub fn advance(&self, state: RegexState, bytes: &[u8]) -> RegexState {
+        let state = bytes
+            .iter()
+            .fold(state.0, |state, &b| self.dfa.next_state(state, b));
+        RegexState(state)
+    }
+
+    /// Whether some continuation of the characters leading to `state` matches.
+    pub fn can_match(&self, state: RegexState) -> bool {
+        !self.dfa.is_dead_state(state.0)
+    }
+
+    /// Whether the characters leading to `state` match.
+    pub fn is_match(&self, state: RegexState) -> bool {
+        self.dfa.is_match_state(self.dfa.next_eoi_state(state.0))
+    }
+
+    /// Whether `middle` matches.
+    pub fn matches(&self, middle: &[u8]) -> bool {
+        self.is_match(self.advance(self.start, middle))
+    }
+}
+
+impl FromStr for NameRegex {
+    type Err = String;
+
+    fn from_str(pattern: &str) -> Result<Self, String> {
+        // matches are only reported at the end of the input, so that the DFA never stops early
+        // after a shorter match
+        let dfa = dense::Builder::new()
+            .configure(
+                dense::Config::new()
+                    .match_kind(MatchKind::All)
+                    .start_kind(StartKind::Anchored)
+                    .dfa_size_limit(Some(DFA_SIZE_LIMIT))
+                    .determinize_size_limit(Some(DFA_SIZE_LIMIT)),
+            )
+            .syntax(syntax::Config::new().unicode(false).utf8(false))
+            .thompson(thompson::Config::new().utf8(false))
+            .build(&format!("(?:{pattern})$"))
+            // the build error itself only says which stage failed
+            .map_err(|e| {
+                let mut error: &dyn Error = &e;
+                while let Some(source) = error.source() {
+                    error = source;
+                }
+                error.to_string()
+            })?;
+        let start = dfa
+            .start_state(&start::Config::new().anchored(Anchored::Yes))
+            .map_err(|e| e.to_string())?;
+        Ok(Self {
+            pattern: pattern.to_owned(),
+            dfa,
+            start: RegexState(start),
+        })
+    }
+}
+
+impl fmt::Debug for NameRegex {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_tuple("NameRegex").field(&self.pattern).finish()
+    }
+}
+
+impl fmt::Display for NameRegex {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.pattern)
+    }
+}
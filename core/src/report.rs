@@ -0,0 +1,178 @@
+use std::{fmt::Write as _, fs, io, path::Path};
+
+use crate::{HashWidth, status::TargetStatus};
+
+/// A candidate name for a target, along with the score it is ranked by.
+#[derive(Debug, Clone)]
+pub struct Candidate {
+    pub name: Vec<u8>,
+    pub score: f64,
+}
+
+/// The `capacity` best scoring candidates out of a stream of unknown length.
+///
+/// Candidates are kept in a buffer of twice the capacity which is pruned whenever it fills up,
+/// so memory use is bounded no matter how many candidates are pushed.
+#[derive(Debug, Clone)]
+pub struct TopCandidates {
+    items: Vec<Candidate>,
+    capacity: usize,
+}
+
+impl TopCandidates {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            items: Vec::with_capacity(2 * capacity),
+            capacity,
+        }
+    }
+
+    pub fn push(&mut self, candidate: Candidate) {
+        self.items.push(candidate);
+        if self.items.len() >= 2 * self.capacity.max(1) {
+            self.prune();
+        }
+    }
+
+    /// The kept candidates, best first.
+    pub fn into_sorted(mut self) -> Vec<Candidate> {
+        self.prune();
+        self.items
+    }
+
+    fn prune(&mut self) {
+        self.items.sort_by(|a, b| b.score.total_cmp(&a.score));
+        self.items.truncate(self.capacity);
+    }
+}
+
+/// Outcome of a run for a single target, as shown in a report.
+#[derive(Debug, Clone)]
+pub struct TargetReport {
+    pub status: TargetStatus,
+    /// Best candidates for the target, best first.
+    pub candidates: Vec<Candidate>,
+}
+
+/// Everything shown in an HTML report.
+#[derive(Debug, Clone, Default)]
+pub struct Report {
+    pub title: String,
+    pub targets: Vec<TargetReport>,
+    /// Width of the target hashes, which are padded to its digits.
+    pub hash_width: HashWidth,
+    /// Name of the devices the search ran on.
+    pub devices: Vec<String>,
+    /// Run statistics, as label and value pairs.
+    pub stats: Vec<(String, String)>,
+}
+
+impl Report {
+    /// Render the report as a self-contained HTML page, which doesn't load any external
+    /// resources so that it can be shared as a single file.
+    pub fn to_html(&self) -> String {
+        let mut out = String::new();
+        let title = escape_html(self.title.as_bytes());
+        let resolved = self.targets.iter().filter(|t| t.status.resolved()).count();
+        let width = self.hash_width.hex_digits();
+
+        out += "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n";
+        writeln!(out, "<title>{title}</title>").unwrap();
+        writeln!(out, "<style>{STYLE}</style>\n</head>\n<body>").unwrap();
+        writeln!(out, "<h1>{title}</h1>").unwrap();
+        writeln!(
+            out,
+            "<p>{resolved} of {} target(s) resolved.</p>",
+            self.targets.len()
+        )
+        .unwrap();
+
+        out +=
+            "<h2>Targets</h2>\n<table>\n<tr><th>Target</th><th>Status</th><th>Matches</th></tr>\n";
+        for t in &self.targets {
+            let (class, status) = match t.status.resolved() {
+                true => ("resolved", "resolved"),
+                false => ("unresolved", "unresolved"),
+            };
+            writeln!(
+                out,
+                "<tr><td><code>{:0width$x}</code></td><td class=\"{class}\">{status}</td><td>{}</td></tr>",
+                t.status.target, t.status.matches
+            )
+            .unwrap();
+        }
+        out += "</table>\n";
+
+        for t in self.targets.iter().filter(|t| !t.candidates.is_empty()) {
+            writeln!(
+                out,
+                "<h2>Top candidates for <code>{:0width$x}</code></h2>",
+                t.status.target
+            )
+            .unwrap();
+            out += "<table>\n<tr><th>#</th><th>Name</th><th>Score</th></tr>\n";
+            for (i, c) in t.candidates.iter().enumerate() {
+                writeln!(
+                    out,
+                    "<tr><td>{}</td><td><code>{}</code></td><td>{:.3}</td></tr>",
+                    i + 1,
+                    escape_html(&c.name),
+                    c.score
+                )
+                .unwrap();
+            }
+            out += "</table>\n";
+        }
+
+        if !self.devices.is_empty() {
+            out += "<h2>Devices</h2>\n<ul>\n";
+            for device in &self.devices {
+                writeln!(out, "<li>{}</li>", escape_html(device.as_bytes())).unwrap();
+            }
+            out += "</ul>\n";
+        }
+
+        if !self.stats.is_empty() {
+            out += "<h2>Run statistics</h2>\n<table>\n";
+            for (label, value) in &self.stats {
+                writeln!(
+                    out,
+                    "<tr><th>{}</th><td>{}</td></tr>",
+                    escape_html(label.as_bytes()),
+                    escape_html(value.as_bytes())
+                )
+                .unwrap();
+            }
+            out += "</table>\n";
+        }
+
+        out += "</body>\n</html>\n";
+        out
+    }
+
+    pub fn write_html(&self, path: &Path) -> io::Result<()> {
+        fs::write(path, self.to_html())
+    }
+}
+
+const STYLE: &str = "body{font-family:sans-serif;margin:2em auto;max-width:60em}\
+table{border-collapse:collapse;margin-bottom:1em}\
+th,td{border:1px solid #ccc;padding:.2em .6em;text-align:left}\
+.resolved{color:#080}.unresolved{color:#b00}";
+
+/// Escape `bytes` for use in HTML text. Non-ASCII and control bytes are shown as `\xNN`
+/// escapes, since names aren't necessarily valid UTF-8.
+fn escape_html(bytes: &[u8]) -> String {
+    let mut out = String::with_capacity(bytes.len());
+    for &b in bytes {
+        match b {
+            b'&' => out += "&amp;",
+            b'<' => out += "&lt;",
+            b'>' => out += "&gt;",
+            b'"' => out += "&quot;",
+            b' '..=b'~' => out.push(b as char),
+            _ => write!(out, "\\x{b:02x}").unwrap(),
+        }
+    }
+    out
+}
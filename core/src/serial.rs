@@ -0,0 +1,245 @@
+//! Stable serialization of the structures describing a search, for the files that persist or
+//! hand out searches.
+//!
+//! Alphabets are written as strings holding their characters in order, with each byte as the
+//! character of the same code point so that any byte round-trips. Templates are written as the
+//! text they were parsed from. Masks keep their text along with their slots, since `?1` depends
+//! on the alphabet it was parsed with. Wrap the top-level value in [`Versioned`], so that files
+//! written by later formats are rejected rather than misread.
+
+use std::{fmt, marker::PhantomData};
+
+use serde::{
+    Deserialize, Deserializer, Serialize, Serializer,
+    de::{self, Visitor},
+};
+
+use crate::{
+    alphabet::{Alphabet, DynAlphabet},
+    template::OutputTemplate,
+};
+
+/// Version of the format written by this build. Bump it whenever a serialized structure
+/// changes incompatibly.
+pub const FORMAT_VERSION: u32 = 1;
+
+/// A value tagged with the [`FORMAT_VERSION`] it was written with.
+#[derive(Debug, Clone, Serialize)]
+pub struct Versioned<T> {
+    pub version: u32,
+    pub value: T,
+}
+
+impl<T> Versioned<T> {
+    pub fn new(value: T) -> Self {
+        Self {
+            version: FORMAT_VERSION,
+            value,
+        }
+    }
+}
+
+impl<'de, T: Deserialize<'de>> Deserialize<'de> for Versioned<T> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        #[derive(Deserialize)]
+        struct Raw<T> {
+            version: u32,
+            value: T,
+        }
+
+        let raw = Raw::<T>::deserialize(deserializer)?;
+        if raw.version != FORMAT_VERSION {
+            return Err(de::Error::custom(format!(
+                "unsupported format version {} (expected {FORMAT_VERSION})",
+                raw.version
+            )));
+        }
+        Ok(Self {
+            version: raw.version,
+            value: raw.value,
+        })
+    }
+}
+
+fn alphabet_string(bytes: &[u8]) -> String {
+    bytes.iter().map(|&b| b as char).collect()
+}
+
+/// Alphabets built from the bytes they are deserialized from.
+trait FromAlphabetBytes: Sized {
+    fn from_alphabet_bytes(bytes: Vec<u8>) -> Result<Self, String>;
+}
+
+impl<const N: usize> FromAlphabetBytes for Alphabet<N> {
+    fn from_alphabet_bytes(bytes: Vec<u8>) -> Result<Self, String> {
+        let len = bytes.len();
+        let bytes: [u8; N] = bytes
+            .try_into()
+            .map_err(|_| format!("expected {N} alphabet characters, got {len}"))?;
+        Self::try_from_array(bytes).map_err(|e| e.to_string())
+    }
+}
+
+impl FromAlphabetBytes for DynAlphabet {
+    fn from_alphabet_bytes(bytes: Vec<u8>) -> Result<Self, String> {
+        Self::new(&bytes).map_err(|e| e.to_string())
+    }
+}
+
+/// Visitor of the string an alphabet is written as.
+struct AlphabetVisitor<T>(PhantomData<T>);
+
+impl<T: FromAlphabetBytes> Visitor<'_> for AlphabetVisitor<T> {
+    type Value = T;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a string of alphabet characters")
+    }
+
+    fn visit_str<E: de::Error>(self, s: &str) -> Result<T, E> {
+        let bytes = s
+            .chars()
+            .map(|c| {
+                u8::try_from(c).map_err(|_| E::custom(format!("invalid alphabet character {c:?}")))
+            })
+            .collect::<Result<_, _>>()?;
+        T::from_alphabet_bytes(bytes).map_err(E::custom)
+    }
+}
+
+impl<const N: usize> Serialize for Alphabet<N> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&alphabet_string(self.bytes()))
+    }
+}
+
+impl<'de, const N: usize> Deserialize<'de> for Alphabet<N> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_str(AlphabetVisitor::<Self>(PhantomData))
+    }
+}
+
+impl Serialize for DynAlphabet {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&alphabet_string(self.order()))
+    }
+}
+
+impl<'de> Deserialize<'de> for DynAlphabet {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_str(AlphabetVisitor::<Self>(PhantomData))
+    }
+}
+
+impl Serialize for OutputTemplate {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for OutputTemplate {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let text = String::deserialize(deserializer)?;
+        text.parse().map_err(de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        HashWidth,
+        mask::{Mask, Slot},
+        template::Fields,
+    };
+
+    fn round_trip<T: Serialize + for<'de> Deserialize<'de>>(value: &T) -> T {
+        let json = serde_json::to_string(&Versioned::new(value)).unwrap();
+        serde_json::from_str::<Versioned<T>>(&json).unwrap().value
+    }
+
+    /// Characters of each slot, in the order they were given in.
+    fn slot_chars(mask: &Mask) -> Vec<Vec<u8>> {
+        mask.slots()
+            .iter()
+            .map(|slot| slot.alphabet().order().to_vec())
+            .collect()
+    }
+
+    #[test]
+    fn dyn_alphabet_round_trips() {
+        let bytes: Vec<u8> = (0..=u8::MAX).rev().step_by(3).collect();
+        assert!(bytes.iter().any(|&b| b >= 0x80));
+        let alphabet = DynAlphabet::new(&bytes).unwrap();
+        let back = round_trip(&alphabet);
+        assert_eq!(back.order(), alphabet.order());
+        assert_eq!(back.bytes(), alphabet.bytes());
+    }
+
+    #[test]
+    fn alphabet_round_trips() {
+        let alphabet = Alphabet::try_from_array(*b"9876543210").unwrap();
+        let back: Alphabet<10> = round_trip(&alphabet);
+        assert_eq!(back.bytes(), alphabet.bytes());
+        assert!(serde_json::from_str::<Alphabet<9>>(r#""9876543210""#).is_err());
+        assert!(serde_json::from_str::<Alphabet<2>>(r#""aa""#).is_err());
+    }
+
+    #[test]
+    fn mask_round_trips() {
+        let custom = DynAlphabet::new(b"zyx_\xe9").unwrap();
+        let mask = Mask::parse("/c?1?1/c%2d_?1?l.dcx", &custom).unwrap();
+        let back = round_trip(&mask);
+        assert_eq!(back.to_string(), mask.to_string());
+        assert_eq!(slot_chars(&back), slot_chars(&mask));
+    }
+
+    #[test]
+    fn template_round_trips() {
+        let template: OutputTemplate = "{prefix}{middle}{suffix}\\t{hash:X} {{{hash:d}}}"
+            .parse()
+            .unwrap();
+        let back = round_trip(&template);
+        assert_eq!(back.to_string(), template.to_string());
+
+        let fields = Fields {
+            prefix: b"/other/",
+            middle: b"mab",
+            suffix: b".dcx",
+            hash: 0xd7255946,
+            hash_width: HashWidth::Bits32,
+        };
+        let (mut expected, mut actual) = (Vec::new(), Vec::new());
+        template.render(&fields, &mut expected);
+        back.render(&fields, &mut actual);
+        assert_eq!(actual, expected);
+    }
+
+    /// Files written by earlier builds must keep loading, so the v1 format is pinned here.
+    #[test]
+    fn v1_format_is_stable() {
+        type Plan = (DynAlphabet, Alphabet<3>, Mask, OutputTemplate);
+        const V1: &str = r#"{"version":1,"value":["cba\u0000é","xyz",{"text":"m?1","slots":[{"fixed":109},{"set":"\u0000abcé"}]},"{middle}\t{hash:x}"]}"#;
+
+        let custom = DynAlphabet::new(b"cba\0\xe9").unwrap();
+        let plan: Plan = (
+            custom.clone(),
+            Alphabet::try_from_array(*b"xyz").unwrap(),
+            Mask::parse("m?1", &custom).unwrap(),
+            "{middle}\t{hash:x}".parse().unwrap(),
+        );
+        assert_eq!(serde_json::to_string(&Versioned::new(&plan)).unwrap(), V1);
+
+        let (alphabet, fixed, mask, template) =
+            serde_json::from_str::<Versioned<Plan>>(V1).unwrap().value;
+        assert_eq!(alphabet.order(), b"cba\0\xe9");
+        assert_eq!(fixed.bytes(), b"xyz");
+        assert_eq!(mask.to_string(), "m?1");
+        assert!(matches!(mask.slots(), [Slot::Fixed(b'm'), Slot::Set(_)]));
+        assert_eq!(slot_chars(&mask)[1], b"\0abc\xe9");
+        assert_eq!(template.to_string(), "{middle}\t{hash:x}");
+
+        let v2 = V1.replacen(r#""version":1"#, r#""version":2"#, 1);
+        assert!(serde_json::from_str::<Versioned<Plan>>(&v2).is_err());
+    }
+}
@@ -0,0 +1,74 @@
+use std::{collections::BTreeMap, fs, io, path::Path};
+
+use crate::HashWidth;
+
+/// Exit code when every target was resolved.
+pub const EXIT_ALL_RESOLVED: i32 = 0;
+/// Exit code when some, but not all targets were resolved.
+pub const EXIT_PARTIAL: i32 = 2;
+/// Exit code when no target was resolved.
+pub const EXIT_NONE_RESOLVED: i32 = 3;
+/// Exit code when the search was stopped by its time limit before completing.
+pub const EXIT_TIMED_OUT: i32 = 4;
+/// Exit code when the requested backend has no usable device to run on.
+pub const EXIT_NO_DEVICE: i32 = 5;
+
+/// Outcome of the search for a single target hash.
+#[derive(Debug, Clone, Copy)]
+pub struct TargetStatus {
+    /// Target hash, widened to a `u64`.
+    pub target: u64,
+    pub matches: usize,
+}
+
+impl TargetStatus {
+    pub fn resolved(&self) -> bool {
+        self.matches != 0
+    }
+}
+
+/// Process exit code summarizing the resolution of `statuses`.
+pub fn exit_code(statuses: &[TargetStatus]) -> i32 {
+    let resolved = statuses.iter().filter(|s| s.resolved()).count();
+    if resolved == statuses.len() {
+        EXIT_ALL_RESOLVED
+    } else if resolved == 0 {
+        EXIT_NONE_RESOLVED
+    } else {
+        EXIT_PARTIAL
+    }
+}
+
+/// Write one `<target hex>\t<resolved|unresolved>\t<match count>` line per target to `path`,
+/// with the targets padded to the digits of `hash_width`.
+pub fn write_status_file(
+    path: &Path,
+    statuses: &[TargetStatus],
+    hash_width: HashWidth,
+) -> io::Result<()> {
+    let width = hash_width.hex_digits();
+    let mut out = String::new();
+    for s in statuses {
+        let status = if s.resolved() {
+            "resolved"
+        } else {
+            "unresolved"
+        };
+        out += &format!("{:0width$x}\t{status}\t{}\n", s.target, s.matches);
+    }
+    fs::write(path, out)
+}
+
+/// Print how many matches were found for each middle length.
+pub fn print_length_histogram(counts: &BTreeMap<usize, usize>) {
+    if counts.is_empty() {
+        return;
+    }
+
+    let max = counts.values().copied().max().unwrap_or(1);
+    println!("matches by middle length:");
+    for (len, &count) in counts {
+        let bar = "#".repeat((40 * count).div_ceil(max));
+        println!("{len:>4}: {count:>8} {bar}");
+    }
+}
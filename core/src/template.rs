@@ -0,0 +1,128 @@
+use std::{
+    fmt::{self, Write as _},
+    str::FromStr,
+};
+
+use crate::HashWidth;
+
+/// The values that can be substituted into an [`OutputTemplate`].
+#[derive(Debug, Clone, Copy)]
+pub struct Fields<'a> {
+    pub prefix: &'a [u8],
+    pub middle: &'a [u8],
+    pub suffix: &'a [u8],
+    /// Hash of the match, widened to a `u64`.
+    pub hash: u64,
+    pub hash_width: HashWidth,
+}
+
+#[derive(Debug, Clone)]
+enum Part {
+    Literal(Vec<u8>),
+    Prefix,
+    Middle,
+    Suffix,
+    HashLowerHex,
+    HashUpperHex,
+    HashDecimal,
+}
+
+/// Line format for reported matches, e.g. `{prefix}{middle}{suffix}\t{hash:x}`.
+///
+/// Supported fields are `prefix`, `middle`, `suffix` and `hash`. The hash can be formatted as
+/// lowercase hex (`{hash}` or `{hash:x}`), uppercase hex (`{hash:X}`) or decimal (`{hash:d}`).
+/// Literal braces are written as `{{` and `}}`, and the `\t`, `\n` and `\\` escapes are
+/// recognized so that templates can be passed from a shell without quoting tricks.
+#[derive(Debug, Clone)]
+pub struct OutputTemplate {
+    text: String,
+    parts: Vec<Part>,
+}
+
+impl OutputTemplate {
+    /// Append the line described by this template to `out`, without a trailing newline.
+    pub fn render(&self, fields: &Fields, out: &mut Vec<u8>) {
+        let width = fields.hash_width.hex_digits();
+        for part in &self.parts {
+            match part {
+                Part::Literal(bytes) => out.extend_from_slice(bytes),
+                Part::Prefix => out.extend_from_slice(fields.prefix),
+                Part::Middle => out.extend_from_slice(fields.middle),
+                Part::Suffix => out.extend_from_slice(fields.suffix),
+                Part::HashLowerHex => {
+                    out.extend_from_slice(format!("{:0width$x}", fields.hash).as_bytes())
+                }
+                Part::HashUpperHex => {
+                    out.extend_from_slice(format!("{:0width$X}", fields.hash).as_bytes())
+                }
+                Part::HashDecimal => out.extend_from_slice(fields.hash.to_string().as_bytes()),
+            }
+        }
+    }
+}
+
+impl FromStr for OutputTemplate {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = Vec::new();
+        let mut literal = String::new();
+
+        let mut chars = s.chars();
+        while let Some(c) = chars.next() {
+            match c {
+                '\\' => match chars.next() {
+                    Some('t') => literal.push('\t'),
+                    Some('n') => literal.push('\n'),
+                    Some('\\') => literal.push('\\'),
+                    Some(other) => write!(literal, "\\{other}").unwrap(),
+                    None => literal.push('\\'),
+                },
+                '}' => match chars.next() {
+                    Some('}') => literal.push('}'),
+                    _ => return Err("unmatched `}` in template (use `}}` for a literal)".into()),
+                },
+                '{' => {
+                    let rest = chars.as_str();
+                    if let Some(after) = rest.strip_prefix('{') {
+                        literal.push('{');
+                        chars = after.chars();
+                        continue;
+                    }
+
+                    let end = rest.find('}').ok_or("unclosed `{` in template")?;
+                    let part = match &rest[..end] {
+                        "prefix" => Part::Prefix,
+                        "middle" => Part::Middle,
+                        "suffix" => Part::Suffix,
+                        "hash" | "hash:x" => Part::HashLowerHex,
+                        "hash:X" => Part::HashUpperHex,
+                        "hash:d" => Part::HashDecimal,
+                        field => return Err(format!("unknown template field `{field}`")),
+                    };
+                    chars = rest[end + 1..].chars();
+
+                    if !literal.is_empty() {
+                        parts.push(Part::Literal(std::mem::take(&mut literal).into_bytes()));
+                    }
+                    parts.push(part);
+                }
+                c => literal.push(c),
+            }
+        }
+
+        if !literal.is_empty() {
+            parts.push(Part::Literal(literal.into_bytes()));
+        }
+        Ok(Self {
+            text: s.to_owned(),
+            parts,
+        })
+    }
+}
+
+impl fmt::Display for OutputTemplate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.text)
+    }
+}
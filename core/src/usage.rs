@@ -0,0 +1,41 @@
+/// Peak resident set size of the current process, in bytes.
+///
+/// Returns `None` if this isn't supported on the current platform.
+#[cfg(target_os = "linux")]
+pub fn peak_rss() -> Option<u64> {
+    let status = std::fs::read_to_string("/proc/self/status").ok()?;
+    let line = status.lines().find(|l| l.starts_with("VmHWM:"))?;
+    let kib: u64 = line["VmHWM:".len()..]
+        .trim()
+        .trim_end_matches("kB")
+        .trim()
+        .parse()
+        .ok()?;
+    Some(kib * 1024)
+}
+
+/// Peak resident set size of the current process, in bytes.
+///
+/// Returns `None` if this isn't supported on the current platform.
+#[cfg(not(target_os = "linux"))]
+pub fn peak_rss() -> Option<u64> {
+    None
+}
+
+/// Format a byte count as a human-readable string.
+pub fn fmt_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 5] = ["B", "KiB", "MiB", "GiB", "TiB"];
+
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+
+    if unit == 0 {
+        format!("{bytes} B")
+    } else {
+        format!("{value:.1} {}", UNITS[unit])
+    }
+}
@@ -0,0 +1,148 @@
+use std::fmt::{Debug, Display};
+
+use cl3::ext::{CL_BLOCKING, CL_MEM_READ_WRITE};
+use opencl3::{
+    command_queue::CommandQueue,
+    context::Context,
+    device::{CL_DEVICE_TYPE_ALL, CL_DEVICE_TYPE_CPU, CL_DEVICE_TYPE_GPU, Device},
+    error_codes::ClError,
+    kernel::{ExecuteKernel, Kernel},
+    memory::Buffer,
+    platform::get_platforms,
+    program::Program,
+};
+
+use fs_hardblast_core::usage;
+
+const TEST_KERNEL: &str = "kernel void add_index(global uint* data) {
+    data[get_global_id(0)] += get_global_id(0);
+}";
+const TEST_ITEMS: usize = 256;
+
+/// Print a report of every OpenCL platform and device, and check that each device can run a
+/// trivial kernel.
+pub fn run() {
+    let platforms = match get_platforms() {
+        Ok(platforms) if !platforms.is_empty() => platforms,
+        Ok(_) => {
+            println!("no OpenCL platforms found. is an OpenCL ICD loader and driver installed?");
+            return;
+        }
+        Err(e) => {
+            println!("failed to enumerate OpenCL platforms: {e:?}");
+            println!("is an OpenCL ICD loader and driver installed?");
+            return;
+        }
+    };
+
+    for (i, platform) in platforms.iter().enumerate() {
+        println!("platform {i}: {}", show(platform.name()));
+        println!("  vendor: {}", show(platform.vendor()));
+        println!("  version: {}", show(platform.version()));
+        println!("  profile: {}", show(platform.profile()));
+
+        let devices = match platform.get_devices(CL_DEVICE_TYPE_ALL) {
+            Ok(devices) => devices,
+            Err(e) => {
+                println!("  failed to enumerate devices: {e:?}");
+                continue;
+            }
+        };
+        if devices.is_empty() {
+            println!("  no devices");
+        }
+
+        for (j, &id) in devices.iter().enumerate() {
+            let device = Device::new(id);
+            let extensions = device.extensions().unwrap_or_default();
+            let version = device.version().unwrap_or_default();
+
+            // global int32 atomics are core since OpenCL 1.1
+            let atomics = version.as_str() >= "OpenCL 1.1"
+                || extensions.contains("cl_khr_global_int32_base_atomics");
+
+            println!("  device {j}: {}", show(device.name()));
+            println!(
+                "    type: {}",
+                show(device.dev_type().map(device_type_name))
+            );
+            println!("    vendor: {}", show(device.vendor()));
+            println!("    version: {}", show(device.version()));
+            println!("    driver version: {}", show(device.driver_version()));
+            println!("    OpenCL C version: {}", show(device.opencl_c_version()));
+            println!("    available: {}", show(device.available()));
+            println!("    compute units: {}", show(device.max_compute_units()));
+            println!("    max clock: {} MHz", show(device.max_clock_frequency()));
+            println!(
+                "    max work-group size: {}",
+                show(device.max_work_group_size())
+            );
+            println!(
+                "    global memory: {}",
+                show(device.global_mem_size().map(usage::fmt_bytes))
+            );
+            println!("    global int32 atomics: {atomics}");
+            println!("    extensions: {extensions}");
+            match test_kernel(&device) {
+                Ok(()) => println!("    test kernel: ok"),
+                Err(e) => println!("    test kernel: FAILED ({e})"),
+            }
+        }
+    }
+}
+
+/// Build and run a trivial kernel on `device`, and check its output.
+fn test_kernel(device: &Device) -> Result<(), String> {
+    let cl_err = |e: ClError| format!("{e:?}");
+
+    let context = Context::from_device(device).map_err(cl_err)?;
+    let queue = CommandQueue::create_default(&context, 0).map_err(cl_err)?;
+    let program = Program::create_and_build_from_source(&context, TEST_KERNEL, "")
+        .map_err(|log| format!("build failed: {log}"))?;
+    let kernel = Kernel::create(&program, "add_index").map_err(cl_err)?;
+
+    let mut data = [1u32; TEST_ITEMS];
+    let mut buffer = unsafe {
+        Buffer::<u32>::create(
+            &context,
+            CL_MEM_READ_WRITE,
+            TEST_ITEMS,
+            std::ptr::null_mut(),
+        )
+        .map_err(cl_err)?
+    };
+
+    unsafe {
+        queue
+            .enqueue_write_buffer(&mut buffer, CL_BLOCKING, 0, &data, &[])
+            .map_err(cl_err)?;
+        let event = ExecuteKernel::new(&kernel)
+            .set_arg(&buffer)
+            .set_global_work_size(TEST_ITEMS)
+            .enqueue_nd_range(&queue)
+            .map_err(cl_err)?;
+        queue
+            .enqueue_read_buffer(&buffer, CL_BLOCKING, 0, &mut data, &[event.get()])
+            .map_err(cl_err)?;
+    }
+
+    match data.iter().enumerate().find(|&(i, &x)| x != i as u32 + 1) {
+        None => Ok(()),
+        Some((i, x)) => Err(format!("wrong output at index {i}: {x}")),
+    }
+}
+
+fn device_type_name(ty: u64) -> &'static str {
+    match ty {
+        CL_DEVICE_TYPE_GPU => "GPU",
+        CL_DEVICE_TYPE_CPU => "CPU",
+        _ => "other",
+    }
+}
+
+fn show<T: Display, E: Debug>(result: Result<T, E>) -> String {
+    match result {
+        Ok(value) => value.to_string(),
+        Err(e) => format!("<error: {e:?}>"),
+    }
+}
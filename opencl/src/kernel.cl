@@ -16,6 +16,11 @@
 #ifndef ALPHABET_LIT
 #define ALPHABET_LIT ".0123456789_abcdefghijklmnopqrstuvwxyz"
 #endif
+#ifndef FINAL_ALPHABET_LIT
+#define FINAL_ALPHABET_LIT ALPHABET_LIT // alphabet of the solved last char
+#endif
+// define MULTI_TARGET to check candidates against all of target_shifts rather than its first
+// element only, and TARGET_FILTER to also prefilter them with the bloom filter in filter
 
 #define CAT(a, b) a ## b
 #define XCAT(a,b) CAT(a,b)
@@ -27,14 +32,16 @@ typedef VEC(HASH_T) hashvec_t;
 
 constant uchar ALPHABET[] = ALPHABET_LIT;
 #define ALPHABET_SIZE (sizeof(ALPHABET) - 1)
+constant uchar FINAL_ALPHABET[] = FINAL_ALPHABET_LIT;
+#define FINAL_ALPHABET_SIZE (sizeof(FINAL_ALPHABET) - 1)
 
 bool in_alphabet_prefilter(hashvec_t solutions) {
     // Compiler will unroll this loop and optimize into constant comparisons
     uchar max = 0;
     #pragma unroll
-    for (int i = 0; i < ALPHABET_SIZE; i++) {
-        if (ALPHABET[i] > max) {
-            max = ALPHABET[i];
+    for (int i = 0; i < FINAL_ALPHABET_SIZE; i++) {
+        if (FINAL_ALPHABET[i] > max) {
+            max = FINAL_ALPHABET[i];
         }
     }
     return any(solutions <= max);
@@ -45,8 +52,8 @@ bool in_alphabet(hash_t solution) {
     ulong m0 = 0, m1 = 0, m2 = 0, m3 = 0;
     uchar max = 0;
     #pragma unroll
-    for (int i = 0; i < ALPHABET_SIZE; i++) {
-        uchar c = ALPHABET[i];
+    for (int i = 0; i < FINAL_ALPHABET_SIZE; i++) {
+        uchar c = FINAL_ALPHABET[i];
         if (c > max)      max = c;
         if (c < 64)       m0 |= (1UL << c);
         else if (c < 128) m1 |= (1UL << (c - 64));
@@ -65,6 +72,19 @@ bool in_alphabet(hash_t solution) {
     return (mask >> solution) & 1;
 }
 
+#ifdef TARGET_FILTER
+// same lookup as BloomFilter::may_contain
+bool filter_may_contain(global const ulong* filter, const uint filter_mask, ulong key) {
+    ulong h = key;
+    h ^= h >> 33;
+    h *= 0xff51afd7ed558ccdUL;
+    h ^= h >> 33;
+
+    const ulong bits = (1UL << ((h >> 52) & 63)) | (1UL << (h >> 58));
+    return (filter[h & filter_mask] & bits) == bits;
+}
+#endif
+
 typedef struct {
     uchar bytes[PAR_LEN];
 } ItemBase;
@@ -74,10 +94,40 @@ typedef struct {
     uchar bytes[SEQ_LEN];
 } Match;
 
+void write_match(
+    global Match* out_buffer,
+    const uint out_buffer_size,
+    volatile global int* out_buffer_written,
+    const ItemBase* item_base,
+    const char* char_indices,
+    const char depth,
+    const uchar solution
+) {
+    const uint slot = atomic_add(out_buffer_written, 1);
+    if (slot >= out_buffer_size) {
+        return;
+    }
+    global Match* m = out_buffer + slot;
+    // write base (par) bytes
+    m->base = *item_base;
+    // write seq bytes
+    for (int j = 0; j <= depth; j++) {
+        m->bytes[j] = ALPHABET[char_indices[j]];
+    }
+    m->bytes[depth+1] = solution;
+    // nul-terminate
+    if (depth + 2 < SEQ_LEN) {
+        m->bytes[depth+2] = 0;
+    }
+}
+
 kernel void find_collisions(
     const ulong work_items,
     const hash_t prefix_hash,
-    const hash_t suffix_shift,
+    global const hash_t* target_shifts, // sorted
+    const uint target_count,
+    global const ulong* filter, // bloom filter of target_shifts >> 8
+    const uint filter_mask,
     global Match* out_buffer,
     const uint out_buffer_size,
     volatile global int* out_buffer_written
@@ -108,6 +158,9 @@ kernel void find_collisions(
 
     // load item base hash into a vector
     hashvec_t item_base_hash = VEC(vload)(0, nonvec_base_hashes);
+#ifndef MULTI_TARGET
+    const hash_t suffix_shift = target_shifts[0];
+#endif
 
     // DFS state variables
     hashvec_t base_hashes[SEARCH_DEPTH] = { [0] = item_base_hash };
@@ -128,32 +181,54 @@ kernel void find_collisions(
         }
 
         const hashvec_t base_hash = (base_hashes[depth] + (hash_t)ALPHABET[i]) * FNV_PRIME;
+#ifdef MULTI_TARGET
+        // the target shifts a single char away from a base hash are the ones in
+        // [base, base + 255], which may wrap around the end of the sorted list
+        hash_t base_hash_nonvec[VEC_LEN];
+        VEC(vstore)(base_hash, 0, base_hash_nonvec);
+
+        for (int k = 0; k < VEC_LEN; k++) {
+            const hash_t base = base_hash_nonvec[k];
+#ifdef TARGET_FILTER
+            if (!filter_may_contain(filter, filter_mask, base >> 8)
+                && !filter_may_contain(filter, filter_mask, (hash_t)(base + 0xff) >> 8)) {
+                continue;
+            }
+#endif
+            uint lo = 0, hi = target_count;
+            while (lo < hi) {
+                const uint mid = lo + (hi - lo) / 2;
+                if (target_shifts[mid] < base) {
+                    lo = mid + 1;
+                } else {
+                    hi = mid;
+                }
+            }
+            for (uint n = 0; n < target_count; n++) {
+                const hash_t solution = target_shifts[(lo + n) % target_count] - base;
+                if (solution > 0xff) {
+                    break;
+                }
+                if (in_alphabet(solution)) {
+                    write_match(out_buffer, out_buffer_size, out_buffer_written,
+                        &item_base[k], char_indices, depth, solution);
+                }
+            }
+        }
+#else
         const hashvec_t solution = suffix_shift - base_hash;
         if (in_alphabet_prefilter(solution)) {
             hash_t solution_nonvvec[VEC_LEN];
             VEC(vstore)(solution, 0, solution_nonvvec);
 
             for (int k = 0; k < VEC_LEN; k++) {
-                if (!in_alphabet(solution_nonvvec[k])) {
-                    continue;
-                }
-                const uint slot = atomic_add(out_buffer_written, 1);
-                if (slot < out_buffer_size) {
-                    global Match* m = out_buffer + slot;
-                    // write base (par) bytes
-                    m->base = item_base[k];
-                    // write seq bytes
-                    for (int j = 0; j <= depth; j++) {
-                        m->bytes[j] = ALPHABET[char_indices[j]];
-                    }
-                    m->bytes[depth+1] = solution_nonvvec[k];
-                    // nul-terminate
-                    if (depth + 2 < SEQ_LEN) {
-                        m->bytes[depth+2] = 0;
-                    }
+                if (in_alphabet(solution_nonvvec[k])) {
+                    write_match(out_buffer, out_buffer_size, out_buffer_written,
+                        &item_base[k], char_indices, depth, solution_nonvvec[k]);
                 }
             }
         }
+#endif
 
         if (depth < SEARCH_DEPTH - 1) {
             base_hashes[++depth] = base_hash;
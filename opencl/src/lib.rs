@@ -0,0 +1,892 @@
+//! OpenCL implementation of the collision search.
+
+use std::{
+    borrow::Cow,
+    cmp::Reverse,
+    ffi::c_void,
+    fmt::Write,
+    ops::RangeInclusive,
+    ptr,
+    time::{Duration, Instant},
+};
+
+use cl3::{
+    ext::{
+        CL_BLOCKING, CL_DEVICE_AVAILABLE, CL_DEVICE_MAX_CLOCK_FREQUENCY,
+        CL_DEVICE_MAX_COMPUTE_UNITS, CL_DEVICE_VERSION, CL_MEM_COPY_HOST_PTR, CL_MEM_READ_ONLY,
+        CL_MEM_READ_WRITE, CL_MEM_WRITE_ONLY, CL_NON_BLOCKING,
+    },
+    info_type::InfoType,
+};
+use fs_hardblast_core::{
+    Hash, HashVariant, HashWidth, PrecomputedSuffix,
+    alphabet::DynAlphabet,
+    backend::{self, Backend, BackendError, MitmConfig, SearchConfig},
+    bloom::{BLOOM_MIN_TARGETS, BloomFilter},
+    density,
+    rng::SplitMix64,
+};
+use opencl3::{
+    command_queue::CommandQueue,
+    context::Context,
+    device::{CL_DEVICE_NAME, CL_DEVICE_TYPE_GPU, Device, get_all_devices, get_device_info},
+    error_codes::ClError,
+    kernel::Kernel,
+    memory::Buffer,
+    program::Program,
+};
+
+pub mod diagnose;
+
+pub const PAR_LEN: usize = 4; // Assign a gpu thread to each prefix of this length
+pub const VEC_LEN: usize = 8; // SIMD vector size in kernel, tune for your GPU
+
+pub const BLOCK_SIZE: usize = 256; // tune this for your GPU
+
+/// Number of random names hashed to estimate the number of matches of a run, which sizes its
+/// results buffer.
+const DENSITY_SAMPLES: usize = 1 << 12;
+
+/// OpenCL C source of the built-in kernel.
+pub const KERNEL_SOURCE: &str = include_str!("kernel.cl");
+
+/// OpenCL C source of the kernels of meet-in-the-middle searches.
+pub const MITM_SOURCE: &str = include_str!("mitm.cl");
+
+/// Largest number of combinations of the front half of a meet-in-the-middle search, so that
+/// the table is indexed by `uint`s.
+const MITM_MAX_FRONTS: f64 = (1u64 << 31) as f64;
+
+/// Error of the ICD loader when no OpenCL platform is installed, which cl3 doesn't define.
+const CL_PLATFORM_NOT_FOUND_KHR: i32 = -1001;
+
+/// Shortest string the kernel can find: one character past the ones each work item is
+/// assigned, plus the solved last character. Shorter strings are searched on the host.
+pub const MIN_LEN: usize = PAR_LEN + 2;
+
+/// Statistics about the last search run by an [`OpenClBackend`].
+#[derive(Debug, Default, Clone, Copy)]
+pub struct RunStats {
+    pub kernel_time: Duration,
+    /// Number of matches found by the kernel, including dropped ones.
+    pub found: usize,
+    /// Number of matches that didn't fit in the results buffer.
+    pub dropped: usize,
+    /// Host time spent preparing the launch before the kernel was enqueued, which is mostly
+    /// spent allocating and binding buffers when they can't be reused.
+    pub launch_overhead: Duration,
+    /// Time spent searching the lengths too short for the kernel on the host.
+    pub host_time: Duration,
+    /// Device memory allocated for the search, in bytes.
+    pub device_mem: usize,
+}
+
+/// Buffers bound to the arguments of the built kernel, reused by the following searches as long
+/// as only their prefix changes, e.g. from one start character to the next.
+struct Bindings {
+    target_shifts: Vec<Hash>,
+    work_items: usize,
+    buf_len: usize,
+    results: Buffer<u8>,
+    results_count: Buffer<u32>,
+    // only referenced through the kernel arguments, which they must outlive
+    _targets: Buffer<Hash>,
+    _filter: Buffer<u64>,
+    /// Device memory allocated for the buffers, in bytes.
+    device_mem: usize,
+}
+
+/// Runs the search on the most powerful usable OpenCL GPU.
+pub struct OpenClBackend {
+    context: Context,
+    queue: CommandQueue,
+    /// OpenCL C source of the kernel.
+    source: Cow<'static, str>,
+    /// Last built kernel, along with the build options it was built with.
+    kernel: Option<(String, Program, Kernel)>,
+    /// Buffers bound to the arguments of `kernel`, if it ran already.
+    bindings: Option<Bindings>,
+    /// Program of the meet-in-the-middle kernels, once built, along with its build options.
+    mitm_program: Option<(String, Program)>,
+    /// Number of GPU devices that were skipped because they are unavailable or unsupported.
+    pub skipped_devices: usize,
+    /// Name of the device the search runs on.
+    pub device_name: String,
+    pub last_run: RunStats,
+}
+
+impl OpenClBackend {
+    /// Pick the usable GPU with the most compute, printing the list of usable devices, or
+    /// return `None` if there is no usable GPU.
+    pub fn new() -> Result<Option<Self>, BackendError> {
+        Self::new_cl().map_err(|e| BackendError(format!("OpenCL error: {e:?}")))
+    }
+
+    /// Run searches with the kernel in `source` instead of the built-in one.
+    ///
+    /// The kernel must implement the same interface as [`KERNEL_SOURCE`]: a `find_collisions`
+    /// kernel taking the same arguments and build options, and writing its results in the same
+    /// format.
+    pub fn with_kernel_source(mut self, source: String) -> Self {
+        self.source = Cow::Owned(source);
+        self.kernel = None;
+        self.bindings = None;
+        self
+    }
+
+    fn new_cl() -> Result<Option<Self>, ClError> {
+        let devices = match get_all_devices(CL_DEVICE_TYPE_GPU) {
+            Err(ClError(CL_PLATFORM_NOT_FOUND_KHR)) => return Ok(None),
+            devices => devices?,
+        };
+        let device_count = devices.len();
+        let mut usable: Vec<_> = devices
+            .into_iter()
+            .filter(|&dev| {
+                match get_device_info(dev, CL_DEVICE_AVAILABLE) {
+                    Ok(InfoType::Uint(1..)) => (),
+                    _ => return false,
+                }
+                if let Ok(InfoType::VecUchar(ver)) = get_device_info(dev, CL_DEVICE_VERSION) {
+                    // for global int32 atomics support
+                    return ver.as_slice() >= b"1.1";
+                }
+                false
+            })
+            .filter_map(|dev| {
+                let max_clock = get_device_info(dev, CL_DEVICE_MAX_CLOCK_FREQUENCY).ok()?;
+                let max_cu = get_device_info(dev, CL_DEVICE_MAX_COMPUTE_UNITS).ok()?;
+                match (max_clock, max_cu) {
+                    (InfoType::Uint(clock), InfoType::Uint(cu)) => Some((dev, clock * cu)),
+                    _ => None,
+                }
+            })
+            .collect();
+
+        usable.sort_by_key(|&(_, compute)| Reverse(compute));
+
+        if usable.is_empty() {
+            return Ok(None);
+        }
+
+        let skipped_devices = device_count - usable.len();
+        if skipped_devices != 0 {
+            println!("skipped {skipped_devices} unavailable or unsupported GPU device(s).");
+        }
+
+        println!("usable devices (OpenCL support >= 1.1):");
+        let mut names = Vec::with_capacity(usable.len());
+        for (i, &(dev, compute)) in usable.iter().enumerate() {
+            let name = match get_device_info(dev, CL_DEVICE_NAME) {
+                Ok(InfoType::VecUchar(data)) => {
+                    // trim trailing nulls
+                    if let Some(pos) = data.iter().position(|&b| b == 0) {
+                        String::from_utf8_lossy(&data[..pos]).to_string()
+                    } else {
+                        String::from_utf8_lossy(&data).to_string()
+                    }
+                }
+                _ => "<failed to get name>".to_string(),
+            };
+            println!("{i}: {name}, effective compute {compute} MHz");
+            names.push(name);
+        }
+
+        println!("\nusing device 0.");
+
+        let device = Device::new(usable[0].0);
+        let context = Context::from_device(&device)?;
+        let queue = CommandQueue::create_default(&context, 0)?;
+
+        Ok(Some(Self {
+            context,
+            queue,
+            source: Cow::Borrowed(KERNEL_SOURCE),
+            kernel: None,
+            bindings: None,
+            mitm_program: None,
+            skipped_devices,
+            device_name: names.swap_remove(0),
+            last_run: RunStats::default(),
+        }))
+    }
+
+    /// Build the kernel for `cfg`, or reuse the last one if it was built with the same options.
+    ///
+    /// The kernel checks each candidate against the first of the target shifts only, unless
+    /// there are `targets` of them.
+    fn build_kernel(&mut self, cfg: &SearchConfig, targets: usize) -> Result<(), BackendError> {
+        let hash_type = if size_of::<Hash>() == 4 {
+            "uint"
+        } else {
+            "ulong"
+        };
+        let alphabet_lit = cl_string_lit(cfg.alphabet.bytes());
+        let final_alphabet_lit = cl_string_lit(cfg.final_alphabet.bytes());
+        let seq_len = cfg.max_len - PAR_LEN;
+        let prime = cfg.hash.prime;
+
+        let mut options = format!(
+            "-D PAR_LEN={PAR_LEN} \
+            -D SEQ_LEN={seq_len} \
+            -D VEC_LEN={VEC_LEN} \
+            -D FNV_PRIME={prime}u \
+            -D HASH_T={hash_type} \
+            -D 'ALPHABET_LIT=\"{alphabet_lit}\"' \
+            -D 'FINAL_ALPHABET_LIT=\"{final_alphabet_lit}\"' \
+            -Werror",
+        );
+        if targets > 1 {
+            options += " -D MULTI_TARGET";
+        }
+        if targets >= BLOOM_MIN_TARGETS {
+            options += " -D TARGET_FILTER";
+        }
+
+        if !matches!(&self.kernel, Some((built, ..)) if *built == options) {
+            let program =
+                Program::create_and_build_from_source(&self.context, &self.source, &options)
+                    .map_err(|log| BackendError(format!("kernel failed to build: {log}")))?;
+            let kernel = Kernel::create(&program, "find_collisions")
+                .map_err(|e| BackendError(format!("OpenCL error: {e:?}")))?;
+            self.kernel = Some((options, program, kernel));
+            self.bindings = None;
+        }
+        Ok(())
+    }
+
+    /// Run the kernel last built by [`Self::build_kernel`], with the sorted `target_shifts` of
+    /// every target.
+    ///
+    /// Only the prefix hash argument is set if the last run's buffers can be reused.
+    fn run(&mut self, cfg: &SearchConfig, target_shifts: &[Hash]) -> Result<Vec<Vec<u8>>, ClError> {
+        // index of the prefix hash in the kernel's arguments, the only one set for every run
+        const PREFIX_HASH_ARG: u32 = 1;
+
+        let pre_launch = Instant::now();
+        let alphabet_len = cfg.alphabet.bytes().len();
+        let final_alphabet_len = cfg.final_alphabet.bytes().len();
+        let total_len = cfg.max_len;
+
+        let prefix_hash = cfg.hash.typed::<Hash>().hash(&cfg.prefix);
+
+        let work_items = alphabet_len.pow(PAR_LEN as u32);
+        let work_size = work_items.div_ceil(VEC_LEN).next_multiple_of(BLOCK_SIZE);
+
+        let analytic_collisions = (alphabet_len as f64).powi(total_len as i32 - 1)
+            * final_alphabet_len as f64
+            * target_shifts.len() as f64
+            / 256f64.powi(size_of::<Hash>() as i32);
+        // the hashes of names from small alphabets can be clustered, which the analytic estimate
+        // assumes they aren't
+        let positions: Vec<_> = std::iter::repeat_n(&cfg.alphabet, total_len - 1)
+            .chain([&cfg.final_alphabet])
+            .collect();
+        let sampled_collisions = density::estimate(
+            &cfg.prefix,
+            &positions,
+            &cfg.suffix,
+            &cfg.targets,
+            &cfg.hash,
+            DENSITY_SAMPLES,
+            &mut SplitMix64::new(0),
+        )
+        .sampled;
+        let expected_collisions = analytic_collisions.max(sampled_collisions);
+        let buf_len = (1.5 * expected_collisions) as usize + 100; // safety margin
+
+        // a larger results buffer than needed can be reused as well
+        let reusable = matches!(
+            &self.bindings,
+            Some(b) if b.target_shifts == target_shifts
+                && b.work_items == work_items
+                && b.buf_len >= buf_len
+        );
+        if !reusable {
+            // drop the old buffers before allocating new ones
+            self.bindings = None;
+            self.bindings = Some(self.bind(target_shifts, work_items, buf_len, total_len)?);
+        }
+        let kernel = &self.kernel.as_ref().unwrap().2;
+        let bindings = self.bindings.as_mut().unwrap();
+        let buf_len = bindings.buf_len;
+
+        let kernel_event = unsafe {
+            static ZERO: [u32; 1] = [0];
+            kernel.set_arg(PREFIX_HASH_ARG, &prefix_hash)?;
+            // the queue is in order, so the kernel starts after the count is reset
+            self.queue.enqueue_write_buffer(
+                &mut bindings.results_count,
+                CL_NON_BLOCKING,
+                0,
+                &ZERO,
+                &[],
+            )?;
+            self.queue.enqueue_nd_range_kernel(
+                kernel.get(),
+                1,
+                ptr::null(),
+                [work_size].as_ptr(),
+                [BLOCK_SIZE].as_ptr(),
+                &[],
+            )?
+        };
+        let launch_overhead = pre_launch.elapsed();
+        let pre_kernel = Instant::now();
+
+        // wait for kernel completion and read result count
+        let mut results_count = 0;
+        unsafe {
+            self.queue.enqueue_read_buffer(
+                &bindings.results_count,
+                CL_BLOCKING,
+                0,
+                std::slice::from_mut(&mut results_count),
+                &[kernel_event.get()],
+            )?
+        };
+        let kernel_time = pre_kernel.elapsed();
+
+        let found = results_count as usize;
+        let dropped = found.saturating_sub(buf_len);
+        if dropped != 0 {
+            println!("results buffer overflowed, {dropped} of {found} matches were dropped.");
+        }
+        let results_count = found.min(buf_len);
+
+        // copy initialized portion of results buffer
+        let mut results = vec![0; results_count.max(1) * total_len];
+        unsafe {
+            self.queue.enqueue_read_buffer(
+                &bindings.results,
+                CL_BLOCKING,
+                0,
+                results.as_mut_slice(),
+                &[],
+            )?
+        };
+
+        self.last_run = RunStats {
+            kernel_time,
+            found,
+            dropped,
+            launch_overhead,
+            device_mem: bindings.device_mem,
+            ..RunStats::default()
+        };
+
+        Ok(results[..results_count * total_len]
+            .chunks_exact(total_len)
+            .map(|res| {
+                let len = res.iter().position(|&b| b == 0).unwrap_or(res.len());
+                res[..len].to_vec()
+            })
+            .filter(|m| m.len() >= cfg.min_len)
+            .collect())
+    }
+
+    /// Allocate the buffers of a run and bind every argument of the built kernel to them,
+    /// except for the prefix hash.
+    fn bind(
+        &self,
+        target_shifts: &[Hash],
+        work_items: usize,
+        buf_len: usize,
+        total_len: usize,
+    ) -> Result<Bindings, ClError> {
+        let buf_len_bytes = buf_len * total_len;
+        if buf_len_bytes > u32::MAX as usize {
+            panic!("results buffer too big")
+        }
+
+        println!("using {buf_len} element results buffer\n");
+
+        let results_dev = unsafe {
+            Buffer::<u8>::create(
+                &self.context,
+                CL_MEM_WRITE_ONLY,
+                buf_len_bytes,
+                ptr::null_mut(),
+            )?
+        };
+        // the kernel doesn't write to the targets, so they can be copied from a shared slice
+        let targets_dev = unsafe {
+            Buffer::<Hash>::create(
+                &self.context,
+                CL_MEM_READ_ONLY | CL_MEM_COPY_HOST_PTR,
+                target_shifts.len(),
+                target_shifts.as_ptr() as *mut c_void,
+            )?
+        };
+        // the kernel only reads the filter if it was built with one, but still takes the
+        // argument
+        let filter = match target_shifts.len() {
+            BLOOM_MIN_TARGETS.. => {
+                let mut filter = BloomFilter::with_capacity(target_shifts.len());
+                for &shift in target_shifts {
+                    filter.insert((shift >> 8).into());
+                }
+                filter.words().to_vec()
+            }
+            _ => vec![0],
+        };
+        let filter_dev = unsafe {
+            Buffer::<u64>::create(
+                &self.context,
+                CL_MEM_READ_ONLY | CL_MEM_COPY_HOST_PTR,
+                filter.len(),
+                filter.as_ptr() as *mut c_void,
+            )?
+        };
+        // reset before each run
+        let results_count_dev =
+            unsafe { Buffer::<u32>::create(&self.context, CL_MEM_READ_WRITE, 1, ptr::null_mut())? };
+
+        let kernel = &self.kernel.as_ref().unwrap().2;
+        unsafe {
+            kernel.set_arg(0, &(work_items as u64))?;
+            kernel.set_arg(2, &targets_dev)?;
+            kernel.set_arg(3, &(target_shifts.len() as u32))?;
+            kernel.set_arg(4, &filter_dev)?;
+            kernel.set_arg(5, &(filter.len() as u32 - 1))?;
+            kernel.set_arg(6, &results_dev)?;
+            kernel.set_arg(7, &(buf_len as u32))?;
+            kernel.set_arg(8, &results_count_dev)?;
+        }
+
+        Ok(Bindings {
+            target_shifts: target_shifts.to_vec(),
+            work_items,
+            buf_len,
+            results: results_dev,
+            results_count: results_count_dev,
+            _targets: targets_dev,
+            _filter: filter_dev,
+            device_mem: buf_len_bytes
+                + size_of_val(target_shifts)
+                + size_of_val(filter.as_slice())
+                + size_of::<u32>(),
+        })
+    }
+}
+
+/// Buffers describing the positions of one half of a meet-in-the-middle search, as the
+/// kernels of [`MITM_SOURCE`] take them.
+struct HalfBuffers {
+    chars: Buffer<u8>,
+    offsets: Buffer<u32>,
+    sizes: Buffer<u32>,
+    strides: Buffer<u64>,
+    len: u32,
+    /// Number of combinations of the half.
+    combinations: u64,
+    /// Device memory allocated for the buffers, in bytes.
+    device_mem: usize,
+}
+
+impl OpenClBackend {
+    /// Build the meet-in-the-middle kernels for `cfg`, or reuse the last ones if they were
+    /// built with the same options.
+    fn build_mitm(&mut self, cfg: &MitmConfig) -> Result<(), BackendError> {
+        let prime = cfg.hash.prime as Hash;
+        let options = format!(
+            "-D FNV_PRIME={prime}u -D PRIME_INV={}u -Werror",
+            fs_hardblast_core::HashWord::minv(prime)
+        );
+        if !matches!(&self.mitm_program, Some((built, _)) if *built == options) {
+            let program =
+                Program::create_and_build_from_source(&self.context, MITM_SOURCE, &options)
+                    .map_err(|log| BackendError(format!("kernel failed to build: {log}")))?;
+            self.mitm_program = Some((options, program));
+        }
+        Ok(())
+    }
+
+    /// Upload the positions of one half of a meet-in-the-middle search.
+    fn half_buffers(&self, positions: &[DynAlphabet]) -> Result<HalfBuffers, ClError> {
+        let mut chars = Vec::new();
+        let mut offsets = Vec::new();
+        let mut sizes = Vec::new();
+        for position in positions {
+            offsets.push(chars.len() as u32);
+            sizes.push(position.bytes().len() as u32);
+            chars.extend_from_slice(position.bytes());
+        }
+        // the first position is the most significant digit
+        let mut strides = vec![1u64; positions.len()];
+        for i in (0..positions.len().saturating_sub(1)).rev() {
+            strides[i] = strides[i + 1] * sizes[i + 1] as u64;
+        }
+        let combinations = strides.first().map_or(1, |&s| s * sizes[0] as u64);
+
+        // buffers can't be empty, and the kernels don't read past `len` positions
+        let device_mem = size_of_val(chars.as_slice())
+            + size_of_val(offsets.as_slice())
+            + size_of_val(sizes.as_slice())
+            + size_of_val(strides.as_slice());
+        chars.push(0);
+        offsets.push(0);
+        sizes.push(1);
+        strides.push(1);
+        fn create<T>(context: &Context, data: &[T]) -> Result<Buffer<T>, ClError> {
+            unsafe {
+                Buffer::create(
+                    context,
+                    CL_MEM_READ_ONLY | CL_MEM_COPY_HOST_PTR,
+                    data.len(),
+                    data.as_ptr() as *mut c_void,
+                )
+            }
+        }
+        Ok(HalfBuffers {
+            chars: create(&self.context, &chars)?,
+            offsets: create(&self.context, &offsets)?,
+            sizes: create(&self.context, &sizes)?,
+            strides: create(&self.context, &strides)?,
+            len: positions.len() as u32,
+            combinations,
+            device_mem,
+        })
+    }
+
+    /// Run the meet-in-the-middle kernels last built by [`Self::build_mitm`] on `cfg`.
+    fn run_mitm(&mut self, cfg: &MitmConfig) -> Result<Vec<Vec<u8>>, ClError> {
+        let pre_launch = Instant::now();
+        let program = &self.mitm_program.as_ref().unwrap().1;
+        let build_table = Kernel::create(program, "build_table")?;
+        let sort_step = Kernel::create(program, "sort_step")?;
+        let probe = Kernel::create(program, "probe")?;
+
+        let (front, back) = cfg.positions.split_at(cfg.split());
+        let fnv = cfg.hash.typed::<Hash>();
+        let front_dev = self.half_buffers(front)?;
+        let back_dev = self.half_buffers(back)?;
+
+        let table_len = (front_dev.combinations as usize)
+            .next_power_of_two()
+            .max(BLOCK_SIZE);
+        let table_dev = unsafe {
+            Buffer::<u64>::create(&self.context, CL_MEM_READ_WRITE, table_len, ptr::null_mut())?
+        };
+
+        let mut targets: Vec<Hash> = cfg
+            .targets
+            .iter()
+            .map(|&t| fnv.unextend(t as Hash, &cfg.suffix))
+            .collect();
+        targets.sort_unstable();
+        targets.dedup();
+        let targets_dev = unsafe {
+            Buffer::<Hash>::create(
+                &self.context,
+                CL_MEM_READ_ONLY | CL_MEM_COPY_HOST_PTR,
+                targets.len(),
+                targets.as_ptr() as *mut c_void,
+            )?
+        };
+
+        let expected =
+            front_dev.combinations as f64 * back_dev.combinations as f64 * targets.len() as f64
+                / 256f64.powi(size_of::<Hash>() as i32);
+        let buf_len = (1.5 * expected) as usize + 100; // safety margin
+        let results_dev = unsafe {
+            Buffer::<u32>::create(
+                &self.context,
+                CL_MEM_WRITE_ONLY,
+                2 * buf_len,
+                ptr::null_mut(),
+            )?
+        };
+        let mut results_count_dev =
+            unsafe { Buffer::<u32>::create(&self.context, CL_MEM_READ_WRITE, 1, ptr::null_mut())? };
+
+        let enqueue = |kernel: &Kernel, work_size: usize| unsafe {
+            self.queue.enqueue_nd_range_kernel(
+                kernel.get(),
+                1,
+                ptr::null(),
+                [work_size].as_ptr(),
+                [BLOCK_SIZE].as_ptr(),
+                &[],
+            )
+        };
+        let set_half_args = |kernel: &Kernel, first: u32, half: &HalfBuffers| unsafe {
+            kernel.set_arg(first, &half.chars)?;
+            kernel.set_arg(first + 1, &half.offsets)?;
+            kernel.set_arg(first + 2, &half.sizes)?;
+            kernel.set_arg(first + 3, &half.strides)?;
+            kernel.set_arg(first + 4, &half.len)
+        };
+
+        // the queue is in order, so each kernel starts once the previous one is done
+        unsafe {
+            build_table.set_arg(0, &front_dev.combinations)?;
+            build_table.set_arg(1, &fnv.hash(&cfg.prefix))?;
+            set_half_args(&build_table, 2, &front_dev)?;
+            build_table.set_arg(7, &table_dev)?;
+        }
+        enqueue(&build_table, table_len)?;
+
+        unsafe { sort_step.set_arg(0, &table_dev)? };
+        let mut k = 2;
+        while k <= table_len {
+            let mut j = k / 2;
+            while j > 0 {
+                unsafe {
+                    sort_step.set_arg(1, &(j as u32))?;
+                    sort_step.set_arg(2, &(k as u32))?;
+                }
+                enqueue(&sort_step, table_len)?;
+                j /= 2;
+            }
+            k *= 2;
+        }
+
+        let kernel_event = unsafe {
+            static ZERO: [u32; 1] = [0];
+            self.queue.enqueue_write_buffer(
+                &mut results_count_dev,
+                CL_NON_BLOCKING,
+                0,
+                &ZERO,
+                &[],
+            )?;
+            probe.set_arg(0, &back_dev.combinations)?;
+            probe.set_arg(1, &targets_dev)?;
+            probe.set_arg(2, &(targets.len() as u32))?;
+            set_half_args(&probe, 3, &back_dev)?;
+            probe.set_arg(8, &table_dev)?;
+            probe.set_arg(9, &(table_len as u32))?;
+            probe.set_arg(10, &results_dev)?;
+            probe.set_arg(11, &(buf_len as u32))?;
+            probe.set_arg(12, &results_count_dev)?;
+            enqueue(
+                &probe,
+                (back_dev.combinations as usize).next_multiple_of(BLOCK_SIZE),
+            )?
+        };
+        let launch_overhead = pre_launch.elapsed();
+        let pre_kernel = Instant::now();
+
+        let mut results_count = 0;
+        unsafe {
+            self.queue.enqueue_read_buffer(
+                &results_count_dev,
+                CL_BLOCKING,
+                0,
+                std::slice::from_mut(&mut results_count),
+                &[kernel_event.get()],
+            )?
+        };
+        let kernel_time = pre_kernel.elapsed();
+
+        let found = results_count as usize;
+        let dropped = found.saturating_sub(buf_len);
+        if dropped != 0 {
+            println!("results buffer overflowed, {dropped} of {found} matches were dropped.");
+        }
+        let results_count = found.min(buf_len);
+        let mut results = vec![0u32; 2 * results_count.max(1)];
+        unsafe {
+            self.queue.enqueue_read_buffer(
+                &results_dev,
+                CL_BLOCKING,
+                0,
+                results.as_mut_slice(),
+                &[],
+            )?
+        };
+
+        self.last_run = RunStats {
+            kernel_time,
+            found,
+            dropped,
+            launch_overhead,
+            device_mem: front_dev.device_mem
+                + back_dev.device_mem
+                + table_len * size_of::<u64>()
+                + size_of_val(targets.as_slice())
+                + 2 * buf_len * size_of::<u32>()
+                + size_of::<u32>(),
+            ..RunStats::default()
+        };
+
+        Ok(results[..2 * results_count]
+            .chunks_exact(2)
+            .map(|pair| [decode(front, pair[0]), decode(back, pair[1])].concat())
+            .collect())
+    }
+}
+
+impl Backend for OpenClBackend {
+    fn name(&self) -> &'static str {
+        "opencl"
+    }
+
+    fn device(&self) -> String {
+        format!("{} (OpenCL)", self.device_name)
+    }
+
+    fn supports(&self, cfg: &SearchConfig) -> Result<(), BackendError> {
+        let unsupported = |msg: String| Err(BackendError(msg));
+
+        if cfg.hash.width != HashWidth::Bits32 {
+            return unsupported("the OpenCL kernel only supports 32-bit hashes".into());
+        }
+        if cfg.hash.variant != HashVariant::MulAdd {
+            return unsupported("the OpenCL kernel only supports mul-add hashes".into());
+        }
+        // the kernel indexes the alphabet with a `char`
+        if cfg.alphabet.bytes().len() > i8::MAX as usize {
+            return unsupported(format!(
+                "the OpenCL kernel supports alphabets of at most {} characters",
+                i8::MAX
+            ));
+        }
+        // nul bytes terminate matches in the results buffer
+        if cfg.alphabet.contains(0u32) || cfg.final_alphabet.contains(0u32) {
+            return unsupported("the OpenCL kernel doesn't support nul bytes in alphabets".into());
+        }
+        Ok(())
+    }
+
+    fn search(&mut self, cfg: &SearchConfig) -> Result<Vec<Vec<u8>>, BackendError> {
+        self.supports(cfg)?;
+
+        let fnv = cfg.hash.typed::<Hash>();
+        let mut target_shifts: Vec<_> = cfg
+            .targets
+            .iter()
+            .map(|&t| PrecomputedSuffix::new(&cfg.suffix, t as Hash, &fnv).target_shift)
+            .collect();
+        target_shifts.sort_unstable();
+        target_shifts.dedup();
+
+        // the kernel can't find strings shorter than MIN_LEN, which are few enough to search on
+        // the host
+        let pre_host = Instant::now();
+        let mut matches = search_short(
+            cfg,
+            &target_shifts,
+            cfg.min_len..=cfg.max_len.min(MIN_LEN - 1),
+        );
+        let host_time = pre_host.elapsed();
+
+        self.last_run = RunStats::default();
+        if cfg.max_len >= MIN_LEN {
+            let kernel_cfg = SearchConfig {
+                min_len: cfg.min_len.max(MIN_LEN),
+                ..cfg.clone()
+            };
+            self.build_kernel(&kernel_cfg, target_shifts.len())?;
+            let found = self
+                .run(&kernel_cfg, &target_shifts)
+                .map_err(|e| BackendError(format!("OpenCL error: {e:?}")))?;
+            matches.extend(found);
+        }
+        self.last_run.host_time = host_time;
+        Ok(matches)
+    }
+
+    fn supports_mitm(&self, cfg: &MitmConfig) -> Result<(), BackendError> {
+        if cfg.hash.width != HashWidth::Bits32 || cfg.hash.variant != HashVariant::MulAdd {
+            return Err(BackendError(
+                "the OpenCL kernels only meet in the middle of 32-bit mul-add hashes".into(),
+            ));
+        }
+        let split = cfg.split();
+        if backend::combinations(&cfg.positions[..split]) > MITM_MAX_FRONTS
+            || backend::combinations(&cfg.positions[split..]) > u32::MAX as f64
+        {
+            return Err(BackendError(
+                "the OpenCL kernels can't meet in the middle of this many names".into(),
+            ));
+        }
+        Ok(())
+    }
+
+    fn search_mitm(&mut self, cfg: &MitmConfig) -> Result<Vec<Vec<u8>>, BackendError> {
+        self.supports_mitm(cfg)?;
+        self.build_mitm(cfg)?;
+        self.run_mitm(cfg)
+            .map_err(|e| BackendError(format!("OpenCL error: {e:?}")))
+    }
+}
+
+/// Characters of the half of a meet-in-the-middle search with the given index, whose first
+/// position is the most significant digit.
+fn decode(positions: &[DynAlphabet], mut index: u32) -> Vec<u8> {
+    let mut chars = vec![0; positions.len()];
+    for (c, position) in chars.iter_mut().zip(positions).rev() {
+        let bytes = position.bytes();
+        *c = bytes[index as usize % bytes.len()];
+        index /= bytes.len() as u32;
+    }
+    chars
+}
+
+/// Find the matches of `cfg` with `lens` characters on the host, given the sorted
+/// `target_shifts` of every target.
+///
+/// Every string of all but the last character is enumerated, and the last one solved for like
+/// the kernel does, so this is only fast for short lengths.
+fn search_short(
+    cfg: &SearchConfig,
+    target_shifts: &[Hash],
+    lens: RangeInclusive<usize>,
+) -> Vec<Vec<u8>> {
+    let fnv = cfg.hash.typed::<Hash>();
+    let prefix_hash = fnv.hash(&cfg.prefix);
+    let alphabet = cfg.alphabet.bytes();
+
+    let mut matches = Vec::new();
+    for len in lens {
+        // the target shift is the hash of prefix|m
+        if len == 0 {
+            if target_shifts.binary_search(&prefix_hash).is_ok() {
+                matches.push(Vec::new());
+            }
+            continue;
+        }
+
+        let mut indices = vec![0; len - 1];
+        let mut middle = vec![0; len];
+        loop {
+            for (b, &i) in middle.iter_mut().zip(&indices) {
+                *b = alphabet[i];
+            }
+            let base = fnv
+                .extend(prefix_hash, &middle[..len - 1])
+                .wrapping_mul(fnv.prime);
+            // the target shifts a single char away from base are the ones in [base, base + 255],
+            // which may wrap around the end of the sorted list
+            let lo = target_shifts.partition_point(|&shift| shift < base);
+            for n in 0..target_shifts.len() {
+                let solution = target_shifts[(lo + n) % target_shifts.len()].wrapping_sub(base);
+                if solution > 0xff {
+                    break;
+                }
+                if cfg.final_alphabet.contains(solution) {
+                    middle[len - 1] = solution as u8;
+                    matches.push(middle.clone());
+                }
+            }
+
+            // move on to the next string, in lexicographic order of the indices
+            let Some(pos) = indices.iter().rposition(|&i| i + 1 < alphabet.len()) else {
+                break;
+            };
+            indices[pos] += 1;
+            indices[pos + 1..].fill(0);
+        }
+    }
+    matches
+}
+
+/// Escape `bytes` as the contents of an OpenCL C string literal.
+fn cl_string_lit(bytes: &[u8]) -> String {
+    bytes.iter().fold(String::new(), |mut s, b| {
+        write!(&mut s, "\\x{b:02x}").unwrap();
+        s
+    })
+}
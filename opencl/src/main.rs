@@ -1,276 +1,234 @@
-use std::{cmp::Reverse, ffi::c_void, fmt::Write, process::exit, ptr, time::Instant};
-
-use cl3::{
-    ext::{
-        CL_BLOCKING, CL_DEVICE_AVAILABLE, CL_DEVICE_MAX_CLOCK_FREQUENCY,
-        CL_DEVICE_MAX_COMPUTE_UNITS, CL_DEVICE_VERSION, CL_MEM_READ_WRITE, CL_MEM_USE_HOST_PTR,
-        CL_MEM_WRITE_ONLY,
-    },
-    info_type::InfoType,
-};
-use opencl3::{
-    command_queue::CommandQueue,
-    context::Context,
-    device::{CL_DEVICE_NAME, CL_DEVICE_TYPE_GPU, Device, get_all_devices, get_device_info},
-    error_codes::ClError,
-    kernel::{ExecuteKernel, Kernel},
-    memory::Buffer,
-    program::Program,
+use std::{
+    collections::BTreeMap,
+    io::{self, IsTerminal},
+    path::PathBuf,
+    process::exit,
 };
 
-type Hash = u32;
-
-const FNV_PRIME: Hash = 37; // 133 for u64 hashes!
-const ALPHABET: &[u8] = b".0123456789_abcdefghijklmnopqrstuvwxyz";
+use clap::{Parser, Subcommand};
+use fs_hardblast_core::{
+    Hash, HashFlavor, HashWidth,
+    alphabet::DynAlphabet,
+    backend::{Backend, SearchConfig},
+    filter::MatchFilter,
+    fnv_hash,
+    output::print_match,
+    status,
+    template::{Fields, OutputTemplate},
+    usage,
+};
+use fs_hardblast_opencl::{OpenClBackend, PAR_LEN, diagnose};
 
-const PREFIX: &[u8] = b"/other/";
-const SUFFIX: &[u8] = b".dcx";
+// Defaults for the search parameters, all of which can be set from the command line.
+const ALPHABET: &str = ".0123456789_abcdefghijklmnopqrstuvwxyz";
+const PREFIX: &str = "/other/";
+const SUFFIX: &str = ".dcx";
 const TARGET: Hash = 0xd7255946;
-
-const PAR_LEN: usize = 4; // Assign a gpu thread to each prefix of this length
 const SEQ_LEN: usize = 5; // Search for collisions of this many extra chars
-const VEC_LEN: usize = 8; // SIMD vector size in kernel, tune for your GPU
 
-const BLOCK_SIZE: usize = 256; // tune this for your GPU
-const TOTAL_LEN: usize = PAR_LEN + SEQ_LEN;
+/// Find FNV hash collisions in FromSoftware file paths using an OpenCL GPU.
+#[derive(Debug, Parser)]
+struct Args {
+    /// Print matches as raw bytes, even when stdout is a terminal.
+    #[arg(long)]
+    raw: bool,
+
+    /// Start of the path, before the searched characters.
+    #[arg(long, default_value = PREFIX)]
+    prefix: String,
+
+    /// End of the path, after the searched characters.
+    #[arg(long, default_value = SUFFIX)]
+    suffix: String,
+
+    /// Hash to find collisions for, in hex. Defaults to `d7255946`.
+    #[arg(long, value_name = "HASH", value_parser = parse_hash)]
+    target: Option<Hash>,
+
+    /// Maximum number of searched characters. Strings of less than `PAR_LEN + 2` (6)
+    /// characters are searched on the host rather than by the kernel.
+    #[arg(long, value_name = "LEN", default_value_t = PAR_LEN + SEQ_LEN, value_parser = parse_max_len)]
+    max_len: usize,
+
+    /// Characters making up the searched string.
+    #[arg(long, value_name = "CHARS", default_value = ALPHABET, value_parser = parse_alphabet)]
+    alphabet: DynAlphabet,
+
+    /// Characters the searched string may end with. Defaults to `--alphabet`.
+    #[arg(long, value_name = "CHARS", value_parser = parse_alphabet)]
+    final_alphabet: Option<DynAlphabet>,
+
+    /// Write the resolution status of each target to this file.
+    #[arg(long, value_name = "FILE")]
+    status_file: Option<PathBuf>,
+
+    /// Format of the lines printed for each match, e.g. `{prefix}{middle}{suffix}\t{hash:x}`.
+    #[arg(
+        long,
+        value_name = "TEMPLATE",
+        default_value = "{prefix}{middle}{suffix}"
+    )]
+    output_template: OutputTemplate,
+
+    /// Only report matches with at least this many distinct characters in the middle.
+    #[arg(long, value_name = "COUNT", default_value_t = 0)]
+    min_distinct: usize,
+
+    /// Only report matches whose middle has at least this much Shannon entropy, in bits per
+    /// character.
+    #[arg(long, value_name = "BITS", default_value_t = 0.0)]
+    min_entropy: f64,
+
+    /// Fail instead of carrying on when GPU devices had to be skipped or when matches didn't
+    /// fit in the results buffer.
+    #[arg(long)]
+    strict: bool,
+
+    #[command(subcommand)]
+    command: Option<Command>,
+}
 
-fn main() -> Result<(), Err> {
-    let suffix = PrecomputedSuffix::new(SUFFIX, TARGET);
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// Print a report of all OpenCL platforms and devices, and test each device with a trivial
+    /// kernel.
+    DiagnoseOpencl,
+}
 
-    let prefix_hash = fnv_hash(PREFIX);
+fn main() {
+    let args = Args::parse();
+    let escape = !args.raw && io::stdout().is_terminal();
 
-    let devices = get_all_devices(CL_DEVICE_TYPE_GPU)?;
-    let mut usable: Vec<_> = devices
-        .into_iter()
-        .filter(|&dev| {
-            match get_device_info(dev, CL_DEVICE_AVAILABLE) {
-                Ok(InfoType::Uint(1..)) => (),
-                _ => return false,
-            }
-            if let Ok(InfoType::VecUchar(ver)) = get_device_info(dev, CL_DEVICE_VERSION) {
-                // for global int32 atomics support
-                return ver.as_slice() >= b"1.1";
-            }
-            false
-        })
-        .filter_map(|dev| {
-            let max_clock = get_device_info(dev, CL_DEVICE_MAX_CLOCK_FREQUENCY).ok()?;
-            let max_cu = get_device_info(dev, CL_DEVICE_MAX_COMPUTE_UNITS).ok()?;
-            match (max_clock, max_cu) {
-                (InfoType::Uint(clock), InfoType::Uint(cu)) => Some((dev, clock * cu)),
-                _ => None,
-            }
-        })
-        .collect();
+    if let Some(Command::DiagnoseOpencl) = args.command {
+        diagnose::run();
+        return;
+    }
 
-    usable.sort_by_key(|&(_, compute)| Reverse(compute));
+    let prefix = args.prefix.as_bytes();
+    let suffix = args.suffix.as_bytes();
+    let target = args.target.unwrap_or(TARGET);
+    let alphabet = args.alphabet.clone();
+    let cfg = SearchConfig {
+        prefix: prefix.to_owned(),
+        suffix: suffix.to_owned(),
+        targets: vec![target.into()],
+        hash: HashFlavor::default(),
+        final_alphabet: args
+            .final_alphabet
+            .clone()
+            .unwrap_or_else(|| alphabet.clone()),
+        alphabet,
+        min_len: 1,
+        max_len: args.max_len,
+    };
 
-    if usable.is_empty() {
-        println!("no usable OpenCL GPU device found.");
+    let mut backend = match OpenClBackend::new() {
+        Ok(Some(backend)) => backend,
+        Ok(None) => {
+            println!("no usable OpenCL GPU device found.");
+            println!("run with `diagnose-opencl` for details about the available devices.");
+            exit(status::EXIT_NO_DEVICE);
+        }
+        Err(e) => {
+            println!("{e}.");
+            println!("run with `diagnose-opencl` for details about the available devices.");
+            exit(1);
+        }
+    };
+    if backend.skipped_devices != 0 && args.strict {
+        eprintln!("error: GPU devices were skipped in strict mode");
         exit(1);
     }
 
-    println!("usable devices (OpenCL support >= 1.1):");
-    for (i, &(dev, compute)) in usable.iter().enumerate() {
-        let name = match get_device_info(dev, CL_DEVICE_NAME) {
-            Ok(InfoType::VecUchar(data)) => {
-                // trim trailing nulls
-                if let Some(pos) = data.iter().position(|&b| b == 0) {
-                    String::from_utf8_lossy(&data[..pos]).to_string()
-                } else {
-                    String::from_utf8_lossy(&data).to_string()
-                }
-            }
-            _ => "<failed to get name>".to_string(),
-        };
-        println!("{i}: {name}, effective compute {compute} MHz");
-    }
-
-    println!("\nusing device 0.");
-
-    let device = Device::new(usable[0].0);
-    let context = Context::from_device(&device)?;
-    let queue = CommandQueue::create_default(&context, 0)?;
-
-    let hash_type = if size_of::<Hash>() == 4 {
-        "uint"
-    } else {
-        "ulong"
-    };
-    let alphabet_lit = ALPHABET.iter().fold(String::new(), |mut s, b| {
-        write!(&mut s, "\\x{b:02x}").unwrap();
-        s
+    let results = backend.search(&cfg).unwrap_or_else(|e| {
+        eprintln!("search failed: {e}");
+        exit(1);
     });
-
-    let program = Program::create_and_build_from_source(
-        &context,
-        include_str!("kernel.cl"),
-        &format!(
-            "-D PAR_LEN={PAR_LEN} \
-            -D SEQ_LEN={SEQ_LEN} \
-            -D VEC_LEN={VEC_LEN} \
-            -D FNV_PRIME={FNV_PRIME} \
-            -D HASH_T={hash_type} \
-            -D 'ALPHABET_LIT=\"{alphabet_lit}\"' \
-            -Werror",
-        ),
-    )
-    .expect("kernel failed to build");
-
-    let kernel = Kernel::create(&program, "find_collisions")?;
-
-    let work_items = ALPHABET.len().pow(PAR_LEN as u32);
-    let work_size = work_items.div_ceil(VEC_LEN).next_multiple_of(BLOCK_SIZE);
-
-    let expected_collisions =
-        (ALPHABET.len() as f64).powi(TOTAL_LEN as i32) / 256f64.powi(size_of::<Hash>() as i32);
-    let buf_len = (1.5 * expected_collisions) as usize + 100; // safety margin
-    let buf_len_bytes = buf_len * TOTAL_LEN;
-    if buf_len_bytes > u32::MAX as usize {
-        panic!("results buffer too big")
+    let run = backend.last_run;
+    if run.dropped != 0 && args.strict {
+        eprintln!("error: results were truncated in strict mode");
+        exit(1);
     }
 
-    println!("using {buf_len} element results buffer\n");
-
-    let results_dev = unsafe {
-        Buffer::<u8>::create(&context, CL_MEM_WRITE_ONLY, buf_len_bytes, ptr::null_mut())?
-    };
-    let results_count_dev = unsafe {
-        static ZERO: &u32 = &0;
-        Buffer::<u32>::create(
-            &context,
-            CL_MEM_READ_WRITE | CL_MEM_USE_HOST_PTR,
-            1,
-            ZERO as *const u32 as *mut c_void,
-        )?
-    };
-
-    let pre_kernel = Instant::now();
-
-    let kernel_event = unsafe {
-        ExecuteKernel::new(&kernel)
-            .set_arg(&(work_items as u64))
-            .set_arg(&prefix_hash)
-            .set_arg(&suffix.target_shift)
-            .set_arg(&results_dev)
-            .set_arg(&(buf_len as u32))
-            .set_arg(&results_count_dev)
-            .set_global_work_size(work_size)
-            .set_local_work_size(BLOCK_SIZE)
-            .enqueue_nd_range(&queue)?
-    };
-
-    // wait for kernel completion and read result count
-    let mut results_count = 0;
-    unsafe {
-        queue.enqueue_read_buffer(
-            &results_count_dev,
-            CL_BLOCKING,
-            0,
-            std::slice::from_mut(&mut results_count),
-            &[kernel_event.get()],
-        )?
-    };
-    let results_count = results_count.min(buf_len as u32) as usize;
-    let kernel_time = pre_kernel.elapsed();
-
-    // copy initialized portion of results buffer
-    let mut results = vec![0; results_count.max(1) * TOTAL_LEN];
-    unsafe {
-        queue.enqueue_read_buffer(&results_dev, CL_BLOCKING, 0, results.as_mut_slice(), &[])?
+    // print matches
+    let filter = MatchFilter {
+        min_distinct: args.min_distinct,
+        min_entropy: args.min_entropy,
     };
 
-    // print matches
+    let mut length_counts = BTreeMap::new();
     let mut full_collision = Vec::new();
-    for res in results[..results_count].chunks_exact(TOTAL_LEN) {
-        let len = res.iter().position(|&b| b == 0).unwrap_or(res.len());
-
+    let mut line = Vec::new();
+    for middle in &results {
         full_collision.clear();
-        full_collision.extend_from_slice(PREFIX);
-        full_collision.extend_from_slice(&res[..len]);
-        full_collision.extend_from_slice(SUFFIX);
-
-        println!("{}", String::from_utf8_lossy(&full_collision));
-        assert_eq!(fnv_hash(&full_collision), TARGET);
-    }
-
-    println!("\nfound {} solutions in {:?}", results_count, kernel_time);
+        full_collision.extend_from_slice(prefix);
+        full_collision.extend_from_slice(middle);
+        full_collision.extend_from_slice(suffix);
+        assert_eq!(fnv_hash(&full_collision), target);
 
-    Ok(())
-}
-
-const fn fnv_hash(bytes: &[u8]) -> Hash {
-    let mut hash: Hash = 0;
-    let mut i = 0;
-    while i < bytes.len() {
-        hash = hash.wrapping_mul(FNV_PRIME).wrapping_add(bytes[i] as Hash);
-        i += 1;
-    }
-    hash
-}
-
-/// Precomputed information about the hash of a suffix.
-///
-/// Used to efficiently compute the combined hash of `base|suffix` given `hash(base)`
-/// as well as efficiently finding a single character `x` such that
-/// `hash(base|x|suffix) == target_hash`.
-#[derive(Debug, Clone, Copy)]
-#[allow(unused)]
-struct PrecomputedSuffix {
-    hash: Hash,
-    mult: Hash,
-    target_shift: Hash,
-}
-
-impl PrecomputedSuffix {
-    pub const fn new(suffix: &[u8], target_hash: Hash) -> Self {
-        // 64-bit modular inverse using 4 Newton-Raphson iterations
-        // From https://arxiv.org/abs/2204.04342
-        const fn minv32(a: Hash) -> Hash {
-            assert!(!a.is_multiple_of(2));
-
-            let mut x = (3 as Hash).wrapping_mul(a) ^ 2;
-            let mut y = (1 as Hash).wrapping_sub(a.wrapping_mul(x));
-
-            x = x.wrapping_mul(y.wrapping_add(1));
-            y = y.wrapping_mul(y);
-            x = x.wrapping_mul(y.wrapping_add(1));
-            y = y.wrapping_mul(y);
-            x = x.wrapping_mul(y.wrapping_add(1));
-            y = y.wrapping_mul(y);
-
-            x.wrapping_mul(y.wrapping_add(1))
+        if !filter.accepts(middle) {
+            continue;
         }
+        *length_counts.entry(middle.len()).or_insert(0) += 1;
+
+        let fields = Fields {
+            prefix,
+            middle,
+            suffix,
+            hash: target.into(),
+            hash_width: HashWidth::Bits32,
+        };
+        line.clear();
+        args.output_template.render(&fields, &mut line);
+        print_match(&line, escape);
+    }
 
-        let hash = fnv_hash(suffix);
-        let mult = FNV_PRIME.wrapping_pow(suffix.len() as u32);
-        let target_shift = target_hash.wrapping_sub(hash).wrapping_mul(minv32(mult));
-
-        Self {
-            hash,
-            mult,
-            target_shift,
-        }
+    let accepted: usize = length_counts.values().sum();
+    println!(
+        "\nfound {} solutions ({accepted} after filtering) in {:?}",
+        results.len(),
+        run.kernel_time
+    );
+    println!("kernel launch overhead: {:?}", run.launch_overhead);
+    println!("host search of short lengths: {:?}", run.host_time);
+    status::print_length_histogram(&length_counts);
+
+    let peak_rss = usage::peak_rss().map_or("unavailable".to_owned(), usage::fmt_bytes);
+    println!("peak host memory usage: {peak_rss}");
+    println!(
+        "device 0 memory allocated: {}",
+        usage::fmt_bytes(run.device_mem as u64)
+    );
+
+    let statuses = [status::TargetStatus {
+        target: target.into(),
+        matches: accepted,
+    }];
+    if let Some(path) = &args.status_file
+        && let Err(e) = status::write_status_file(path, &statuses, HashWidth::Bits32)
+    {
+        eprintln!("failed to write status file {}: {e}", path.display());
     }
+    exit(status::exit_code(&statuses))
 }
 
-#[derive(Debug)]
-pub struct Err(#[allow(unused)] ClError);
-
-impl std::fmt::Display for Err {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        std::fmt::Display::fmt(&self, f)
-    }
+/// Parse a hash given in hex, with or without a `0x` prefix.
+fn parse_hash(s: &str) -> Result<Hash, String> {
+    Hash::from_str_radix(s.trim_start_matches("0x"), 16).map_err(|e| format!("invalid hash: {e}"))
 }
 
-impl From<ClError> for Err {
-    fn from(value: ClError) -> Self {
-        Self(value)
+/// Parse a maximum search length, of at least one character.
+fn parse_max_len(s: &str) -> Result<usize, String> {
+    match s.parse() {
+        Ok(0) => Err("must be at least 1".into()),
+        Ok(len) => Ok(len),
+        Err(e) => Err(e.to_string()),
     }
 }
 
-impl From<i32> for Err {
-    fn from(value: i32) -> Self {
-        Self(ClError(value))
+fn parse_alphabet(s: &str) -> Result<DynAlphabet, String> {
+    if s.is_empty() {
+        return Err("alphabet is empty".into());
     }
+    DynAlphabet::new(s.as_bytes()).map_err(|e| e.to_string())
 }
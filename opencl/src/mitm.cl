@@ -0,0 +1,121 @@
+// Meet-in-the-middle search of 32-bit mul-add hashes: `build_table` hashes the prefix and every
+// front half into a table, `sort_step` sorts the table with a bitonic sort, and `probe` undoes
+// every back half from the targets and looks what is left up in the table.
+//
+// The positions of a half are passed as their concatenated characters, along with the offset of
+// the characters of each position, their count and the stride of the position's digit in the
+// index of a half, whose first position is the most significant digit.
+
+#ifndef FNV_PRIME
+#define FNV_PRIME 37u
+#endif
+#ifndef PRIME_INV
+#define PRIME_INV 2437684141u // inverse of FNV_PRIME modulo 2^32
+#endif
+
+typedef uint hash_t;
+
+// Character of position `i` in the half of the given index.
+uchar position_char(
+    ulong index,
+    uint i,
+    global const uchar* chars,
+    global const uint* offsets,
+    global const uint* sizes,
+    global const ulong* strides
+) {
+    return chars[offsets[i] + (index / strides[i]) % sizes[i]];
+}
+
+// Entries hold a hash in their high half and the index of its front in their low half, so that
+// sorting them sorts the hashes. The table is padded to a power of two with all ones, which
+// can't be an entry since indices are smaller than UINT_MAX.
+kernel void build_table(
+    const ulong fronts,
+    const hash_t prefix_hash,
+    global const uchar* chars,
+    global const uint* offsets,
+    global const uint* sizes,
+    global const ulong* strides,
+    const uint len,
+    global ulong* table
+) {
+    ulong index = get_global_id(0);
+    if (index >= fronts) {
+        table[index] = ULONG_MAX;
+        return;
+    }
+
+    hash_t h = prefix_hash;
+    for (uint i = 0; i < len; i++) {
+        h = h * FNV_PRIME + position_char(index, i, chars, offsets, sizes, strides);
+    }
+    table[index] = ((ulong)h << 32) | index;
+}
+
+// One step of a bitonic sort of the table: compare the entries `j` apart, in blocks of `k`
+// entries sorted in alternating directions.
+kernel void sort_step(global ulong* table, const uint j, const uint k) {
+    uint i = get_global_id(0);
+    uint ixj = i ^ j;
+    if (ixj <= i) {
+        return;
+    }
+
+    ulong a = table[i];
+    ulong b = table[ixj];
+    bool ascending = (i & k) == 0;
+    if ((a > b) == ascending) {
+        table[i] = b;
+        table[ixj] = a;
+    }
+}
+
+// Write the index of the front and back of every match to `results`, where `targets` hold the
+// hashes of prefix|m for each target, with the suffix undone.
+kernel void probe(
+    const ulong backs,
+    global const hash_t* targets,
+    const uint target_count,
+    global const uchar* chars,
+    global const uint* offsets,
+    global const uint* sizes,
+    global const ulong* strides,
+    const uint len,
+    global const ulong* table,
+    const uint table_len,
+    global uint2* results,
+    const uint buf_len,
+    volatile global uint* results_count
+) {
+    ulong index = get_global_id(0);
+    if (index >= backs) {
+        return;
+    }
+
+    for (uint t = 0; t < target_count; t++) {
+        hash_t h = targets[t];
+        for (uint i = len; i-- > 0;) {
+            h = (h - position_char(index, i, chars, offsets, sizes, strides)) * PRIME_INV;
+        }
+
+        // first entry of the hash, if any
+        ulong key = (ulong)h << 32;
+        uint lo = 0, hi = table_len;
+        while (lo < hi) {
+            uint mid = lo + (hi - lo) / 2;
+            if (table[mid] < key) {
+                lo = mid + 1;
+            } else {
+                hi = mid;
+            }
+        }
+
+        for (uint e = lo; e < table_len && table[e] != ULONG_MAX && (table[e] >> 32) == h; e++) {
+            uint slot = atomic_inc(results_count);
+            if (slot < buf_len) {
+                results[slot] = (uint2)((uint)table[e], (uint)index);
+            }
+        }
+    }
+}
@@ -0,0 +1,285 @@
+use std::ffi::OsString;
+
+use fs_hardblast_core::HashWidth;
+
+use crate::{ALPHABET, Args, SEARCH};
+
+/// A reason targets may have been missed, along with a follow-up run ruling it out.
+pub struct Advice {
+    pub reason: String,
+    /// Changes to the command line of this run, giving the follow-up one.
+    pub edit: Edit,
+}
+
+/// Changes to a command line.
+#[derive(Default)]
+pub struct Edit {
+    /// Options to remove, along with the number of values they take.
+    remove: Vec<(&'static str, usize)>,
+    /// Arguments to append.
+    add: Vec<String>,
+}
+
+impl Edit {
+    fn remove(mut self, option: &'static str, values: usize) -> Self {
+        self.remove.push((option, values));
+        self
+    }
+
+    /// Replace any `option` with one holding `value`.
+    fn set(self, option: &'static str, value: impl ToString) -> Self {
+        let mut edit = self.remove(option, 1);
+        edit.add.extend([option.to_owned(), value.to_string()]);
+        edit
+    }
+
+    fn add(mut self, args: impl IntoIterator<Item = String>) -> Self {
+        self.add.extend(args);
+        self
+    }
+
+    /// Apply the edit to `argv`, dropping the options which write files so that the follow-up
+    /// run doesn't overwrite this one's.
+    pub fn apply(&self, argv: &[OsString]) -> Vec<OsString> {
+        const OUTPUTS: &[(&str, usize)] = &[
+            ("--status-file", 1),
+            ("--html", 1),
+            ("--journal", 1),
+            ("--trace", 1),
+            ("--trace-chunks", 1),
+            ("--trace-paths", 1),
+        ];
+
+        let mut out = Vec::new();
+        let mut args = argv.iter();
+        out.extend(args.next().cloned());
+        while let Some(arg) = args.next() {
+            let s = arg.to_string_lossy();
+            let removed = self.remove.iter().chain(OUTPUTS).find(|(option, _)| {
+                s == *option || s.strip_prefix(option).is_some_and(|v| v.starts_with('='))
+            });
+            match removed {
+                // `--option=value` holds its value itself
+                Some(&(option, values)) if s == option => {
+                    args.by_ref().take(values).for_each(drop);
+                }
+                Some(_) => {}
+                None => out.push(arg.clone()),
+            }
+        }
+        out.extend(self.add.iter().map(OsString::from));
+        out
+    }
+}
+
+/// Analyze why a run may have missed its unresolved targets, suggesting follow-up runs.
+///
+/// All targets share a single search space, so the same advice applies to each of them. Only
+/// the run's parameters are considered: a search with no match for a 64-bit target almost
+/// certainly didn't cover the name, but which part of the space it lies in is guesswork.
+pub fn advise(args: &Args, timed_out: bool) -> Vec<Advice> {
+    let mut advice = Vec::new();
+
+    if timed_out {
+        let limit = args.time_limit.unwrap_or_default();
+        advice.push(Advice {
+            reason: format!("the search stopped at its time limit of {limit:?}"),
+            edit: Edit::default().set("--time-limit", format!("{}s", 2 * limit.as_secs().max(1))),
+        });
+    }
+    if args.min_distinct > 0 || args.min_entropy > 0.0 {
+        advice.push(Advice {
+            reason: "matches may have been filtered out by --min-distinct or --min-entropy".into(),
+            edit: Edit::default()
+                .remove("--min-distinct", 1)
+                .remove("--min-entropy", 1),
+        });
+    }
+
+    if args.regex.is_some() || !args.contains.is_empty() {
+        advice.push(Advice {
+            reason: "matches may have been filtered out by --regex or --contains".into(),
+            edit: Edit::default().remove("--regex", 1).remove("--contains", 1),
+        });
+    }
+    if args.max_digit_run.is_some()
+        || args.max_repeat.is_some()
+        || args.no_leading.is_some()
+        || args.consistent_case
+    {
+        advice.push(Advice {
+            reason: "matches may have been filtered out by --max-digit-run, --max-repeat, \
+                --no-leading or --consistent-case"
+                .into(),
+            edit: Edit::default()
+                .remove("--max-digit-run", 1)
+                .remove("--max-repeat", 1)
+                .remove("--no-leading", 1)
+                .remove("--consistent-case", 0),
+        });
+    }
+
+    if args.mask.is_some() || args.grammar.is_some() {
+        // the space they describe was searched exhaustively, and only the user knows how to
+        // widen it
+    } else if args.tokens.is_some() {
+        advice.push(Advice {
+            reason: format!("names may be made of more than {} tokens", args.max_tokens),
+            edit: Edit::default().set("--max-tokens", args.max_tokens + 1),
+        });
+        let free_chars = args.free_chars.unwrap_or(0);
+        if free_chars < 4 {
+            advice.push(Advice {
+                reason: format!(
+                    "tokens may be followed by more than {free_chars} other characters"
+                ),
+                edit: Edit::default().set("--free-chars", free_chars + 1),
+            });
+        }
+    } else if let Some([left, right]) = args.combine.as_deref() {
+        advice.push(Advice {
+            reason: "words of the second list may come first".into(),
+            edit: Edit::default().remove("--combine", 2).add([
+                "--combine".into(),
+                right.display().to_string(),
+                left.display().to_string(),
+            ]),
+        });
+    } else if let Some(width) = args.beam {
+        advice.push(Advice {
+            reason: format!(
+                "names may fall out of the {width} partial strings the model ranks best"
+            ),
+            edit: Edit::default().set("--beam", width.saturating_mul(10)),
+        });
+        advice.push(Advice {
+            reason: format!(
+                "names may be longer than {} characters after the start character",
+                args.beam_len
+            ),
+            edit: Edit::default().set("--beam-len", args.beam_len + 4),
+        });
+    } else {
+        dfs_advice(args, &mut advice);
+    }
+    advice
+}
+
+/// Advice for the regular, character by character search.
+fn dfs_advice(args: &Args, advice: &mut Vec<Advice>) {
+    if args.ngram.is_some() {
+        advice.push(Advice {
+            reason: format!(
+                "the n-gram model may have pruned names scoring below {} bits per character",
+                args.ngram_threshold
+            ),
+            edit: Edit::default().set("--ngram-threshold", args.ngram_threshold - 1.0),
+        });
+    }
+
+    let default: &[u8] = ALPHABET.bytes();
+    if let Some(alphabet) = &args.alphabet
+        && !default.iter().all(|c| alphabet.bytes().contains(c))
+    {
+        advice.push(Advice {
+            reason: "names may use characters outside of --alphabet".into(),
+            edit: Edit::default().remove("--alphabet", 1),
+        });
+    }
+    if args.final_alphabet.is_some() {
+        advice.push(Advice {
+            reason: "names may end with characters outside of --final-alphabet".into(),
+            edit: Edit::default().remove("--final-alphabet", 1),
+        });
+    }
+
+    // only the start characters not searched yet, so that no work is repeated
+    let alphabet = args.alphabet.as_ref().map_or(default, |a| a.bytes());
+    let other_starts: Vec<u8> = alphabet
+        .iter()
+        .copied()
+        .filter(|c| !args.start.as_bytes().contains(c))
+        .collect();
+    if !other_starts.is_empty() {
+        advice.push(Advice {
+            reason: format!(
+                "names may start with other characters than `{}`",
+                args.start
+            ),
+            edit: Edit::default().set("--start", String::from_utf8_lossy(&other_starts)),
+        });
+    }
+
+    if args.max_len < SEARCH {
+        advice.push(Advice {
+            reason: format!(
+                "names may be longer than {} characters after the start character",
+                args.max_len
+            ),
+            edit: Edit::default().set("--max-len", SEARCH),
+        });
+    } else if args.seeds.is_none() && args.prefixes.is_none() {
+        // longer names are out of reach of an exhaustive search
+        let longer = Edit::default()
+            .remove("--max-len", 1)
+            .remove("--start", 1)
+            .remove("--shortest-first", 0);
+        advice.push(Advice {
+            reason: "names may be longer and made of known words".into(),
+            edit: longer
+                .remove("--ngram", 1)
+                .remove("--ngram-order", 1)
+                .remove("--ngram-threshold", 1)
+                .set("--tokens", "<WORDLIST>")
+                .set("--free-chars", 2),
+        });
+        if args.ngram.is_none() {
+            let longer = Edit::default()
+                .remove("--max-len", 1)
+                .remove("--shortest-first", 0);
+            advice.push(Advice {
+                reason: "names may be longer and look like known names".into(),
+                edit: longer.set("--ngram", "<NAMES>").set("--beam", 100_000),
+            });
+        }
+    }
+}
+
+/// Print `advice` for the `unresolved` targets of the given width, with the follow-up command
+/// lines.
+pub fn print_advice(
+    advice: &[Advice],
+    unresolved: &[u64],
+    hash_width: HashWidth,
+    argv: &[OsString],
+) {
+    if advice.is_empty() || unresolved.is_empty() {
+        return;
+    }
+
+    let width = hash_width.hex_digits();
+    let targets: Vec<_> = unresolved.iter().map(|t| format!("{t:0width$x}")).collect();
+    match targets.as_slice() {
+        [target] => println!("target {target} is unresolved. Next steps:"),
+        _ => println!(
+            "{} targets are unresolved ({}). Next steps:",
+            targets.len(),
+            targets.join(", ")
+        ),
+    }
+    for Advice { reason, edit } in advice {
+        let command: Vec<_> = edit.apply(argv).iter().map(shell_quote).collect();
+        println!("  {reason}:");
+        println!("    {}", command.join(" "));
+    }
+}
+
+/// Quote `arg` for a POSIX shell, unless it doesn't need it.
+fn shell_quote(arg: &OsString) -> String {
+    let s = arg.to_string_lossy();
+    let safe = |c: char| c.is_ascii_alphanumeric() || "-_./=:,+@%".contains(c);
+    match !s.is_empty() && s.chars().all(safe) {
+        true => s.into_owned(),
+        false => format!("'{}'", s.replace('\'', r"'\''")),
+    }
+}
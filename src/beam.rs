@@ -0,0 +1,180 @@
+use std::time::Instant;
+
+use fs_hardblast_core::{
+    HashFlavor, HashVariant, HashWidth, HashWord,
+    alphabet::DynAlphabet,
+    ngram::{Context, NgramModel},
+};
+
+use crate::{SearchStats, TargetShifts};
+
+/// Beam search, which only extends the `width` partial strings an n-gram model finds most
+/// plausible at each length instead of all of them.
+///
+/// This reaches names far too long for an exhaustive search, at the cost of only finding those
+/// the model ranks well all the way through.
+pub struct Beam<'a> {
+    pub model: &'a NgramModel,
+    pub width: usize,
+    /// Maximum number of searched characters after the start character.
+    pub max_len: usize,
+    pub start: &'a [u8],
+    pub alphabet: DynAlphabet,
+    pub final_alphabet: DynAlphabet,
+}
+
+/// The partial strings kept at some length, stored side by side.
+struct Level<H> {
+    /// Partial strings, all of the same length, one after the other.
+    texts: Vec<u8>,
+    hashes: Vec<H>,
+    contexts: Vec<Context>,
+    /// Sum of the log2-probabilities of the characters of each partial string.
+    scores: Vec<f64>,
+}
+
+/// An extension of a partial string by one character, before the best ones are picked.
+#[derive(Debug, Clone, Copy)]
+struct Candidate {
+    score: f64,
+    parent: u32,
+    c: u8,
+}
+
+impl Beam<'_> {
+    /// Find middles `m` made of a start character followed by 1 to `max_len` characters such
+    /// that
+    ///
+    /// ```text
+    /// flavor.hash(prefix|m|suffix) is in targets
+    /// ```
+    ///
+    /// among the extensions of the partial strings kept at each length. The last character is
+    /// solved for like in a regular search. If `deadline` passes, the search stops early and
+    /// sets `stats.timed_out`.
+    pub fn search(
+        &self,
+        prefix: &[u8],
+        suffix: &[u8],
+        targets: &[u64],
+        flavor: &HashFlavor,
+        deadline: Option<Instant>,
+        stats: &mut SearchStats,
+    ) -> Vec<Vec<u8>> {
+        let search = match (flavor.width, flavor.variant) {
+            (HashWidth::Bits32, HashVariant::MulAdd) => Self::search_typed::<u32, false>,
+            (HashWidth::Bits32, HashVariant::Fnv1a) => Self::search_typed::<u32, true>,
+            (HashWidth::Bits64, HashVariant::MulAdd) => Self::search_typed::<u64, false>,
+            (HashWidth::Bits64, HashVariant::Fnv1a) => Self::search_typed::<u64, true>,
+        };
+        search(self, prefix, suffix, targets, flavor, deadline, stats)
+    }
+
+    fn search_typed<H: HashWord, const FNV1A: bool>(
+        &self,
+        prefix: &[u8],
+        suffix: &[u8],
+        targets: &[u64],
+        flavor: &HashFlavor,
+        deadline: Option<Instant>,
+        stats: &mut SearchStats,
+    ) -> Vec<Vec<u8>> {
+        let (fnv, model) = (flavor.typed::<H>(), self.model);
+        let shifts = TargetShifts::new(&[suffix], targets, &fnv);
+        let prefix_hash = fnv.hash(prefix);
+        let prefix_context = model.context(prefix);
+
+        // every start character is kept, as they are few
+        let mut level = Level {
+            texts: self.start.to_vec(),
+            hashes: self
+                .start
+                .iter()
+                .map(|&c| fnv.extend(prefix_hash, &[c]))
+                .collect(),
+            contexts: self
+                .start
+                .iter()
+                .map(|&c| model.push(prefix_context, c))
+                .collect(),
+            scores: self
+                .start
+                .iter()
+                .map(|&c| model.log_prob(prefix_context, c))
+                .collect(),
+        };
+
+        let mut matches = Vec::new();
+        let mut candidates = Vec::new();
+        for len in 1.. {
+            for (text, &hash) in level.texts.chunks(len).zip(&level.hashes) {
+                let hash_base = match FNV1A {
+                    true => hash,
+                    false => hash.wrapping_mul(fnv.prime),
+                };
+                shifts.solve::<FNV1A>(hash_base, &self.final_alphabet, |s| {
+                    let mut m = text.to_vec();
+                    m.push(s.to_u64() as u8);
+                    matches.push(m);
+                });
+            }
+            if len >= self.max_len {
+                break;
+            }
+
+            // keep the best candidates as they come rather than all of them, which could take
+            // `width` times the alphabet size in memory
+            candidates.clear();
+            let parents = level.contexts.iter().zip(&level.scores).enumerate();
+            'expand: for (i, (&context, &score)) in parents {
+                for &c in self.alphabet.bytes() {
+                    stats.nodes += 1;
+                    if stats.check_deadline(deadline) {
+                        break 'expand;
+                    }
+
+                    candidates.push(Candidate {
+                        score: score + model.log_prob(context, c),
+                        parent: i as u32,
+                        c,
+                    });
+                    if candidates.len() >= 2 * self.width {
+                        keep_best(&mut candidates, self.width);
+                    }
+                }
+            }
+            if stats.timed_out {
+                break;
+            }
+            keep_best(&mut candidates, self.width);
+
+            let mut next = Level {
+                texts: Vec::with_capacity(candidates.len() * (len + 1)),
+                hashes: Vec::with_capacity(candidates.len()),
+                contexts: Vec::with_capacity(candidates.len()),
+                scores: Vec::with_capacity(candidates.len()),
+            };
+            for &Candidate { score, parent, c } in &candidates {
+                let parent = parent as usize;
+                next.texts
+                    .extend_from_slice(&level.texts[parent * len..(parent + 1) * len]);
+                next.texts.push(c);
+                next.hashes.push(fnv.extend(level.hashes[parent], &[c]));
+                next.contexts.push(model.push(level.contexts[parent], c));
+                next.scores.push(score);
+            }
+            level = next;
+        }
+
+        matches.sort_unstable();
+        matches
+    }
+}
+
+/// Keep only the `width` best scoring candidates, in no particular order.
+fn keep_best(candidates: &mut Vec<Candidate>, width: usize) {
+    if candidates.len() > width {
+        candidates.select_nth_unstable_by(width - 1, |a, b| b.score.total_cmp(&a.score));
+        candidates.truncate(width);
+    }
+}
@@ -0,0 +1,90 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fs, io,
+    path::Path,
+    process::exit,
+};
+
+use fs_hardblast_core::{
+    HashFlavor, HashWidth,
+    status::{self, TargetStatus},
+};
+
+/// Hash each of `names` with `flavor` and report which targets they match, without running a
+/// search.
+///
+/// Targets are read from `targets_path`, one hex hash per line, or default to `target`, one of
+/// which is required. Exits with the same status codes as a search over the targets.
+pub fn run(
+    names: &[String],
+    targets_path: Option<&Path>,
+    target: Option<u64>,
+    flavor: HashFlavor,
+) -> ! {
+    let targets = match targets_path {
+        Some(path) => load_targets(path, flavor.width).unwrap_or_else(|e| {
+            eprintln!("failed to load targets from {}: {e}", path.display());
+            exit(1);
+        }),
+        None => match target {
+            Some(target) => vec![target],
+            None => {
+                eprintln!("--target or --targets is required unless checking the default hash");
+                exit(1);
+            }
+        },
+    };
+
+    let mut matches: HashMap<u64, usize> = targets.iter().map(|&t| (t, 0)).collect();
+    for name in names {
+        let hash = flavor.hash(name.as_bytes());
+        let verdict = match matches.get_mut(&hash) {
+            Some(count) => {
+                *count += 1;
+                "match"
+            }
+            None => "no match",
+        };
+        let width = flavor.width.hex_digits();
+        println!("{hash:0width$x}\t{verdict}\t{name}");
+    }
+
+    let statuses: Vec<_> = targets
+        .iter()
+        .map(|&target| TargetStatus {
+            target,
+            matches: matches[&target],
+        })
+        .collect();
+    exit(status::exit_code(&statuses))
+}
+
+/// Load target hashes from a file holding one hex hash per line. Anything after the first
+/// whitespace on a line is ignored, so status files can be read back as well.
+pub fn load_targets(path: &Path, hash_width: HashWidth) -> io::Result<Vec<u64>> {
+    let invalid = |msg: String| io::Error::new(io::ErrorKind::InvalidData, msg);
+
+    let mut targets = Vec::new();
+    for (i, line) in fs::read_to_string(path)?.lines().enumerate() {
+        let Some(hash) = line.split_whitespace().next() else {
+            continue;
+        };
+        let hash = u64::from_str_radix(hash.trim_start_matches("0x"), 16)
+            .ok()
+            .filter(|&hash| hash_width.fits(hash))
+            .ok_or_else(|| invalid(format!("line {}: invalid hash", i + 1)))?;
+        targets.push(hash);
+    }
+    Ok(targets)
+}
+
+/// Load known names, such as a community dictionary, from a file holding one full path per
+/// line. Blank lines and lines starting with `#` are ignored.
+pub fn load_known_names(path: &Path) -> io::Result<HashSet<Vec<u8>>> {
+    Ok(fs::read(path)?
+        .split(|&b| b == b'\n')
+        .map(<[u8]>::trim_ascii)
+        .filter(|line| !line.is_empty() && !line.starts_with(b"#"))
+        .map(<[u8]>::to_vec)
+        .collect())
+}
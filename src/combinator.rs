@@ -0,0 +1,110 @@
+use std::time::Instant;
+
+use fs_hardblast_core::{HashFlavor, HashVariant, HashWidth, HashWord, alphabet::DynAlphabet};
+
+use crate::{SearchStats, TargetShifts};
+
+/// Hashcat-style combinator attack, crossing two wordlists with a separator between their words.
+pub struct Combinator {
+    left: Vec<Vec<u8>>,
+    separators: Vec<Vec<u8>>,
+    /// Right words without their last character, along with the characters they end with.
+    right: Vec<(Vec<u8>, DynAlphabet)>,
+}
+
+impl Combinator {
+    /// Combine every word of `left` with every word of `right`, which must all be non-empty.
+    pub fn new(left: Vec<Vec<u8>>, separators: Vec<Vec<u8>>, right: &[Vec<u8>]) -> Self {
+        let mut right: Vec<_> = right
+            .iter()
+            .map(|word| word.split_last().expect("right words must not be empty"))
+            .map(|(&last, body)| (body, last))
+            .collect();
+        right.sort_unstable();
+        right.dedup();
+
+        // words sharing all but their last character are solved for together
+        let right = right
+            .chunk_by(|(a, _), (b, _)| a == b)
+            .map(|group| {
+                let lasts: Vec<_> = group.iter().map(|&(_, last)| last).collect();
+                (group[0].0.to_owned(), DynAlphabet::new(&lasts).unwrap())
+            })
+            .collect();
+        Self {
+            left,
+            separators,
+            right,
+        }
+    }
+
+    /// Find every middle `m = left|separator|right` such that
+    ///
+    /// ```text
+    /// flavor.hash(prefix|m|suffix) is in targets
+    /// ```
+    ///
+    /// The last character of the right word is solved for like the last character of a regular
+    /// search. Each match is returned once, however many ways it can be split into words. If
+    /// `deadline` passes, the search stops early and sets `stats.timed_out`.
+    pub fn search(
+        &self,
+        prefix: &[u8],
+        suffix: &[u8],
+        targets: &[u64],
+        flavor: &HashFlavor,
+        deadline: Option<Instant>,
+        stats: &mut SearchStats,
+    ) -> Vec<Vec<u8>> {
+        let search = match (flavor.width, flavor.variant) {
+            (HashWidth::Bits32, HashVariant::MulAdd) => Self::search_typed::<u32, false>,
+            (HashWidth::Bits32, HashVariant::Fnv1a) => Self::search_typed::<u32, true>,
+            (HashWidth::Bits64, HashVariant::MulAdd) => Self::search_typed::<u64, false>,
+            (HashWidth::Bits64, HashVariant::Fnv1a) => Self::search_typed::<u64, true>,
+        };
+        search(self, prefix, suffix, targets, flavor, deadline, stats)
+    }
+
+    fn search_typed<H: HashWord, const FNV1A: bool>(
+        &self,
+        prefix: &[u8],
+        suffix: &[u8],
+        targets: &[u64],
+        flavor: &HashFlavor,
+        deadline: Option<Instant>,
+        stats: &mut SearchStats,
+    ) -> Vec<Vec<u8>> {
+        let fnv = flavor.typed::<H>();
+        let targets = TargetShifts::new(&[suffix], targets, &fnv);
+        let prefix_hash = fnv.hash(prefix);
+
+        let mut matches = Vec::new();
+        'search: for left in &self.left {
+            let left_hash = fnv.extend(prefix_hash, left);
+            for sep in &self.separators {
+                let sep_hash = fnv.extend(left_hash, sep);
+                for (body, lasts) in &self.right {
+                    stats.nodes += 1;
+                    if stats.check_deadline(deadline) {
+                        break 'search;
+                    }
+                    let hash = fnv.extend(sep_hash, body);
+                    let hash_base = match FNV1A {
+                        true => hash,
+                        false => hash.wrapping_mul(fnv.prime),
+                    };
+                    targets.solve::<FNV1A>(hash_base, lasts, |s| {
+                        let mut m = [&left[..], sep, body].concat();
+                        m.push(s.to_u64() as u8);
+                        matches.push(m);
+                    });
+                }
+            }
+        }
+
+        // different words and separators may spell the same name
+        matches.sort_unstable();
+        matches.dedup();
+        matches
+    }
+}
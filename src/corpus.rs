@@ -0,0 +1,149 @@
+use std::{fs, path::Path, process::exit};
+
+use crate::{SEARCH, check, prefixes};
+
+/// Print how often each character occurs in the file names of the known names in `path`, one
+/// full path per line, followed by the smallest alphabet covering all of them.
+///
+/// File names are taken after the last `/` and up to the first `.`, which is the part of a name
+/// searches usually enumerate. With `by_frequency`, the alphabet lists the most frequent
+/// characters first, so that searches explore them first.
+pub fn run(path: &Path, by_frequency: bool) -> ! {
+    let names = fs::read(path).unwrap_or_else(|e| {
+        eprintln!("failed to load names from {}: {e}", path.display());
+        exit(1);
+    });
+
+    let mut counts = [0usize; 256];
+    for line in names.split(|&b| b == b'\n').map(<[u8]>::trim_ascii) {
+        if line.is_empty() || line.starts_with(b"#") {
+            continue;
+        }
+        let file = line.rsplit(|&b| b == b'/').next().unwrap();
+        let stem = file.split(|&b| b == b'.').next().unwrap();
+        for &c in stem {
+            counts[c as usize] += 1;
+        }
+    }
+
+    let total: usize = counts.iter().sum();
+    if total == 0 {
+        eprintln!("no characters in the names of {}", path.display());
+        exit(1);
+    }
+    let mut chars: Vec<u8> = (0..=u8::MAX).filter(|&c| counts[c as usize] != 0).collect();
+    // most frequent first, with ties in ascending order
+    chars.sort_by_key(|&c| std::cmp::Reverse(counts[c as usize]));
+
+    println!("char      count  frequency");
+    for &c in &chars {
+        let count = counts[c as usize];
+        println!(
+            "{:>4}  {count:>9}  {:>8.3}%",
+            [c].escape_ascii().to_string(),
+            100.0 * count as f64 / total as f64
+        );
+    }
+
+    if !by_frequency {
+        chars.sort_unstable();
+    }
+    println!("\nalphabet: {}", chars.escape_ascii());
+    exit(0)
+}
+
+/// Print the prefixes in `prefixes_path`, in the format of `--prefixes`, with the lengths to
+/// search after each of them suggested from the known names in `names_path`.
+///
+/// A known name belongs to a prefix if it starts with it and ends with one of its suffixes, or
+/// with its own extension from the first `.` on if the prefix has none, and doesn't continue
+/// into a subdirectory. Its length is counted after the start character, like `--max-len`. The
+/// shortest range of lengths covering `coverage` percent of the names of a prefix is suggested,
+/// so that rare outliers don't widen it. Prefixes without known names are printed as-is.
+pub fn suggest_lengths(names_path: &Path, prefixes_path: &Path, coverage: u32) -> ! {
+    let names = check::load_known_names(names_path).unwrap_or_else(|e| {
+        eprintln!("failed to load names from {}: {e}", names_path.display());
+        exit(1);
+    });
+    let mut prefixes = prefixes::load_prefixes(prefixes_path).unwrap_or_else(|e| {
+        eprintln!(
+            "failed to load prefixes from {}: {e}",
+            prefixes_path.display()
+        );
+        exit(1);
+    });
+
+    for entry in &mut prefixes {
+        // known names by length, and the ones too long to search
+        let (mut counts, mut longer) = ([0usize; SEARCH + 1], 0);
+        for name in &names {
+            let Some(rest) = name.strip_prefix(&entry.prefix[..]) else {
+                continue;
+            };
+            let middle = match &entry.suffixes {
+                // the longest suffix wins, e.g. `.bnd.dcx` over `.dcx`
+                Some(suffixes) => suffixes
+                    .iter()
+                    .filter_map(|s| rest.strip_suffix(&s[..]))
+                    .min_by_key(|middle| middle.len()),
+                None => rest.split(|&b| b == b'.').next(),
+            };
+            match middle {
+                Some(middle) if !middle.is_empty() && !middle.contains(&b'/') => {
+                    match counts.get_mut(middle.len() - 1) {
+                        Some(count) => *count += 1,
+                        None => longer += 1,
+                    }
+                }
+                _ => (),
+            }
+        }
+
+        let total: usize = counts.iter().sum();
+        if total + longer == 0 {
+            println!("# no known names");
+            println!("{entry}");
+            continue;
+        }
+        let by_length: Vec<_> = (0..=SEARCH)
+            .filter(|&len| counts[len] != 0)
+            .map(|len| format!("{len}:{}", counts[len]))
+            .collect();
+        print!(
+            "# {} known names, by length {}",
+            total + longer,
+            by_length.join(" ")
+        );
+        if longer != 0 {
+            print!(", {longer} longer than {SEARCH}");
+        }
+        println!();
+        if total != 0 {
+            entry.lengths = Some(shortest_range(
+                &counts,
+                (total * coverage as usize).div_ceil(100),
+            ));
+        }
+        println!("{entry}");
+    }
+    exit(0)
+}
+
+/// Shortest range of lengths whose `counts` add up to at least `needed`, the lowest one among
+/// ties.
+fn shortest_range(counts: &[usize], needed: usize) -> (usize, usize) {
+    let mut best = (0, counts.len() - 1);
+    for min in 0..counts.len() {
+        let mut sum = 0;
+        for (max, count) in counts.iter().enumerate().skip(min) {
+            sum += count;
+            if sum >= needed {
+                if max - min < best.1 - best.0 {
+                    best = (min, max);
+                }
+                break;
+            }
+        }
+    }
+    best
+}
@@ -0,0 +1,185 @@
+use std::{process::exit, time::Instant};
+
+use fs_hardblast_core::{
+    alphabet::DynAlphabet,
+    backend::SearchConfig,
+    density::{self, DensityEstimate},
+    mask::Slot,
+    rng::SplitMix64,
+};
+
+use crate::{CpuBackend, SearchStats, mask};
+
+/// Largest number of names searched to measure the speed of the CPU search.
+const CALIBRATION_CANDIDATES: f64 = (1 << 22) as f64;
+
+/// Prefix of a character by character search, with its suffixes and range of lengths.
+pub struct Entry<'a> {
+    pub prefix: &'a [u8],
+    pub suffixes: Vec<Vec<u8>>,
+    pub lengths: (usize, usize),
+}
+
+/// Print the expected number of matches of each length of the search of `cfg` over every
+/// entry, with each of `start` as the first character, from `samples` random names per length.
+/// Then print how long the search would take on `cpu`, from the speed of a small part of it.
+pub fn run_chars(
+    cpu: &mut CpuBackend,
+    cfg: &SearchConfig,
+    entries: &[Entry],
+    start: &[u8],
+    samples: usize,
+) -> ! {
+    let mut start = start.to_vec();
+    start.sort_unstable();
+    start.dedup();
+    let start = DynAlphabet::new(&start).unwrap();
+    let mut rng = SplitMix64::from_time();
+
+    let max_len = entries.iter().map(|e| e.lengths.1).max().unwrap_or(0);
+    let mut rows = Vec::new();
+    for len in 0..=max_len {
+        // names have one of the start characters before the `len` characters searched after it
+        let mut positions = vec![&start];
+        if len > 0 {
+            positions.extend(vec![&cfg.alphabet; len - 1]);
+            positions.push(&cfg.final_alphabet);
+        }
+
+        let spaces: Vec<_> = entries
+            .iter()
+            .filter(|e| (e.lengths.0..=e.lengths.1).contains(&len))
+            .flat_map(|e| e.suffixes.iter().map(move |suffix| (e.prefix, suffix)))
+            .collect();
+        let mut row = DensityEstimate::default();
+        for (prefix, suffix) in &spaces {
+            let space_samples = (samples / spaces.len()).max(1);
+            let estimate = density::estimate(
+                prefix,
+                &positions,
+                suffix,
+                &cfg.targets,
+                &cfg.hash,
+                space_samples,
+                &mut rng,
+            );
+            row.add(&estimate);
+        }
+        // lengths are printed with the start character, like in the histogram of a search
+        if !spaces.is_empty() {
+            rows.push((len + 1, row));
+        }
+    }
+
+    // the longest lengths of the first space that are still quick to search
+    let calibration = entries.first().and_then(|entry| {
+        let candidates = |max_len| {
+            (1..=max_len)
+                .map(|len| {
+                    cfg.alphabet.bytes().len().pow(len as u32 - 1) as f64
+                        * cfg.final_alphabet.bytes().len() as f64
+                })
+                .sum::<f64>()
+        };
+        let max_len = (1..=entry.lengths.1)
+            .take_while(|&len| candidates(len) <= CALIBRATION_CANDIDATES)
+            .last()?;
+        let cfg = SearchConfig {
+            prefix: [entry.prefix, &start.bytes()[..1]].concat(),
+            suffix: entry.suffixes[0].clone(),
+            min_len: 0,
+            max_len,
+            ..cfg.clone()
+        };
+        let time = Instant::now();
+        cpu.search_suffixes(&cfg, &entry.suffixes[..1]).ok()?;
+        Some((candidates(max_len), time.elapsed().as_secs_f64()))
+    });
+
+    print(&rows, calibration);
+    exit(0)
+}
+
+/// Print the expected number of matches of the mask search of `prefix|slots|suffix` from
+/// `samples` random names, and how long the search would take, from the speed of a small part
+/// of it.
+pub fn run_mask(
+    prefix: &[u8],
+    slots: &[Slot],
+    suffix: &[u8],
+    cfg: &SearchConfig,
+    samples: usize,
+) -> ! {
+    let alphabets: Vec<_> = slots.iter().map(Slot::alphabet).collect();
+    let positions: Vec<_> = alphabets.iter().collect();
+    let estimate = density::estimate(
+        prefix,
+        &positions,
+        suffix,
+        &cfg.targets,
+        &cfg.hash,
+        samples,
+        &mut SplitMix64::from_time(),
+    );
+
+    // fix the leading slots to their first character until the rest is quick to search
+    let mut calibration_slots = slots.to_vec();
+    let mut candidates = estimate.candidates;
+    for slot in &mut calibration_slots {
+        if candidates <= CALIBRATION_CANDIDATES {
+            break;
+        }
+        if let Slot::Set(set) = slot {
+            candidates /= set.bytes().len() as f64;
+            *slot = Slot::Fixed(set.bytes()[0]);
+        }
+    }
+    let time = Instant::now();
+    mask::search(
+        prefix,
+        &calibration_slots,
+        suffix,
+        &cfg.targets,
+        &cfg.hash,
+        None,
+        &mut SearchStats::default(),
+    );
+    let calibration = (candidates, time.elapsed().as_secs_f64());
+
+    print(&[(slots.len(), estimate)], Some(calibration));
+    exit(0)
+}
+
+/// Print the estimates of each length, and the run time extrapolated from `calibration`, the
+/// number of names searched in some number of seconds.
+fn print(rows: &[(usize, DensityEstimate)], calibration: Option<(f64, f64)>) {
+    println!("length  candidates  uniform hashes  sampled hashes  hits");
+    let mut total = DensityEstimate::default();
+    for (len, estimate) in rows {
+        println!(
+            "{len:>6}  {:>10.3e}  {:>14.3e}  {:>14.3e}  {} of {} on {} bits",
+            estimate.candidates,
+            estimate.analytic,
+            estimate.sampled,
+            estimate.hits,
+            estimate.samples,
+            estimate.bits,
+        );
+        total.add(estimate);
+    }
+    println!(
+        "{:>6}  {:>10.3e}  {:>14.3e}  {:>14.3e}",
+        "total", total.candidates, total.analytic, total.sampled
+    );
+
+    match calibration {
+        Some((candidates, secs)) if secs > 0.0 => {
+            let rate = candidates / secs;
+            println!(
+                "\nexpected CPU run time: {:.3}s, at {rate:.3e} names/s",
+                total.candidates / rate
+            );
+        }
+        _ => println!("\nthe run time couldn't be measured"),
+    }
+}
@@ -0,0 +1,183 @@
+use std::{collections::HashMap, time::Instant};
+
+use fs_hardblast_core::{
+    Fnv, HashFlavor, HashVariant, HashWidth, HashWord,
+    alphabet::DynAlphabet,
+    grammar::{Grammar, Item},
+};
+
+use crate::{SearchStats, TargetShifts};
+
+/// Find every middle `m` the grammar expands to, such that
+///
+/// ```text
+/// flavor.hash(prefix|m|suffix) is in targets
+/// ```
+///
+/// Expansions ending with a character class followed only by literals have that character
+/// solved for like the last character of a regular search, with the literals after it folded
+/// into the suffix. Each match is returned once, however many ways the grammar can expand to
+/// it. If `deadline` passes, the search stops early and sets `stats.timed_out`.
+pub fn search(
+    prefix: &[u8],
+    grammar: &Grammar,
+    suffix: &[u8],
+    targets: &[u64],
+    flavor: &HashFlavor,
+    deadline: Option<Instant>,
+    stats: &mut SearchStats,
+) -> Vec<Vec<u8>> {
+    let search = match (flavor.width, flavor.variant) {
+        (HashWidth::Bits32, HashVariant::MulAdd) => search_typed::<u32, false>,
+        (HashWidth::Bits32, HashVariant::Fnv1a) => search_typed::<u32, true>,
+        (HashWidth::Bits64, HashVariant::MulAdd) => search_typed::<u64, false>,
+        (HashWidth::Bits64, HashVariant::Fnv1a) => search_typed::<u64, true>,
+    };
+    search(prefix, grammar, suffix, targets, flavor, deadline, stats)
+}
+
+fn search_typed<H: HashWord, const FNV1A: bool>(
+    prefix: &[u8],
+    grammar: &Grammar,
+    suffix: &[u8],
+    targets: &[u64],
+    flavor: &HashFlavor,
+    deadline: Option<Instant>,
+    stats: &mut SearchStats,
+) -> Vec<Vec<u8>> {
+    let fnv = flavor.typed::<H>();
+    let mut sorted_targets = targets.to_vec();
+    sorted_targets.sort_unstable();
+
+    let mut walk = Walk::<H, FNV1A> {
+        grammar,
+        fnv,
+        suffix,
+        targets: &sorted_targets,
+        shifts: HashMap::new(),
+        deadline,
+        stats,
+        middle: Vec::new(),
+        matches: Vec::new(),
+    };
+    for alternative in &grammar.start().alternatives {
+        walk.walk(fnv.hash(prefix), alternative, None);
+    }
+
+    // different expansions may spell the same name
+    let mut matches = walk.matches;
+    matches.sort_unstable();
+    matches.dedup();
+    matches
+}
+
+/// Items left to expand once the current alternative is done, linked through the call stack.
+struct Continuation<'a> {
+    items: &'a [Item],
+    next: Option<&'a Continuation<'a>>,
+}
+
+struct Walk<'a, H, const FNV1A: bool> {
+    grammar: &'a Grammar,
+    fnv: Fnv<H>,
+    suffix: &'a [u8],
+    /// Sorted targets, to check expansions that don't end with a class.
+    targets: &'a [u64],
+    /// Target shifts for each literal tail following a solved class, along with the suffix.
+    shifts: HashMap<Vec<u8>, TargetShifts<H>>,
+    deadline: Option<Instant>,
+    stats: &'a mut SearchStats,
+    /// Expansion so far.
+    middle: Vec<u8>,
+    matches: Vec<Vec<u8>>,
+}
+
+impl<H: HashWord, const FNV1A: bool> Walk<'_, H, FNV1A> {
+    /// Expand `items` then `next`, given the hash of `prefix|middle`.
+    fn walk(&mut self, hash: H, items: &[Item], next: Option<&Continuation>) {
+        self.stats.nodes += 1;
+        if self.stats.timed_out {
+            return;
+        }
+        if self.stats.check_deadline(self.deadline) {
+            return;
+        }
+
+        let Some((item, rest)) = items.split_first() else {
+            match next {
+                Some(next) => self.walk(hash, next.items, next.next),
+                None => {
+                    let hash = self.fnv.extend(hash, self.suffix).to_u64();
+                    if self.targets.binary_search(&hash).is_ok() {
+                        self.matches.push(self.middle.clone());
+                    }
+                }
+            }
+            return;
+        };
+
+        let len = self.middle.len();
+        match item {
+            Item::Literal(literal) => {
+                self.middle.extend_from_slice(literal);
+                self.walk(self.fnv.extend(hash, literal), rest, next);
+            }
+            Item::Class(class) => match literal_tail(rest, next) {
+                Some(tail) => self.solve(hash, class, tail),
+                None => {
+                    for &c in class.bytes() {
+                        self.middle.push(c);
+                        self.walk(self.fnv.extend(hash, &[c]), rest, next);
+                        self.middle.truncate(len);
+                    }
+                }
+            },
+            Item::Rule(rule) => {
+                let grammar = self.grammar;
+                let next = Continuation { items: rest, next };
+                for alternative in &grammar.rules()[*rule].alternatives {
+                    self.walk(hash, alternative, Some(&next));
+                }
+            }
+        }
+        self.middle.truncate(len);
+    }
+
+    /// Solve for the characters of `class` that complete a match when followed by `tail`.
+    fn solve(&mut self, hash: H, class: &DynAlphabet, tail: Vec<u8>) {
+        let hash_base = match FNV1A {
+            true => hash,
+            false => hash.wrapping_mul(self.fnv.prime),
+        };
+        let (fnv, suffix, targets) = (&self.fnv, self.suffix, self.targets);
+        let shifts = self
+            .shifts
+            .entry(tail.clone())
+            .or_insert_with(|| TargetShifts::new(&[[&tail[..], suffix].concat()], targets, fnv));
+        let (middle, matches) = (&self.middle, &mut self.matches);
+        shifts.solve::<FNV1A>(hash_base, class, |s| {
+            let mut m = middle.clone();
+            m.push(s.to_u64() as u8);
+            m.extend_from_slice(&tail);
+            matches.push(m);
+        });
+    }
+}
+
+/// The literals left in `items` and then `next`, if there is nothing else left.
+fn literal_tail(items: &[Item], mut next: Option<&Continuation>) -> Option<Vec<u8>> {
+    let mut tail = Vec::new();
+    let mut items = items;
+    loop {
+        for item in items {
+            match item {
+                Item::Literal(literal) => tail.extend_from_slice(literal),
+                Item::Class(_) | Item::Rule(_) => return None,
+            }
+        }
+        let Some(cont) = next else {
+            return Some(tail);
+        };
+        (items, next) = (cont.items, cont.next);
+    }
+}
@@ -0,0 +1,154 @@
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{self, Write},
+    path::Path,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+/// Outcome of searching a single chunk of the search space, i.e. one prefix and suffix pair
+/// over one range of lengths.
+#[derive(Debug, Clone, Copy)]
+pub struct Entry<'a> {
+    pub prefix: &'a [u8],
+    /// Suffix of the chunk, or its suffixes separated by commas if it searched several at once.
+    pub suffix: &'a [u8],
+    pub min_len: usize,
+    pub max_len: usize,
+    pub duration: Duration,
+    pub matches: usize,
+    pub nodes: u64,
+    pub timed_out: bool,
+}
+
+/// Append-only log of chunk outcomes, one tab-separated line per chunk:
+///
+/// ```text
+/// <unix time ms> <prefix> <suffix> <min len> <max len> <duration us> <matches> <nodes> <complete|timed_out>
+/// ```
+pub struct Journal {
+    file: File,
+}
+
+impl Journal {
+    pub fn open(path: &Path) -> io::Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        Ok(Self { file })
+    }
+
+    pub fn record(&mut self, entry: &Entry) -> io::Result<()> {
+        let time = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis();
+        let outcome = match entry.timed_out {
+            true => "timed_out",
+            false => "complete",
+        };
+        // written in a single call so that concurrent runs sharing a journal don't interleave
+        let line = format!(
+            "{time}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{outcome}\n",
+            entry.prefix.escape_ascii(),
+            entry.suffix.escape_ascii(),
+            entry.min_len,
+            entry.max_len,
+            entry.duration.as_micros(),
+            entry.matches,
+            entry.nodes,
+        );
+        self.file.write_all(line.as_bytes())
+    }
+
+    /// Make the recorded chunks durable, so that a run stopped by its time limit leaves a
+    /// complete journal behind.
+    pub fn sync(&mut self) -> io::Result<()> {
+        self.file.sync_data()
+    }
+}
+
+/// A chunk read back from a journal.
+struct Record {
+    descriptor: String,
+    duration: Duration,
+    matches: usize,
+    nodes: u64,
+    timed_out: bool,
+}
+
+impl Record {
+    fn parse(line: &str) -> Option<Self> {
+        let fields: Vec<_> = line.split('\t').collect();
+        let [
+            _,
+            prefix,
+            suffix,
+            min_len,
+            max_len,
+            duration,
+            matches,
+            nodes,
+            outcome,
+        ] = *fields
+        else {
+            return None;
+        };
+        Some(Self {
+            descriptor: format!("{prefix}*{suffix} len {min_len}..={max_len}"),
+            duration: Duration::from_micros(duration.parse().ok()?),
+            matches: matches.parse().ok()?,
+            nodes: nodes.parse().ok()?,
+            timed_out: outcome == "timed_out",
+        })
+    }
+
+    /// DFS nodes processed per second.
+    fn throughput(&self) -> f64 {
+        self.nodes as f64 / self.duration.as_secs_f64().max(1e-9)
+    }
+}
+
+/// Number of slowest chunks listed by [`report`].
+const SLOWEST_SHOWN: usize = 5;
+
+/// Print a summary of the journal at `path`: totals, throughput and the chunks with the lowest
+/// throughput.
+pub fn report(path: &Path) -> io::Result<()> {
+    let contents = fs::read_to_string(path)?;
+    let mut records = Vec::new();
+    let mut malformed = 0;
+    for line in contents.lines().filter(|l| !l.trim().is_empty()) {
+        match Record::parse(line) {
+            Some(record) => records.push(record),
+            None => malformed += 1,
+        }
+    }
+
+    let duration: Duration = records.iter().map(|r| r.duration).sum();
+    let matches: usize = records.iter().map(|r| r.matches).sum();
+    let nodes: u64 = records.iter().map(|r| r.nodes).sum();
+    let timed_out = records.iter().filter(|r| r.timed_out).count();
+
+    println!("chunks: {} ({timed_out} timed out)", records.len());
+    println!("time searching: {duration:?}");
+    println!("matches: {matches}");
+    println!(
+        "DFS nodes: {nodes} ({:.3e} nodes/s)",
+        nodes as f64 / duration.as_secs_f64().max(1e-9)
+    );
+    if malformed != 0 {
+        println!("malformed lines: {malformed}");
+    }
+
+    records.sort_by(|a, b| a.throughput().total_cmp(&b.throughput()));
+    if !records.is_empty() {
+        println!("slowest chunks:");
+    }
+    for record in records.iter().take(SLOWEST_SHOWN) {
+        println!(
+            "  {:.3e} nodes/s, {:?}: {}",
+            record.throughput(),
+            record.duration,
+            record.descriptor
+        );
+    }
+    Ok(())
+}
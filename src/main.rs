@@ -2,233 +2,1970 @@
 #![feature(likely_unlikely)]
 
 use std::{
+    cell::RefCell,
+    collections::HashSet,
+    fs,
     hint::unlikely,
-    simd::{LaneCount, Mask, Simd, SupportedLaneCount, cmp::SimdPartialEq},
-    time::Instant,
+    io,
+    ops::{Add, BitXor, Mul, Sub},
+    path::{Path, PathBuf},
+    process::exit,
+    rc::Rc,
+    simd::{
+        LaneCount, Mask, Simd, SupportedLaneCount,
+        cmp::{SimdPartialEq, SimdPartialOrd},
+        num::SimdUint,
+    },
+    slice,
+    time::{Duration, Instant},
 };
 
-mod alphabet;
-mod const_vec;
+use clap::{Parser, Subcommand, ValueEnum, builder::NonEmptyStringValueParser};
+use fs_hardblast_core::{
+    Fnv, HashFlavor, HashVariant, HashWidth, HashWord, PrecomputedSuffix,
+    alphabet::{Alphabet, DynAlphabet},
+    backend::{Backend, BackendError, MitmConfig, SearchConfig},
+    bloom::{BLOOM_MIN_TARGETS, BloomFilter},
+    contains::Substring,
+    filter::{CharRules, RulesState},
+    matches::{Match, MatchVec},
+    ngram::{self, NgramModel},
+    output::print_match,
+    progress::ProgressReporter,
+    regex::NameRegex,
+    rng::SplitMix64,
+    status,
+    template::{Fields, OutputTemplate},
+};
+
+mod advice;
+mod beam;
+mod check;
+mod combinator;
+mod corpus;
+mod estimate;
+mod grammar;
+mod journal;
+mod mask;
+mod mitm;
+mod prefixes;
+mod run;
+mod sample;
+mod table_cache;
+mod tokens;
+mod trace;
+mod verify;
 
-use alphabet::Alphabet;
+use prefixes::Prefix;
+use run::{Mode, Run};
 
-const PREFIX: &[u8] = b"/other/";
-const SUFFIX: &[u8] = b".dcx";
+// Defaults for the search parameters, all of which can be set from the command line.
+const PREFIX: &str = "/other/";
+const SUFFIX: &str = ".dcx";
 
 const ALPHABET: Alphabet<38> = Alphabet::new(b"_.abcdefghijklmnopqrstuvwxyz0123456789");
 
-const START: &[u8] = b"mnopqrs";
-const TARGET: u32 = 0xd7255946;
+const START: &str = "mnopqrs";
+const TARGET: u64 = 0xd7255946;
 const SEARCH: usize = 7;
 
-/// Note that this isn't the real FNV prime, but what FromSoft uses.
-const FNV_PRIME: u32 = 37;
+/// Number of candidates listed for each target in HTML reports.
+const REPORT_CANDIDATES: usize = 100;
 
-/// Precomputed information about the hash of a suffix.
-///
-/// Used to efficiently compute the combined hash of `base|suffix` given `hash(base)`
-/// as well as efficiently finding a single character `x` such that
-/// `hash(base|x|suffix) == target_hash`.
+/// Find FNV hash collisions in FromSoftware file paths.
+#[derive(Debug, Parser)]
+struct Args {
+    /// Print matches as raw bytes, even when stdout is a terminal.
+    #[arg(long)]
+    raw: bool,
+
+    /// Start of the path, before the searched characters.
+    #[arg(long, default_value = PREFIX)]
+    prefix: String,
+
+    /// End of the path, after the searched characters.
+    #[arg(long, default_value = SUFFIX)]
+    suffix: String,
+
+    /// Comma-separated list of suffixes to search instead of `--suffix`, e.g.
+    /// `.dcx,.bnd.dcx,.tpf.dcx`.
+    ///
+    /// Character by character searches check every suffix in a single pass, which is much
+    /// cheaper than a run per suffix. Other searches go through the suffixes in turn.
+    #[arg(
+        long,
+        value_delimiter = ',',
+        value_name = "SUFFIXES",
+        conflicts_with_all = ["suffix", "mask"]
+    )]
+    suffixes: Vec<String>,
+
+    /// Search from each prefix listed in this file instead of `--prefix`, one per line, e.g.
+    /// known directories such as `/chr/` or `/map/m10_00_00_00/`.
+    ///
+    /// Each prefix may be followed by its own lengths and comma-separated suffixes, overriding
+    /// `--max-len` and `--suffix`, e.g. `/sfx/ 3-7 .ffxbnd.dcx` for 3 to 7 characters after the
+    /// start character. Lengths only apply to character by character searches. Every prefix is
+    /// searched in the same process, so backends are only set up once.
+    #[arg(
+        long,
+        value_name = "FILE",
+        conflicts_with_all = ["prefix", "mask", "seeds", "emit_all"]
+    )]
+    prefixes: Option<PathBuf>,
+
+    /// Hash to find collisions for, in hex. Defaults to `d7255946` for the default hash, and is
+    /// required for any other.
+    #[arg(long, value_name = "HASH", value_parser = parse_hash)]
+    target: Option<u64>,
+
+    /// File of target hashes to search for all at once, one hex hash per line.
+    ///
+    /// Every target is checked in a single pass over the search space, so this is much faster
+    /// than searching for each target in turn.
+    #[arg(long, value_name = "FILE", conflicts_with = "target")]
+    targets: Option<PathBuf>,
+
+    /// File of known names, one full path per line, e.g. a community dictionary.
+    ///
+    /// Targets one of them hashes to are already resolved and skipped, and `--emit-all` leaves
+    /// them out.
+    #[arg(long, value_name = "FILE")]
+    known: Option<PathBuf>,
+
+    /// Width of the hashes in bits: 32 for games before Elden Ring, 64 from Elden Ring on.
+    #[arg(long, value_name = "BITS", default_value_t = HashWidth::Bits32)]
+    hash_width: HashWidth,
+
+    /// How each byte is mixed into the hash: `mul-add` (`h * prime + byte`, as FromSoftware
+    /// does) or `fnv1a` (`(h ^ byte) * prime`).
+    #[arg(long, value_name = "VARIANT", default_value_t = HashVariant::MulAdd)]
+    variant: HashVariant,
+
+    /// Prime the hash is multiplied by for each byte, in decimal or `0x`-prefixed hex. Defaults
+    /// to the one FromSoftware uses for `--hash-width`, 37 or 133.
+    #[arg(long, value_name = "PRIME", value_parser = parse_int)]
+    prime: Option<u64>,
+
+    /// Initial value of the hash, in decimal or `0x`-prefixed hex.
+    #[arg(long, value_name = "BASIS", default_value_t = 0, value_parser = parse_int)]
+    basis: u64,
+
+    /// Maximum number of searched characters after the start character, at most 7.
+    #[arg(long, value_name = "LEN", default_value_t = SEARCH, value_parser = parse_max_len)]
+    max_len: usize,
+
+    /// Characters making up the searched string. Defaults to `_.` followed by `a-z0-9`.
+    ///
+    /// The CPU search explores characters in the order given, which matters to searches cut
+    /// short by `--time-limit`. The `alphabet` command can order them by frequency.
+    #[arg(long, value_name = "CHARS", value_parser = parse_alphabet)]
+    alphabet: Option<DynAlphabet>,
+
+    /// Characters the searched string may end with. Defaults to `--alphabet`.
+    ///
+    /// The last character is solved for rather than enumerated, so narrowing this (e.g. to
+    /// digits) doesn't make the search any slower.
+    #[arg(long, value_name = "CHARS", value_parser = parse_alphabet)]
+    final_alphabet: Option<DynAlphabet>,
+
+    /// Characters the searched string may start with. Each one is searched in turn.
+    #[arg(long, value_name = "CHARS", default_value = START, value_parser = NonEmptyStringValueParser::new())]
+    start: String,
+
+    /// Seed the search from a file of already explored branches instead of the root.
+    ///
+    /// Each line holds the hash of `prefix|partial` in hex followed by `partial`, e.g.
+    /// `1a2b3c4d mno`. Only the subtrees below each partial string are searched.
+    #[arg(long, value_name = "FILE")]
+    seeds: Option<PathBuf>,
+
+    /// Print every candidate in the search space along with its hash, instead of only the
+    /// ones matching the target.
+    #[arg(long, conflicts_with = "seeds")]
+    emit_all: bool,
+
+    /// Write the resolution status of each target to this file.
+    #[arg(long, value_name = "FILE")]
+    status_file: Option<PathBuf>,
+
+    /// Comma-separated list of strings the name may end with right before the suffix.
+    ///
+    /// When the last few characters are known to come from a tiny set (e.g. `_l,_m,_h`), this
+    /// solves for the character before them instead of enumerating them.
+    #[arg(long, value_delimiter = ',')]
+    tail: Vec<String>,
+
+    /// Report all matches of a given length before moving on to longer ones, or with `--tokens`,
+    /// all matches of a given number of tokens before moving on to more tokens.
+    ///
+    /// This uses iterative deepening, which re-explores shorter strings once per length, but
+    /// this only adds about `1 / (alphabet size - 1)` to the total work.
+    #[arg(long, conflicts_with = "emit_all")]
+    shortest_first: bool,
+
+    /// Format of the lines printed for each match, e.g. `{prefix}{middle}{suffix}\t{hash:x}`.
+    ///
+    /// Defaults to `{prefix}{middle}{suffix}`, or `{prefix}{middle}{suffix},{hash:x}` with
+    /// `--emit-all`.
+    #[arg(long, value_name = "TEMPLATE")]
+    output_template: Option<OutputTemplate>,
+
+    /// Only keep a uniform random sample of this many matches, printed at the end of the run.
+    ///
+    /// The total number of matches is still counted exactly.
+    #[arg(long, value_name = "COUNT", conflicts_with = "emit_all")]
+    sample_results: Option<usize>,
+
+    /// Only report matches with at least this many distinct characters in the middle.
+    ///
+    /// Branches that can't reach this many distinct characters are pruned from the search.
+    #[arg(long, value_name = "COUNT", default_value_t = 0)]
+    min_distinct: usize,
+
+    /// Only report matches whose middle has at least this much Shannon entropy, in bits per
+    /// character.
+    #[arg(long, value_name = "BITS", default_value_t = 0.0)]
+    min_entropy: f64,
+
+    /// Only report matches whose searched characters, from the start character on, match this
+    /// regex as a whole, e.g. `[a-z]+[0-9]{2}`.
+    ///
+    /// The regex is compiled to a DFA, which the CPU search follows to prune the branches that
+    /// can no longer match. It matches bytes, so `.` and classes never match multi-byte
+    /// characters.
+    #[arg(long, value_name = "REGEX")]
+    regex: Option<NameRegex>,
+
+    /// Only report matches whose searched characters, from the start character on, contain
+    /// this string. May be given several times.
+    ///
+    /// The CPU search prunes the branches left with too few characters to contain it, so the
+    /// longer the string, the faster the search.
+    #[arg(long, value_name = "STRING", value_parser = NonEmptyStringValueParser::new())]
+    contains: Vec<String>,
+
+    /// Only report matches with at most this many digits in a row in their searched
+    /// characters, from the start character on.
+    ///
+    /// Like `--max-repeat` and `--no-leading`, this prunes the branches breaking it from the
+    /// CPU search.
+    #[arg(long, value_name = "COUNT")]
+    max_digit_run: Option<usize>,
+
+    /// Only report matches with no character repeated more than this many times in a row in
+    /// their searched characters, from the start character on, e.g. 2 to reject `aaa`.
+    #[arg(long, value_name = "COUNT")]
+    max_repeat: Option<usize>,
+
+    /// Only report matches whose searched characters, from the start character on, don't start
+    /// with any of these characters, e.g. `_.`.
+    #[arg(long, value_name = "CHARS")]
+    no_leading: Option<String>,
+
+    /// Only report matches whose words, the runs of ASCII letters of their searched characters
+    /// from the start character on, are all lowercase or all uppercase, e.g. to reject `aB_c` but
+    /// not `ab_C`.
+    ///
+    /// This keeps an alphabet with both cases from multiplying the matches of every word.
+    #[arg(long)]
+    consistent_case: bool,
+
+    /// Train a character n-gram model on the known names in this file, one per line, and prune
+    /// the branches it finds implausible.
+    ///
+    /// This gives up on completeness to reach longer names sooner.
+    #[arg(
+        long,
+        value_name = "FILE",
+        conflicts_with_all = ["emit_all", "mask", "tokens", "combine", "grammar"]
+    )]
+    ngram: Option<PathBuf>,
+
+    /// Number of characters in the n-grams of `--ngram`, including the predicted one.
+    #[arg(long, value_name = "N", default_value_t = 3, requires = "ngram", value_parser = parse_ngram_order)]
+    ngram_order: usize,
+
+    /// Prune branches whose partial string has a lower mean log2-probability per character
+    /// under the `--ngram` model. Guessing uniformly among the characters of the default
+    /// alphabet would score about -5.2.
+    #[arg(long, value_name = "BITS", default_value_t = -4.5, requires = "ngram", allow_negative_numbers = true)]
+    ngram_threshold: f64,
+
+    /// Run a beam search instead, only extending the `WIDTH` partial strings the `--ngram`
+    /// model finds most plausible at each length.
+    ///
+    /// This can reach names far longer than an exhaustive search, but only finds those the
+    /// model ranks well all the way through. `--ngram-threshold` isn't used.
+    #[arg(
+        long,
+        value_name = "WIDTH",
+        requires = "ngram",
+        value_parser = clap::value_parser!(u32).range(1..),
+        conflicts_with_all = ["max_len", "seeds", "shortest_first"]
+    )]
+    beam: Option<u32>,
+
+    /// Maximum number of searched characters after the start character, with `--beam`.
+    #[arg(long, value_name = "LEN", default_value_t = 12, requires = "beam")]
+    beam_len: usize,
+
+    /// Stop the search after this much time, e.g. `90s`, `30m` or `1h30m`.
+    ///
+    /// Matches found so far are still reported, the chunk the search stopped in is recorded in
+    /// the `--journal`, and the process exits with a distinct status.
+    #[arg(long, value_name = "DURATION", value_parser = parse_duration)]
+    time_limit: Option<Duration>,
+
+    /// Print the progress of the search to stderr at most once per this long, e.g. `10s`.
+    ///
+    /// Progress is counted in chunks, i.e. one start character of one prefix over one range
+    /// of lengths, which are counted as they complete. DFS nodes are reported as the search
+    /// goes, including within chunks.
+    #[arg(long, value_name = "DURATION", value_parser = parse_duration)]
+    progress: Option<Duration>,
+
+    /// Append the outcome of each searched chunk to this file, for use with `journal report`.
+    #[arg(long, value_name = "FILE")]
+    journal: Option<PathBuf>,
+
+    /// Write sampled diagnostics of the run to this file, for tuning long searches.
+    ///
+    /// Detailed statistics are written for one chunk in `--trace-chunks`, along with the
+    /// partial strings of about one in `--trace-paths` DFS nodes of the CPU search.
+    #[arg(long, value_name = "FILE")]
+    trace: Option<PathBuf>,
+
+    /// Write the statistics of one chunk in this many to the trace.
+    #[arg(long, value_name = "COUNT", default_value_t = 16, requires = "trace")]
+    trace_chunks: usize,
+
+    /// Write the partial string of about one in this many DFS nodes to the trace, or none if 0.
+    #[arg(long, value_name = "COUNT", default_value_t = 1 << 20, requires = "trace")]
+    trace_paths: u64,
+
+    /// Write a self-contained HTML report of the run to this file, for sharing results.
+    ///
+    /// Candidates are ranked by the Shannon entropy of their middle, as used by
+    /// `--min-entropy`.
+    #[arg(long, value_name = "FILE", conflicts_with = "emit_all")]
+    html: Option<PathBuf>,
+
+    /// Instead of searching, estimate the number of matches of each length from this many
+    /// random names per length, and how long the search would take on the CPU.
+    ///
+    /// Hashes of short names or of names from small alphabets are far from uniformly
+    /// distributed, so the number of matches is sampled rather than derived from the size of
+    /// the search space. Filters such as `--regex` aren't taken into account.
+    #[arg(
+        long,
+        value_name = "SAMPLES",
+        conflicts_with_all = ["emit_all", "seeds", "tokens", "combine", "grammar", "beam"]
+    )]
+    estimate: Option<usize>,
+
+    /// Search the names matching a hashcat-style mask instead, e.g. `c?d?d?d?d_?l?l?l.anibnd.dcx`.
+    ///
+    /// `?l`, `?u`, `?d`, `?h`, `?H`, `?s` and `?a` stand for lowercase letters, uppercase
+    /// letters, digits, lowercase hex digits, uppercase hex digits, symbols and all of these.
+    /// `?1` stands for the characters of `--alphabet`, and `??` is a literal `?`. The last
+    /// placeholder is solved for, so it may be `?a` for free.
+    ///
+    /// `%` followed by a count and a placeholder letter repeats the placeholder, which spells
+    /// out numeric IDs like `c%4d` for `c?d?d?d?d` or `m%2d_%2d_%2d_%2d` for map names. `%%` is
+    /// a literal `%`.
+    ///
+    /// Placeholders may form several gaps separated by fixed text, which are searched jointly,
+    /// e.g. `/chr/c?1?1?1?1/c?1?1?1?1_a.anibnd.dcx`.
+    #[arg(
+        long,
+        value_name = "MASK",
+        conflicts_with_all = [
+            "prefix", "suffix", "start", "final_alphabet", "max_len", "seeds",
+            "emit_all", "tail", "shortest_first",
+        ]
+    )]
+    mask: Option<String>,
+
+    /// Search the mask by meeting in the middle: hash every combination of the first half of
+    /// its placeholders into a table, then undo every combination of the second half from the
+    /// targets and look the rest up in the table.
+    ///
+    /// This takes about the square root of the time of a regular mask search, so masks can be
+    /// about twice as long, but the table takes 16 bytes per combination of the first half.
+    ///
+    /// With the OpenCL backend, 32-bit mul-add hashes are searched on the GPU, whose table takes
+    /// 8 bytes per combination and must fit in device memory. Other hashes fall back to the CPU
+    /// with `--backend auto`.
+    ///
+    /// Only masks are searched this way, so `--mask` is required. To meet in the middle of a
+    /// character by character search, give each length as a mask of `?1`s over `--alphabet`,
+    /// e.g. `--mask /other/m?1?1?1?1?1.dcx` for 5 characters after the start character `m`.
+    #[arg(long, requires = "mask", conflicts_with = "estimate")]
+    mitm: bool,
+
+    /// Meet in the middle with tables spilled to sorted runs in this directory, merged from
+    /// disk, for masks whose tables don't fit in memory. The search always runs on the CPU.
+    ///
+    /// Both halves take about 32 bytes of disk per combination, and the back half takes that
+    /// much per target.
+    #[arg(long, value_name = "DIR", requires = "mitm")]
+    mitm_spill: Option<PathBuf>,
+
+    /// Cache the table of the first half of `--mitm` searches in this directory, and load it
+    /// from there when a later search has the same hash, prefix and first half, e.g. with
+    /// other targets. Cached tables are mapped into memory rather than read, and searched on
+    /// the CPU.
+    #[arg(
+        long,
+        value_name = "DIR",
+        requires = "mitm",
+        conflicts_with = "mitm_spill"
+    )]
+    mitm_cache: Option<PathBuf>,
+
+    /// Process several DFS nodes at once in the SIMD lanes of the CPU search, one character
+    /// at a time, rather than several characters of one node.
+    ///
+    /// This keeps every lane busy whatever the size of the alphabet, which is usually faster
+    /// when it isn't a multiple of 4, e.g. `a-z_` or `a-z0-9_`, and especially with FNV-1a.
+    #[arg(long)]
+    node_lanes: bool,
+
+    /// Memory used to sort each run of the tables of `--mitm-spill`, in MiB.
+    #[arg(long, value_name = "MIB", default_value_t = 1024, requires = "mitm_spill", value_parser = clap::value_parser!(u64).range(1..))]
+    mitm_memory: u64,
+
+    /// Search names made of tokens from this wordlist instead of single characters, one token
+    /// per line (e.g. `sword`, `ruin`, `m10_`).
+    ///
+    /// Each step of the search hashes a whole token, which can recover much longer names than
+    /// character-level search when they are made of known words. `@fromsoft` stands for the
+    /// built-in list of FromSoftware path tokens, with the `builtin-wordlist` feature.
+    #[arg(
+        long,
+        value_name = "FILE",
+        conflicts_with_all = ["mask", "start", "max_len", "seeds", "emit_all"]
+    )]
+    tokens: Option<PathBuf>,
+
+    /// Maximum number of tokens in the searched string, with `--tokens`.
+    #[arg(long, value_name = "COUNT", default_value_t = 3, requires = "tokens")]
+    max_tokens: usize,
+
+    /// With `--tokens`, follow the tokens with 1 to this many characters of `--alphabet`, at
+    /// most 4, e.g. `grass` and `03b` for `grass03b.tpf`.
+    ///
+    /// These are searched like in a regular search, so the last one is solved for from
+    /// `--final-alphabet`.
+    #[arg(long, value_name = "COUNT", requires = "tokens", value_parser = parse_free_chars)]
+    free_chars: Option<usize>,
+
+    /// Search names made of a word from the first wordlist, a separator and a word from the
+    /// second one instead, e.g. `wind` and `strong` for `sfx_wind_strong.ffx`.
+    ///
+    /// The last character of the second word is solved for rather than enumerated. Either
+    /// wordlist may be `@fromsoft`, as with `--tokens`.
+    #[arg(
+        long,
+        num_args = 2,
+        value_names = ["LEFT", "RIGHT"],
+        conflicts_with_all = [
+            "mask", "tokens", "start", "alphabet", "final_alphabet", "max_len", "seeds",
+            "emit_all", "shortest_first",
+        ]
+    )]
+    combine: Option<Vec<PathBuf>>,
+
+    /// Search the names described by the grammar in this file instead, between `--prefix` and
+    /// `--suffix`.
+    ///
+    /// Each line holds a rule such as `map := "m" digit digit "_" digit digit`, where items
+    /// are `"literals"`, character classes like `[a-z_]` and names of other rules, and
+    /// alternatives are separated by `|`. Names are expanded from the first rule. A class
+    /// followed only by literals is solved for rather than enumerated.
+    #[arg(
+        long,
+        value_name = "FILE",
+        conflicts_with_all = [
+            "mask", "tokens", "combine", "start", "alphabet", "final_alphabet", "max_len",
+            "seeds", "emit_all", "shortest_first",
+        ]
+    )]
+    grammar: Option<PathBuf>,
+
+    /// Comma-separated strings to put between the words of `--combine`. An empty entry, as at
+    /// the end of the default, combines the words directly.
+    #[arg(
+        long,
+        value_delimiter = ',',
+        default_value = "_,-,",
+        requires = "combine"
+    )]
+    separators: Vec<String>,
+
+    /// Where to run the search. `auto` uses the GPU if one is usable, and the CPU otherwise.
+    ///
+    /// Lengths too short for the GPU are still searched on the CPU. `--seeds`, `--emit-all`,
+    /// `--mask`, `--tokens`, `--combine`, `--grammar`, `--ngram` and `--beam` always run on the
+    /// CPU.
+    #[arg(long, value_enum, default_value_t = BackendKind::Cpu)]
+    backend: BackendKind,
+
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+impl Args {
+    /// Characters to search, from `--alphabet` or the default alphabet.
+    fn alphabet(&self) -> DynAlphabet {
+        self.alphabet
+            .clone()
+            .unwrap_or_else(|| DynAlphabet::from(&ALPHABET))
+    }
+
+    /// Characters the last one is solved for from, from `--final-alphabet` or the alphabet.
+    fn final_alphabet(&self) -> DynAlphabet {
+        self.final_alphabet
+            .clone()
+            .unwrap_or_else(|| self.alphabet())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum BackendKind {
+    Cpu,
+    /// Requires building with `--features opencl`.
+    Opencl,
+    Auto,
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// Check whether full paths match a target hash, without searching.
+    Check {
+        /// Full paths to hash, e.g. `/other/mab9.dcx`.
+        #[arg(required = true)]
+        names: Vec<String>,
+
+        /// File of target hashes, one hex hash per line. Defaults to the global `--targets`, or
+        /// to the built-in target.
+        #[arg(long, value_name = "FILE")]
+        targets: Option<PathBuf>,
+    },
+    /// Count the characters of the file names of known names, and print the smallest alphabet
+    /// covering them.
+    Alphabet {
+        /// File of known names, one full path per line.
+        names: PathBuf,
+
+        /// Order the alphabet from the most frequent character to the least, for the search to
+        /// explore likely characters first.
+        #[arg(long)]
+        by_frequency: bool,
+    },
+    /// Suggest the lengths to search after each prefix of a `--prefixes` file from the known
+    /// names under it, and print the file with them filled in.
+    Lengths {
+        /// File of known names, one full path per line.
+        names: PathBuf,
+
+        /// File of prefixes, in the format of `--prefixes`. Prefixes keep their suffixes, which
+        /// select the known names they are suggested lengths from.
+        prefixes: PathBuf,
+
+        /// Percentage of the known names of each prefix the suggested lengths cover. The
+        /// shortest range of lengths covering them is suggested, leaving out rare outliers.
+        #[arg(long, value_name = "PERCENT", default_value_t = 95, value_parser = clap::value_parser!(u32).range(1..=100))]
+        coverage: u32,
+    },
+    /// Inspect search journals written with `--journal`.
+    Journal {
+        #[command(subcommand)]
+        command: JournalCommand,
+    },
+    /// Check an OpenCL kernel against the CPU search on random configurations.
+    ///
+    /// The built-in meet-in-the-middle kernels are checked the same way afterwards. Requires
+    /// building with `--features opencl`.
+    VerifyKernel {
+        /// OpenCL C source of the kernel, implementing the same interface as the built-in one.
+        source: PathBuf,
+
+        /// Number of configurations to check.
+        #[arg(long, default_value_t = 50)]
+        rounds: usize,
+
+        /// Seed to generate the configurations from, to reproduce a previous run. Random by
+        /// default.
+        #[arg(long)]
+        seed: Option<u64>,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+enum JournalCommand {
+    /// Summarize throughput and list the slowest chunks of a journal.
+    Report {
+        /// Journal file to summarize.
+        file: PathBuf,
+    },
+}
+
+/// Parameters of a search, shared by its root and seeded variants.
 #[derive(Debug, Clone, Copy)]
-#[allow(unused)]
-struct PrecomputedSuffix {
-    hash: u32,
-    mult: u32,
-    target_shift: u32,
+struct SearchParams<'a, H> {
+    /// Characters to enumerate.
+    alphabet: &'a DynAlphabet,
+    /// Characters the solved last character may be.
+    final_alphabet: &'a DynAlphabet,
+    min_len: usize,
+    max_len: usize,
+    /// Prune branches that can't reach this many distinct characters.
+    min_distinct: usize,
+    /// Target hashes, widened to `u64`s.
+    targets: &'a [u64],
+    fnv: Fnv<H>,
+    /// Stop searching once this instant has passed.
+    deadline: Option<Instant>,
+    /// Sample the partial string of about one in this many DFS nodes, unless 0.
+    path_sample: u64,
+    /// Prune branches whose partial string has a lower mean log2-probability under the model.
+    ngram: Option<(&'a NgramModel, f64)>,
+    /// Characters before the searched string, which `ngram` conditions on.
+    context: &'a [u8],
+    /// Prune branches whose partial string can't start a match of the regex.
+    regex: Option<&'a NameRegex>,
+    /// Prune branches which can't contain all of these substrings anymore.
+    contains: &'a [Substring],
+    /// Prune branches breaking these rules.
+    rules: Option<&'a CharRules>,
+    /// Searched characters before the searched string, such as the start character, which
+    /// `regex`, `contains` and `rules` apply to along with it.
+    segment: &'a [u8],
+    /// Spread the SIMD lanes over DFS nodes rather than over the characters of one node.
+    node_lanes: bool,
 }
 
-impl PrecomputedSuffix {
-    pub const fn new(suffix: &[u8], target_hash: u32) -> Self {
-        // 32-bit modular inverse using 3 Newton-Raphson iterations :)
-        // From https://arxiv.org/abs/2204.04342
-        const fn minv32(a: u32) -> u32 {
-            assert!(!a.is_multiple_of(2));
+/// Statistics collected over the course of a search.
+#[derive(Debug, Default, Clone)]
+struct SearchStats {
+    /// Maximum number of entries held on the DFS stack at once.
+    stack_high_water: usize,
+    /// Number of DFS nodes processed.
+    nodes: u64,
+    /// Whether the search was stopped early because its deadline passed.
+    timed_out: bool,
+    /// Partial strings sampled from the DFS since they were last taken, after the prefix.
+    sampled_paths: Vec<Match>,
+    /// Progress of the run, reported from within the searches as well as after each chunk.
+    progress: Option<Rc<RefCell<RunProgress>>>,
+}
 
-            let mut x = 3u32.wrapping_mul(a) ^ 2;
-            let mut y = 1u32.wrapping_sub(a.wrapping_mul(x));
+impl SearchStats {
+    /// How many nodes to process between deadline checks.
+    const DEADLINE_CHECK_INTERVAL: u64 = 1 << 12;
 
-            x = x.wrapping_mul(y.wrapping_add(1));
-            y = y.wrapping_mul(y);
-            x = x.wrapping_mul(y.wrapping_add(1));
-            y = y.wrapping_mul(y);
-            x.wrapping_mul(y.wrapping_add(1))
+    /// Every [`Self::DEADLINE_CHECK_INTERVAL`] nodes, report progress and check whether
+    /// `deadline` has passed, which stops the search. Returns whether the search timed out.
+    fn check_deadline(&mut self, deadline: Option<Instant>) -> bool {
+        if self.nodes.is_multiple_of(Self::DEADLINE_CHECK_INTERVAL) {
+            if let Some(progress) = &self.progress {
+                progress.borrow_mut().report(self.nodes);
+            }
+            if deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+                self.timed_out = true;
+            }
         }
+        self.timed_out
+    }
 
-        let hash = fnv_hash(suffix);
-        let mult = FNV_PRIME.wrapping_pow(suffix.len() as u32);
-        let target_shift = target_hash.wrapping_sub(hash).wrapping_mul(minv32(mult));
+    /// Size in bytes of the DFS stack at its high-water mark, when searching hashes of the
+    /// given width.
+    pub fn stack_high_water_bytes(&self, hash_width: HashWidth) -> usize {
+        self.stack_high_water * (hash_width.bytes() + size_of::<u64>() + size_of::<u8>())
+    }
+}
 
-        Self {
-            hash,
-            mult,
-            target_shift,
+/// Progress reporter shared with the searches, so that progress is also reported while a chunk
+/// is underway, with the chunks and matches counted up to the last complete chunk.
+#[derive(Debug)]
+struct RunProgress {
+    reporter: ProgressReporter<'static>,
+    /// Fraction of the chunks of the run that are complete.
+    fraction: f64,
+    /// Matches found in the complete chunks.
+    matches: usize,
+}
+
+impl RunProgress {
+    fn report(&mut self, nodes: u64) {
+        self.reporter.report(self.fraction, nodes, self.matches);
+    }
+}
+
+/// Backend running the DFS search on the CPU.
+struct CpuBackend {
+    /// Prune branches that can't reach this many distinct characters.
+    min_distinct: usize,
+    /// Stop searching once this instant has passed.
+    deadline: Option<Instant>,
+    /// Sample the partial string of about one in this many DFS nodes, unless 0.
+    path_sample: u64,
+    /// Prune branches whose partial string has a lower mean log2-probability under the model.
+    ngram: Option<(NgramModel, f64)>,
+    /// Prune branches whose partial string can't start a match of the regex.
+    regex: Option<NameRegex>,
+    /// Prune branches which can't contain all of these substrings anymore.
+    contains: Vec<Substring>,
+    /// Prune branches breaking these rules.
+    rules: CharRules,
+    /// Length of the prefix of searches before the searched characters, such as the start
+    /// character, which constraints like `regex` apply to.
+    segment_start: usize,
+    /// Spill the tables of meet-in-the-middle searches to disk.
+    mitm_spill: Option<mitm::Spill>,
+    /// Directory caching the tables of meet-in-the-middle searches.
+    mitm_cache: Option<PathBuf>,
+    /// Spread the SIMD lanes of searches over DFS nodes rather than over characters.
+    node_lanes: bool,
+    /// Statistics accumulated over all searches run so far.
+    stats: SearchStats,
+}
+
+impl CpuBackend {
+    fn params<'a, H: HashWord>(&self, cfg: &'a SearchConfig) -> SearchParams<'a, H> {
+        SearchParams {
+            alphabet: &cfg.alphabet,
+            final_alphabet: &cfg.final_alphabet,
+            min_len: cfg.min_len,
+            max_len: cfg.max_len,
+            min_distinct: self.min_distinct,
+            targets: &cfg.targets,
+            fnv: cfg.hash.typed(),
+            deadline: self.deadline,
+            path_sample: self.path_sample,
+            // borrowed from `self` separately in `run_sized`, alongside the stats
+            ngram: None,
+            context: &cfg.prefix,
+            regex: None,
+            contains: &[],
+            rules: None,
+            segment: &cfg.prefix[self.segment_start.min(cfg.prefix.len())..],
+            node_lanes: self.node_lanes,
+        }
+    }
+
+    /// Run the search described by `cfg` with every one of `suffixes` in place of `cfg.suffix`,
+    /// or only the subtrees below `seeds` if given.
+    fn run(
+        &mut self,
+        cfg: &SearchConfig,
+        suffixes: &[Vec<u8>],
+        seeds: Option<&[Seed]>,
+    ) -> MatchVec {
+        let run = match (cfg.hash.width, cfg.hash.variant) {
+            (HashWidth::Bits32, HashVariant::MulAdd) => Self::run_typed::<u32, false>,
+            (HashWidth::Bits32, HashVariant::Fnv1a) => Self::run_typed::<u32, true>,
+            (HashWidth::Bits64, HashVariant::MulAdd) => Self::run_typed::<u64, false>,
+            (HashWidth::Bits64, HashVariant::Fnv1a) => Self::run_typed::<u64, true>,
+        };
+        run(self, cfg, suffixes, seeds)
+    }
+
+    /// Like [`Backend::search`], but with every one of `suffixes` in place of `cfg.suffix`,
+    /// returning the middles matching with at least one of them.
+    fn search_suffixes(
+        &mut self,
+        cfg: &SearchConfig,
+        suffixes: &[Vec<u8>],
+    ) -> Result<Vec<Vec<u8>>, BackendError> {
+        self.supports(cfg)?;
+        Ok(self
+            .run(cfg, suffixes, None)
+            .iter()
+            .map(|m| m.bytes()[..m.len].to_vec())
+            .collect())
+    }
+
+    fn run_typed<H: HashWord, const FNV1A: bool>(
+        &mut self,
+        cfg: &SearchConfig,
+        suffixes: &[Vec<u8>],
+        seeds: Option<&[Seed]>,
+    ) -> MatchVec
+    where
+        Simd<H, 4>: Add<Output = Simd<H, 4>>
+            + Mul<Output = Simd<H, 4>>
+            + Sub<Output = Simd<H, 4>>
+            + BitXor<Output = Simd<H, 4>>
+            + SimdPartialOrd
+            + SimdPartialEq<Mask = Mask<H::Mask, 4>>
+            + SimdUint<Cast<u64> = Simd<u64, 4>>,
+    {
+        // monomorphize the search for common alphabet sizes, so that runtime alphabets get the
+        // same unrolled loops as compile-time ones
+        match cfg.alphabet.bytes().len() {
+            16 => self.run_sized::<H, FNV1A, 16>(cfg, suffixes, seeds),
+            26 => self.run_sized::<H, FNV1A, 26>(cfg, suffixes, seeds),
+            38 => self.run_sized::<H, FNV1A, 38>(cfg, suffixes, seeds),
+            40 => self.run_sized::<H, FNV1A, 40>(cfg, suffixes, seeds),
+            64 => self.run_sized::<H, FNV1A, 64>(cfg, suffixes, seeds),
+            _ => self.run_sized::<H, FNV1A, 0>(cfg, suffixes, seeds),
+        }
+    }
+
+    fn run_sized<H: HashWord, const FNV1A: bool, const N: usize>(
+        &mut self,
+        cfg: &SearchConfig,
+        suffixes: &[Vec<u8>],
+        seeds: Option<&[Seed]>,
+    ) -> MatchVec
+    where
+        Simd<H, 4>: Add<Output = Simd<H, 4>>
+            + Mul<Output = Simd<H, 4>>
+            + Sub<Output = Simd<H, 4>>
+            + BitXor<Output = Simd<H, 4>>
+            + SimdPartialOrd
+            + SimdPartialEq<Mask = Mask<H::Mask, 4>>
+            + SimdUint<Cast<u64> = Simd<u64, 4>>,
+    {
+        let params = SearchParams {
+            ngram: self
+                .ngram
+                .as_ref()
+                .map(|(model, threshold)| (model, *threshold)),
+            regex: self.regex.as_ref(),
+            contains: &self.contains,
+            rules: (!self.rules.is_empty()).then_some(&self.rules),
+            ..self.params::<H>(cfg)
+        };
+        let stats = &mut self.stats;
+        match seeds {
+            Some(seeds) => {
+                find_collisions_simd_seeded::<H, FNV1A, N, 4>(seeds, suffixes, &params, stats)
+            }
+            None => find_collisions_simd::<H, FNV1A, N, 4>(&cfg.prefix, suffixes, &params, stats),
+        }
+    }
+}
+
+impl Backend for CpuBackend {
+    fn name(&self) -> &'static str {
+        "cpu"
+    }
+
+    fn supports(&self, cfg: &SearchConfig) -> Result<(), BackendError> {
+        match cfg.max_len {
+            ..=8 => Ok(()),
+            _ => Err(BackendError(
+                "the cpu backend can only find strings of at most 8 characters".into(),
+            )),
+        }
+    }
+
+    fn search(&mut self, cfg: &SearchConfig) -> Result<Vec<Vec<u8>>, BackendError> {
+        self.search_suffixes(cfg, slice::from_ref(&cfg.suffix))
+    }
+
+    fn supports_mitm(&self, _cfg: &MitmConfig) -> Result<(), BackendError> {
+        Ok(())
+    }
+
+    fn search_mitm(&mut self, cfg: &MitmConfig) -> Result<Vec<Vec<u8>>, BackendError> {
+        match &self.mitm_spill {
+            Some(spill) => mitm::search_spilled(cfg, spill, self.deadline, &mut self.stats)
+                .map_err(|e| {
+                    BackendError(format!("failed to spill to {}: {e}", spill.dir.display()))
+                }),
+            None => Ok(mitm::search(
+                cfg,
+                self.mitm_cache.as_deref(),
+                self.deadline,
+                &mut self.stats,
+            )),
         }
     }
 }
 
+/// A partially explored branch of the search: `partial` along with `hash(prefix|partial)`,
+/// widened to a `u64`.
 #[derive(Debug, Clone, Copy)]
-struct Match {
-    bytes_be: u64,
-    len: usize,
+struct Seed {
+    hash: u64,
+    partial: Match,
+}
+
+/// The [target shifts](PrecomputedSuffix::target_shift) of every target of a search, sorted so
+/// that the ones reachable from a hash base by a single character can be found with a binary
+/// search rather than by checking each target in turn.
+///
+/// A search for several suffixes at once holds the shifts of every suffix and target pair, so
+/// solved characters match with at least one of the suffixes, but not necessarily all of them.
+///
+/// Shifts differing only in their low byte fall in the same bucket. For large target sets, a
+/// [`BloomFilter`] of the buckets lets most hash bases skip the binary search entirely.
+#[derive(Debug, Clone)]
+struct TargetShifts<H> {
+    sorted: Vec<H>,
+    filter: Option<BloomFilter>,
+}
+
+impl<H: HashWord> TargetShifts<H> {
+    fn new(suffixes: &[impl AsRef<[u8]>], targets: &[u64], fnv: &Fnv<H>) -> Self {
+        assert!(!targets.is_empty(), "no targets to search for");
+        let mut sorted: Vec<_> = suffixes
+            .iter()
+            .flat_map(|suffix| {
+                targets.iter().map(move |&t| {
+                    let target = H::from_u64(t).expect("target doesn't fit the hash width");
+                    PrecomputedSuffix::new(suffix.as_ref(), target, fnv).target_shift
+                })
+            })
+            .collect();
+        sorted.sort_unstable_by_key(|s| s.to_u64());
+        sorted.dedup();
+
+        let filter = (sorted.len() >= BLOOM_MIN_TARGETS).then(|| {
+            let mut filter = BloomFilter::with_capacity(sorted.len());
+            for shift in &sorted {
+                filter.insert(shift.to_u64() >> 8);
+            }
+            filter
+        });
+        Self { sorted, filter }
+    }
+
+    /// The target shift, if there is a single target.
+    fn single(&self) -> Option<H> {
+        match self.sorted.as_slice() {
+            &[shift] => Some(shift),
+            _ => None,
+        }
+    }
+
+    /// Call `f` with every last character of `final_alphabet` which takes `hash_base` to one of
+    /// the targets.
+    #[inline]
+    fn solve<const FNV1A: bool>(
+        &self,
+        hash_base: H,
+        final_alphabet: &DynAlphabet,
+        f: impl FnMut(H),
+    ) {
+        // the shifts within one byte of the hash base form a contiguous range: `[base, base +
+        // 255]` for mul-add, which may wrap around, and `base` with its low byte cleared or set
+        // for FNV-1a. Skip the binary search unless a bucket this range spans may hold a target
+        if let Some(filter) = &self.filter {
+            let first = hash_base.to_u64() >> 8;
+            let last = match FNV1A {
+                true => first,
+                false => hash_base.wrapping_add(H::from_u32(0xff)).to_u64() >> 8,
+            };
+            if !filter.may_contain(first) && !filter.may_contain(last) {
+                return;
+            }
+        }
+
+        let solve = |shift: H| match FNV1A {
+            true => shift.xor(hash_base),
+            false => shift.wrapping_sub(hash_base),
+        };
+        let low = match FNV1A {
+            true => hash_base.to_u64() & !0xff,
+            false => hash_base.to_u64(),
+        };
+
+        let start = self.sorted.partition_point(|s| s.to_u64() < low);
+        let (wrapped, from_low) = self.sorted.split_at(start);
+        from_low
+            .iter()
+            .chain(wrapped)
+            .map(|&shift| solve(shift))
+            .take_while(|s| s.to_u64() <= 0xff)
+            .filter(|&s| final_alphabet.contains(s))
+            .for_each(f);
+    }
 }
 
-impl Match {
-    pub fn bytes(&self) -> [u8; 8] {
-        self.bytes_be
-            .rotate_right(8 * self.len as u32)
-            .to_be_bytes()
+/// Every pair of a second-to-last character of an alphabet and a last character of a final
+/// alphabet, keyed by what the pair adds to a mul-add hash: `hash(prefix|c1|c2)` is
+/// `(hash_base + c1) * prime + c2` for `hash_base = hash(prefix) * prime`, so a target shift
+/// minus `hash_base * prime` is looked up to solve for both characters at once.
+///
+/// The table is open-addressed with linear probing and at most half full, so that lookups
+/// take about one probe.
+#[derive(Debug, Clone)]
+struct EndgameTable<H> {
+    /// `(c1 * prime + c2, c1, c2)` for each pair, or `None` for empty slots.
+    slots: Vec<Option<(H, u8, u8)>>,
+}
+
+impl<H: HashWord> EndgameTable<H> {
+    fn new(alphabet: &DynAlphabet, final_alphabet: &DynAlphabet, prime: H) -> Self {
+        let pairs = alphabet.bytes().len() * final_alphabet.bytes().len();
+        let mut table = Self {
+            slots: vec![None; (2 * pairs).next_power_of_two()],
+        };
+        for &c1 in alphabet.bytes() {
+            for &c2 in final_alphabet.bytes() {
+                let key = H::from_u32(c1 as u32)
+                    .wrapping_mul(prime)
+                    .wrapping_add(H::from_u32(c2 as u32));
+                let mut i = table.slot(key);
+                while table.slots[i].is_some() {
+                    i = (i + 1) & (table.slots.len() - 1);
+                }
+                table.slots[i] = Some((key, c1, c2));
+            }
+        }
+        table
+    }
+
+    #[inline(always)]
+    fn slot(&self, key: H) -> usize {
+        // Fibonacci hashing, since the keys of pairs sharing `c1` only differ in their low bits
+        let hash = key.to_u64().wrapping_mul(0x9e37_79b9_7f4a_7c15);
+        (hash >> 32) as usize & (self.slots.len() - 1)
+    }
+
+    /// Call `f` with every pair `(c1, c2)` adding `key` to a hash base.
+    #[inline]
+    fn solve(&self, key: H, mut f: impl FnMut(u8, u8)) {
+        let mut i = self.slot(key);
+        while let Some((k, c1, c2)) = self.slots[i] {
+            if k == key {
+                f(c1, c2);
+            }
+            i = (i + 1) & (self.slots.len() - 1);
+        }
     }
 }
 
 fn main() {
+    let args = Args::parse();
+    let flavor = HashFlavor {
+        width: args.hash_width,
+        variant: args.variant,
+        prime: args
+            .prime
+            .unwrap_or(HashFlavor::fromsoft(args.hash_width).prime),
+        basis: args.basis,
+    };
+    if let Err(e) = flavor.validate() {
+        eprintln!("invalid hash: {e}");
+        exit(1);
+    }
+    let hash_width = flavor.width;
+    let target = match args.target {
+        Some(target) if !hash_width.fits(target) => {
+            eprintln!("target {target:x} doesn't fit in a {hash_width}-bit hash");
+            exit(1);
+        }
+        Some(target) => Some(target),
+        None => (flavor == HashFlavor::default()).then_some(TARGET),
+    };
+
+    match &args.command {
+        Some(Command::Check { names, targets }) => {
+            let targets = targets.as_deref().or(args.targets.as_deref());
+            check::run(names, targets, target, flavor)
+        }
+        Some(Command::Alphabet {
+            names,
+            by_frequency,
+        }) => corpus::run(names, *by_frequency),
+        Some(Command::Lengths {
+            names,
+            prefixes,
+            coverage,
+        }) => corpus::suggest_lengths(names, prefixes, *coverage),
+        Some(Command::Journal {
+            command: JournalCommand::Report { file },
+        }) => {
+            if let Err(e) = journal::report(file) {
+                eprintln!("failed to read journal {}: {e}", file.display());
+                exit(1);
+            }
+            return;
+        }
+        Some(Command::VerifyKernel {
+            source,
+            rounds,
+            seed,
+        }) => {
+            let source = fs::read_to_string(source).unwrap_or_else(|e| {
+                eprintln!("failed to read kernel {}: {e}", source.display());
+                exit(1);
+            });
+            let mut backend = kernel_backend(source);
+            let seed = seed.unwrap_or_else(|| SplitMix64::from_time().next_u64());
+            verify::run(backend.as_mut(), *rounds, seed)
+        }
+        None => {}
+    }
+
+    let (targets, known) = load_targets(&args, &flavor, target);
     let now = Instant::now();
 
-    let mut prefix = PREFIX.to_owned();
-    prefix.push(0);
+    let ngram = load_ngram(&args);
+    // beam searches score partial strings with the model rather than prune them
+    let (ngram, beam_model) = match args.beam {
+        Some(_) => (None, ngram),
+        None => (ngram, None),
+    };
+    let mode = Mode::load(&args, &flavor, beam_model.as_ref());
+    let prefixes = match &args.prefixes {
+        Some(path) => prefixes::load_prefixes(path).unwrap_or_else(|e| {
+            eprintln!("failed to load prefixes from {}: {e}", path.display());
+            exit(1);
+        }),
+        None => vec![Prefix {
+            prefix: args.prefix.as_bytes().to_owned(),
+            lengths: None,
+            suffixes: None,
+        }],
+    };
+
+    let mut run = Run::new(
+        &args, &mode, flavor, now, &targets, &known, &prefixes, ngram,
+    );
+    run.search();
+    run.finish()
+}
+
+/// Load the targets of the search from `--targets`, or `target` if it isn't given, along with
+/// the `--known` names. Targets resolved by known names are left out, and the process exits if
+/// none are left to search for.
+fn load_targets(
+    args: &Args,
+    flavor: &HashFlavor,
+    target: Option<u64>,
+) -> (Vec<u64>, HashSet<Vec<u8>>) {
+    // targets aren't used when emitting all candidates
+    let targets = match (&args.targets, target) {
+        (Some(path), _) => check::load_targets(path, flavor.width).unwrap_or_else(|e| {
+            eprintln!("failed to load targets from {}: {e}", path.display());
+            exit(1);
+        }),
+        (None, Some(target)) => vec![target],
+        (None, None) if args.emit_all => Vec::new(),
+        (None, None) => {
+            eprintln!("--target or --targets is required unless searching the default hash");
+            exit(1);
+        }
+    };
+    let known = match &args.known {
+        Some(path) => check::load_known_names(path).unwrap_or_else(|e| {
+            eprintln!("failed to load known names from {}: {e}", path.display());
+            exit(1);
+        }),
+        None => HashSet::new(),
+    };
+    let targets = match known.is_empty() {
+        true => targets,
+        false => {
+            let resolved: HashSet<_> = known.iter().map(|name| flavor.hash(name)).collect();
+            let total = targets.len();
+            let targets: Vec<_> = targets
+                .into_iter()
+                .filter(|t| !resolved.contains(t))
+                .collect();
+            if targets.len() < total {
+                eprintln!(
+                    "skipping {} of {total} targets resolved by known names",
+                    total - targets.len()
+                );
+                if targets.is_empty() && !args.emit_all {
+                    exit(status::EXIT_ALL_RESOLVED);
+                }
+            }
+            targets
+        }
+    };
+    if targets.is_empty() && !args.emit_all {
+        eprintln!("no targets to search for");
+        exit(1);
+    }
+    (targets, known)
+}
+
+/// Train the n-gram model of `--ngram` on its names, exiting if they can't be read.
+fn load_ngram(args: &Args) -> Option<NgramModel> {
+    args.ngram.as_ref().map(|path| {
+        let names = fs::read(path).unwrap_or_else(|e| {
+            eprintln!("failed to load names from {}: {e}", path.display());
+            exit(1);
+        });
+        let names = names
+            .split(|&b| b == b'\n')
+            .map(<[u8]>::trim_ascii)
+            .filter(|name| !name.is_empty());
+        NgramModel::train(args.ngram_order, names)
+    })
+}
+
+/// Search `cfg` with every one of `suffixes` in place of `cfg.suffix`, returning the middles
+/// matching with at least one of them.
+///
+/// The CPU checks every suffix in a single pass, while the GPU searches them in turn.
+fn search_chunk(
+    cpu: &mut CpuBackend,
+    gpu: &mut Option<Box<dyn Backend>>,
+    cfg: &SearchConfig,
+    suffixes: &[Vec<u8>],
+    fallback: bool,
+) -> Result<Vec<Vec<u8>>, BackendError> {
+    let Some(gpu) = gpu else {
+        return cpu.search_suffixes(cfg, suffixes);
+    };
+
+    let mut matches = Vec::new();
+    for suffix in suffixes {
+        let cfg = SearchConfig {
+            suffix: suffix.clone(),
+            ..cfg.clone()
+        };
+        matches.extend(search_suffix(cpu, gpu.as_mut(), &cfg, fallback)?);
+    }
+    // a middle may match with several suffixes
+    if suffixes.len() > 1 {
+        matches.sort_unstable();
+        matches.dedup();
+    }
+    Ok(matches)
+}
+
+/// Search `cfg` by meeting in the middle on `gpu` if there is one, or on `cpu`.
+///
+/// If `gpu` can't run `cfg`, it runs on `cpu` when `fallback` is set, and fails otherwise.
+fn search_mitm(
+    cpu: &mut CpuBackend,
+    gpu: &mut Option<Box<dyn Backend>>,
+    cfg: &MitmConfig,
+    fallback: bool,
+) -> Result<Vec<Vec<u8>>, BackendError> {
+    let Some(gpu) = gpu else {
+        return cpu.search_mitm(cfg);
+    };
+    if let Err(e) = gpu.supports_mitm(cfg) {
+        return match fallback {
+            true => cpu.search_mitm(cfg),
+            false => Err(BackendError(format!(
+                "{} backend can't run this search: {e}",
+                gpu.name()
+            ))),
+        };
+    }
+    gpu.search_mitm(cfg)
+}
+
+/// Search `cfg` on `gpu`, leaving the lengths it can't find to `cpu`.
+///
+/// If `gpu` doesn't support `cfg`, the whole search runs on `cpu` when `fallback` is set, and
+/// fails otherwise.
+fn search_suffix(
+    cpu: &mut CpuBackend,
+    gpu: &mut dyn Backend,
+    cfg: &SearchConfig,
+    fallback: bool,
+) -> Result<Vec<Vec<u8>>, BackendError> {
+    let gpu_cfg = SearchConfig {
+        min_len: cfg.min_len.max(gpu.min_len()),
+        ..cfg.clone()
+    };
+    if gpu_cfg.min_len > gpu_cfg.max_len {
+        return cpu.search(cfg);
+    }
+    if let Err(e) = gpu.supports(&gpu_cfg) {
+        return match fallback {
+            true => cpu.search(cfg),
+            false => Err(BackendError(format!(
+                "{} backend can't run this search: {e}",
+                gpu.name()
+            ))),
+        };
+    }
+
+    let mut matches = gpu.search(&gpu_cfg)?;
+    if cfg.min_len < gpu_cfg.min_len {
+        let cpu_cfg = SearchConfig {
+            max_len: gpu_cfg.min_len - 1,
+            ..cfg.clone()
+        };
+        matches.extend(cpu.search(&cpu_cfg)?);
+    }
+    Ok(matches)
+}
+
+/// Open the GPU backend selected by `kind`, if any.
+///
+/// Exits if `kind` requires a backend which isn't available.
+#[cfg(feature = "opencl")]
+fn gpu_backend(kind: BackendKind) -> Option<Box<dyn Backend>> {
+    use fs_hardblast_opencl::OpenClBackend;
+
+    match kind {
+        BackendKind::Cpu => None,
+        BackendKind::Opencl => match OpenClBackend::new() {
+            Ok(Some(backend)) => Some(Box::new(backend)),
+            Ok(None) => {
+                eprintln!("no usable OpenCL GPU device found.");
+                exit(status::EXIT_NO_DEVICE);
+            }
+            Err(e) => {
+                eprintln!("{e}.");
+                exit(1);
+            }
+        },
+        BackendKind::Auto => match OpenClBackend::new() {
+            Ok(Some(backend)) => Some(Box::new(backend)),
+            Ok(None) => {
+                println!("no usable OpenCL GPU device found, using the cpu backend.");
+                None
+            }
+            Err(e) => {
+                println!("{e}, using the cpu backend.");
+                None
+            }
+        },
+    }
+}
+
+/// Open the GPU backend selected by `kind`, if any.
+///
+/// Exits if `kind` requires a backend which isn't available.
+#[cfg(not(feature = "opencl"))]
+fn gpu_backend(kind: BackendKind) -> Option<Box<dyn Backend>> {
+    if kind == BackendKind::Opencl {
+        eprintln!("the opencl backend isn't available, rebuild with `--features opencl`.");
+        exit(1);
+    }
+    None
+}
+
+/// Open the OpenCL backend running the kernel in `source`.
+///
+/// Exits if the backend isn't available.
+#[cfg(feature = "opencl")]
+fn kernel_backend(source: String) -> Box<dyn Backend> {
+    use fs_hardblast_opencl::OpenClBackend;
 
-    for &start_char in START {
-        *prefix.last_mut().unwrap() = start_char;
+    match OpenClBackend::new() {
+        Ok(Some(backend)) => Box::new(backend.with_kernel_source(source)),
+        Ok(None) => {
+            eprintln!("no usable OpenCL GPU device found.");
+            exit(status::EXIT_NO_DEVICE);
+        }
+        Err(e) => {
+            eprintln!("{e}.");
+            exit(1);
+        }
+    }
+}
+
+/// Open the OpenCL backend running the kernel in `source`.
+///
+/// Exits if the backend isn't available.
+#[cfg(not(feature = "opencl"))]
+fn kernel_backend(_source: String) -> Box<dyn Backend> {
+    eprintln!("the opencl backend isn't available, rebuild with `--features opencl`.");
+    exit(1);
+}
+
+/// Print every string `prefix|m|suffix` where `m` is made of at most `max_len` characters of
+/// `alphabet`, formatted using `template` with its hash under `flavor`, except for `known` names.
+#[allow(clippy::too_many_arguments)]
+fn emit_all<H: HashWord>(
+    prefix: &[u8],
+    suffix: &[u8],
+    max_len: usize,
+    alphabet: &DynAlphabet,
+    flavor: &HashFlavor,
+    template: &OutputTemplate,
+    known: &HashSet<Vec<u8>>,
+    escape: bool,
+) {
+    let fnv = flavor.typed::<H>();
 
-        for m in find_collisions_simd::<4>(&prefix, SUFFIX, SEARCH, TARGET) {
-            let match_bytes = &m.bytes()[..m.len];
+    let mut candidate = prefix.to_owned();
+    let mut line = Vec::new();
 
-            let mut collision = prefix.clone();
-            collision.extend_from_slice(match_bytes);
-            collision.extend_from_slice(SUFFIX);
+    // (hash of candidate, middle length, last char of middle)
+    let mut stack = vec![(fnv.hash(prefix), 0, None)];
+    while let Some((hash, len, char)) = stack.pop() {
+        candidate.truncate(prefix.len() + len - char.is_some() as usize);
+        candidate.extend(char);
 
-            println!("{}", String::from_utf8_lossy(&collision));
+        let full_hash = fnv.extend(hash, suffix);
+        let is_known = !known.is_empty() && known.contains(&[&candidate[..], suffix].concat());
+        let fields = Fields {
+            prefix,
+            middle: &candidate[prefix.len()..],
+            suffix,
+            hash: full_hash.to_u64(),
+            hash_width: H::WIDTH,
+        };
+        if !is_known {
+            line.clear();
+            template.render(&fields, &mut line);
+            print_match(&line, escape);
+        }
 
-            // for validation purposes
-            assert_eq!(fnv_hash(&collision), TARGET)
+        if len < max_len {
+            for &c in alphabet.bytes().iter().rev() {
+                stack.push((fnv.extend(hash, &[c]), len + 1, Some(c)));
+            }
         }
     }
+}
+
+/// Parse a hash given in hex, with or without a `0x` prefix.
+fn parse_hash(s: &str) -> Result<u64, String> {
+    u64::from_str_radix(s.trim_start_matches("0x"), 16).map_err(|e| format!("invalid hash: {e}"))
+}
+
+/// Parse an integer given in decimal, or in hex with a `0x` prefix.
+fn parse_int(s: &str) -> Result<u64, String> {
+    match s.strip_prefix("0x") {
+        Some(hex) => u64::from_str_radix(hex, 16),
+        None => s.parse(),
+    }
+    .map_err(|e| format!("invalid integer: {e}"))
+}
+
+/// Parse a maximum search length, which is limited by the 8 bytes a [`Match`] can hold.
+fn parse_max_len(s: &str) -> Result<usize, String> {
+    match s.parse() {
+        Ok(len @ 0..=SEARCH) => Ok(len),
+        Ok(_) => Err(format!("must be at most {SEARCH}")),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+fn parse_ngram_order(s: &str) -> Result<usize, String> {
+    match s.parse() {
+        Ok(order @ 1..=ngram::MAX_ORDER) => Ok(order),
+        Ok(_) => Err(format!("must be between 1 and {}", ngram::MAX_ORDER)),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+fn parse_free_chars(s: &str) -> Result<usize, String> {
+    match s.parse() {
+        Ok(len @ 1..=4) => Ok(len),
+        Ok(_) => Err("must be between 1 and 4".into()),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+fn parse_alphabet(s: &str) -> Result<DynAlphabet, String> {
+    if s.is_empty() {
+        return Err("alphabet is empty".into());
+    }
+    DynAlphabet::new(s.as_bytes()).map_err(|e| e.to_string())
+}
+
+/// Parse a duration made of `<number><unit>` pairs, e.g. `1h30m`. Supported units are `h`,
+/// `m`, `s` and `ms`.
+fn parse_duration(s: &str) -> Result<Duration, String> {
+    let mut total = Duration::ZERO;
+    let mut rest = s.trim();
+    if rest.is_empty() {
+        return Err("empty duration".into());
+    }
 
-    println!("{:?}", now.elapsed());
+    while !rest.is_empty() {
+        let num_end = rest
+            .find(|c: char| !c.is_ascii_digit())
+            .unwrap_or(rest.len());
+        let unit_end = rest[num_end..]
+            .find(|c: char| c.is_ascii_digit())
+            .map_or(rest.len(), |i| num_end + i);
+
+        let value: u64 = rest[..num_end]
+            .parse()
+            .map_err(|_| format!("invalid duration `{s}`"))?;
+        total += match &rest[num_end..unit_end] {
+            "h" => Duration::from_secs(value * 3600),
+            "m" => Duration::from_secs(value * 60),
+            "s" | "" => Duration::from_secs(value),
+            "ms" => Duration::from_millis(value),
+            unit => return Err(format!("unknown duration unit `{unit}`")),
+        };
+        rest = &rest[unit_end..];
+    }
+    Ok(total)
 }
 
-const fn fnv_hash(data: &[u8]) -> u32 {
-    let mut hash: u32 = 0;
-    let mut i = 0;
-    while i < data.len() {
-        hash = hash.wrapping_mul(FNV_PRIME).wrapping_add(data[i] as u32);
-        i += 1;
+/// Load search seeds from a file of `<hash hex> <partial>` lines relative to `prefix`, hashed at
+/// `flavor`. Partial strings may be at most one character longer than `max_len`, to account
+/// for the start character.
+///
+/// Seeds whose hash doesn't match their partial string are rejected.
+fn load_seeds(
+    path: &Path,
+    prefix: &[u8],
+    max_len: usize,
+    flavor: &HashFlavor,
+) -> io::Result<Vec<Seed>> {
+    let invalid = |msg: String| io::Error::new(io::ErrorKind::InvalidData, msg);
+
+    let mut seeds = Vec::new();
+    for (i, line) in fs::read(path)?.split(|&b| b == b'\n').enumerate() {
+        let line = line.trim_ascii();
+        if line.is_empty() {
+            continue;
+        }
+
+        let (hash, partial) = line
+            .iter()
+            .position(|b| b.is_ascii_whitespace())
+            .map(|pos| (&line[..pos], line[pos..].trim_ascii()))
+            .ok_or_else(|| invalid(format!("line {}: expected `<hash> <partial>`", i + 1)))?;
+        let hash = str::from_utf8(hash)
+            .ok()
+            .and_then(|h| u64::from_str_radix(h.trim_start_matches("0x"), 16).ok())
+            .ok_or_else(|| invalid(format!("line {}: invalid hash", i + 1)))?;
+        if partial.len() > max_len + 1 {
+            return Err(invalid(format!("line {}: partial string too long", i + 1)));
+        }
+
+        let mut full = prefix.to_owned();
+        full.extend_from_slice(partial);
+        if flavor.hash(&full) != hash {
+            return Err(invalid(format!(
+                "line {}: hash {hash:0width$x} doesn't match {}",
+                i + 1,
+                partial.escape_ascii(),
+                width = flavor.width.hex_digits()
+            )));
+        }
+
+        seeds.push(Seed {
+            hash,
+            partial: Match::from_bytes(partial),
+        });
     }
-    hash
+    Ok(seeds)
 }
 
-/// Find bytes strings `m` of length at most `max_len` such that
+/// Find bytes strings `m` of length between `params.min_len` and `params.max_len` such that
 ///
 /// ```text
-/// fnv_hash(prefix|m|suffix) == target_hash
+/// params.fnv.hash(prefix|m|suffix) is in params.targets
 /// ```
 ///
+/// for at least one `suffix` of `suffixes`, all of which are checked in the same pass.
+///
 /// The maximum value of `max_len` is 8.
 ///
+/// Branches which can't lead to strings with at least `params.min_distinct` distinct characters
+/// are pruned, but matches with fewer distinct characters may still be returned. So are
+/// branches whose partial string `params.ngram` finds implausible, which may lose matches, and
+/// those whose partial string can't start a match of `params.regex`, contain all of
+/// `params.contains` or follow `params.rules` anymore, though matches still have to be checked
+/// against them. If
+/// `params.deadline` passes, the search stops early and sets `stats.timed_out`.
+///
 /// The search is optimized by using iterative DFS to avoid recomputing
 /// hashes, mathematically solving for the possible value of the last
 /// character and parallelizing the above over second-to-last characters
-/// using `L`-lane SIMD.
-fn find_collisions_simd<const L: usize>(
+/// using `L`-lane SIMD. If `N` is not 0, it must be the size of `params.alphabet`, which lets
+/// the loop over second-to-last characters be unrolled. With `params.node_lanes`, the lanes
+/// are spread over batches of `L` DFS nodes instead, which are extended by one character at a
+/// time.
+fn find_collisions_simd<H: HashWord, const FNV1A: bool, const N: usize, const L: usize>(
     prefix: &[u8],
-    suffix: &[u8],
-    max_len: usize,
-    target_hash: u32,
-) -> Vec<Match>
+    suffixes: &[Vec<u8>],
+    params: &SearchParams<H>,
+    stats: &mut SearchStats,
+) -> MatchVec
 where
     LaneCount<L>: SupportedLaneCount,
-    Simd<u32, L>: SimdPartialEq<Mask = Mask<i32, L>>,
+    Simd<H, L>: Add<Output = Simd<H, L>>
+        + Mul<Output = Simd<H, L>>
+        + Sub<Output = Simd<H, L>>
+        + BitXor<Output = Simd<H, L>>
+        + SimdPartialOrd
+        + SimdPartialEq<Mask = Mask<H::Mask, L>>
+        + SimdUint<Cast<u64> = Simd<u64, L>>,
 {
-    let suffix = PrecomputedSuffix::new(suffix, target_hash);
-    let prefix_hash = fnv_hash(prefix);
-    let mut matches = Vec::with_capacity(8);
+    let prefix_hash = params.fnv.hash(prefix);
+    let mut matches = MatchVec::with_capacity(8);
 
     // check the empty string (matches if prefix|suffix matches)
-    if prefix_hash == target_hash {
+    if params.min_len == 0
+        && suffixes.iter().any(|suffix| {
+            params
+                .targets
+                .contains(&params.fnv.extend(prefix_hash, suffix).to_u64())
+        })
+    {
         matches.push(Match {
             bytes_be: 0,
             len: 0,
         })
     }
 
-    // check one-character strings by directly solving for the possible value
-    let prefix_hash_base = prefix_hash.wrapping_mul(FNV_PRIME);
-    let one_length_collision = suffix.target_shift.wrapping_sub(prefix_hash_base);
-    if ALPHABET.contains(one_length_collision) {
-        matches.push(Match {
-            bytes_be: one_length_collision as u64,
-            len: 1,
-        })
-    }
+    let root = Seed {
+        hash: prefix_hash.to_u64(),
+        partial: Match {
+            bytes_be: 0,
+            len: 0,
+        },
+    };
+    let seeded = find_collisions_simd_seeded::<H, FNV1A, N, L>(&[root], suffixes, params, stats);
+    matches.extend(seeded.iter());
+    matches
+}
 
-    // having 2 vecs means that we can copy the next_hash_base vectors straight into
-    // the DFS stack
-    let init_cap = max_len * ALPHABET.bytes().len();
+/// Like [`find_collisions_simd`], but only search the strings `m` that extend one of the
+/// `seeds` partial strings.
+///
+/// Each seed carries the hash of `prefix|partial`, so the search can start directly from the
+/// seeded branches without knowing the prefix. Matches include the seed's partial string, and
+/// the partial strings themselves are not checked.
+fn find_collisions_simd_seeded<H: HashWord, const FNV1A: bool, const N: usize, const L: usize>(
+    seeds: &[Seed],
+    suffixes: &[Vec<u8>],
+    params: &SearchParams<H>,
+    stats: &mut SearchStats,
+) -> MatchVec
+where
+    LaneCount<L>: SupportedLaneCount,
+    Simd<H, L>: Add<Output = Simd<H, L>>
+        + Mul<Output = Simd<H, L>>
+        + Sub<Output = Simd<H, L>>
+        + BitXor<Output = Simd<H, L>>
+        + SimdPartialOrd
+        + SimdPartialEq<Mask = Mask<H::Mask, L>>
+        + SimdUint<Cast<u64> = Simd<u64, L>>,
+{
+    let &SearchParams {
+        alphabet,
+        final_alphabet,
+        min_len,
+        max_len,
+        min_distinct,
+        targets,
+        fnv,
+        deadline,
+        path_sample,
+        ngram,
+        context,
+        regex,
+        contains,
+        rules,
+        segment,
+        node_lanes,
+    } = params;
+    debug_assert_eq!(FNV1A, fnv.variant == HashVariant::Fnv1a);
+    // state of the regex once the searched characters before the searched string are matched
+    let regex = regex.map(|regex| (regex, regex.advance(regex.start(), segment)));
+    let contains: Vec<_> = contains
+        .iter()
+        .map(|s| (s, s.advance(0, segment)))
+        .collect();
+    // `None` once the searched characters before the searched string break the rules
+    let rules = rules.map(|rules| (rules, rules.advance(RulesState::default(), segment)));
+    let targets = TargetShifts::new(suffixes, targets, &fnv);
+    // a single target and suffix is solved for with plain SIMD arithmetic, several with a
+    // binary search per candidate
+    let single_target = targets.single();
+    let mut matches = MatchVec::with_capacity(8);
+    // the last two characters of mul-add hashes are solved for at once with a table lookup per
+    // target shift, which beats solving for the last character after each second-to-last one
+    // unless there are more shifts than characters
+    let endgame = (!FNV1A && targets.sorted.len() <= alphabet.bytes().len())
+        .then(|| EndgameTable::new(alphabet, final_alphabet, fnv.prime));
+
+    // keeping the DFS stack as a struct of arrays means that we can copy the next_hash_base
+    // and packed byte vectors straight into it
+    let init_cap = max_len * alphabet.bytes().len() + seeds.len();
     let mut hash_base_stack = Vec::with_capacity(init_cap);
-    let mut match_stack = Vec::with_capacity(init_cap);
+    let mut match_stack = MatchVec::with_capacity(init_cap);
+
+    for seed in seeds {
+        let partial = seed.partial;
+
+        // check one-character extensions by directly solving for the possible value
+        let seed_hash = H::from_u64(seed.hash).expect("seed hash doesn't fit the hash width");
+        // FNV-1a mixes characters in before multiplying by the prime, so its hash bases are the
+        // hashes themselves rather than the hashes multiplied by the prime
+        let prefix_hash_base = match FNV1A {
+            true => seed_hash,
+            false => seed_hash.wrapping_mul(fnv.prime),
+        };
+        if (min_len..=max_len).contains(&(partial.len + 1)) {
+            targets.solve::<FNV1A>(prefix_hash_base, final_alphabet, |s| {
+                matches.push(Match {
+                    bytes_be: (partial.bytes_be << 8) | s.to_u64(),
+                    len: partial.len + 1,
+                })
+            });
+        }
+
+        if partial.len + 2 <= max_len {
+            hash_base_stack.push(prefix_hash_base);
+            match_stack.push(Match {
+                bytes_be: partial.bytes_be,
+                len: partial.len + 2,
+            });
+        }
+    }
 
-    hash_base_stack.push(prefix_hash_base);
-    match_stack.push(Match {
+    let target_shift_splat = Simd::splat(single_target.unwrap_or(targets.sorted[0]));
+    let prime_splat = Simd::splat(fnv.prime);
+    let (alphabet_chunks, alphabet_remainder) = alphabet.simd_chunks_sized::<H, N, L>();
+    let mut next_path_sample = match path_sample {
+        0 => u64::MAX,
+        interval => stats.nodes + trace::sample_gap(stats.nodes, interval),
+    };
+
+    // nodes waiting to be processed together with `node_lanes`, of which the first `batch_len`
+    // are set
+    let mut batch_bases = [H::from_u32(0); L];
+    let mut batch_seqs = [Match {
         bytes_be: 0,
-        len: 2,
-    });
+        len: 0,
+    }; L];
+    let mut batch_len = 0;
+    let wide_alphabet = H::widened(alphabet);
+
+    loop {
+        // process a batch once it is full, or once the stack runs out of nodes to fill it with
+        if batch_len == L || (batch_len != 0 && hash_base_stack.is_empty()) {
+            let hash_bases = Simd::from_array(batch_bases);
+            // the children of a full batch of nodes at the same depth are pushed as vectors
+            let uniform_len = batch_seqs.iter().all(|seq| seq.len == batch_seqs[0].len);
+            let batch_depth = (batch_len == L && uniform_len).then_some(batch_seqs[0].len);
+            let batch_bytes_be = Simd::from_array(batch_seqs.map(|seq| seq.bytes_be << 8));
+            for &c in wide_alphabet {
+                let c_splat = Simd::splat(c);
+                let next_hash_base = match FNV1A {
+                    true => (hash_bases ^ c_splat) * prime_splat,
+                    false => (hash_bases + c_splat) * prime_splat,
+                };
+                let next = next_hash_base.as_array();
+
+                // add len+1 strings to the DFS stack
+                match batch_depth {
+                    Some(len) if len != max_len => {
+                        let next_bytes_be = batch_bytes_be | Simd::splat(c.to_u64());
+                        hash_base_stack.extend_from_slice(next);
+                        match_stack.extend_simd(next_bytes_be, len + 1);
+                    }
+                    Some(_) => (),
+                    None => {
+                        for (&next, seq) in next.iter().zip(&batch_seqs).take(batch_len) {
+                            if seq.len != max_len {
+                                hash_base_stack.push(next);
+                                match_stack.push(Match {
+                                    bytes_be: (seq.bytes_be << 8) | c.to_u64(),
+                                    len: seq.len + 1,
+                                });
+                            }
+                        }
+                    }
+                }
+                // solve for the only last character that could collide and report matches
+                let mut report = |lane: usize, s: H| {
+                    let seq = batch_seqs[lane];
+                    matches.push(Match {
+                        bytes_be: (seq.bytes_be << 16 | c.to_u64() << 8 | s.to_u64()),
+                        len: seq.len,
+                    })
+                };
+                if single_target.is_none() {
+                    for (lane, &base) in next.iter().enumerate().take(batch_len) {
+                        if batch_seqs[lane].len >= min_len {
+                            targets.solve::<FNV1A>(base, final_alphabet, |s| report(lane, s));
+                        }
+                    }
+                    continue;
+                }
+                let solutions = match FNV1A {
+                    true => target_shift_splat ^ next_hash_base,
+                    false => target_shift_splat - next_hash_base,
+                };
+                if unlikely(final_alphabet.simd_prefilter(solutions)) {
+                    for (lane, &s) in solutions.as_array().iter().enumerate().take(batch_len) {
+                        if batch_seqs[lane].len >= min_len && final_alphabet.contains(s) {
+                            report(lane, s);
+                        }
+                    }
+                }
+            }
+            batch_len = 0;
+            continue;
+        }
+
+        let (Some(hash_base), Some(seq)) = (hash_base_stack.pop(), match_stack.pop()) else {
+            break;
+        };
+        stats.stack_high_water = stats.stack_high_water.max(hash_base_stack.len() + 1);
+        stats.nodes += 1;
+
+        if unlikely(stats.nodes == next_path_sample) {
+            // entries hold the length the string will have once two characters are added
+            stats.sampled_paths.push(Match {
+                bytes_be: seq.bytes_be,
+                len: seq.len - 2,
+            });
+            next_path_sample += trace::sample_gap(stats.nodes, path_sample);
+        }
+
+        if stats.check_deadline(deadline) {
+            break;
+        }
+
+        // prune branches that can't reach `min_distinct` distinct characters, even if all the
+        // remaining ones are new
+        let known = seq.len - 2;
+        if min_distinct > 0 && seq.distinct_packed(known) + (max_len - known) < min_distinct {
+            continue;
+        }
+        if let Some((model, threshold)) = ngram {
+            let partial = Match {
+                bytes_be: seq.bytes_be,
+                len: known,
+            };
+            if model.mean_log_prob(context, &partial.bytes()[..known]) < threshold {
+                continue;
+            }
+        }
+        if let Some((regex, state)) = regex {
+            let partial = Match {
+                bytes_be: seq.bytes_be,
+                len: known,
+            };
+            if !regex.can_match(regex.advance(state, &partial.bytes()[..known])) {
+                continue;
+            }
+        }
+        if !contains.is_empty() {
+            let partial = Match {
+                bytes_be: seq.bytes_be,
+                len: known,
+            };
+            let partial = &partial.bytes()[..known];
+            if contains
+                .iter()
+                .any(|(s, state)| s.missing(s.advance(*state, partial)) > max_len - known)
+            {
+                continue;
+            }
+        }
+        if let Some((rules, state)) = rules {
+            let partial = Match {
+                bytes_be: seq.bytes_be,
+                len: known,
+            };
+            if state
+                .and_then(|state| rules.advance(state, &partial.bytes()[..known]))
+                .is_none()
+            {
+                continue;
+            }
+        }
 
-    let target_shift_splat = Simd::splat(suffix.target_shift);
+        // nodes at the deepest level have no children to push, only matches to solve for
+        if let Some(endgame) = &endgame
+            && seq.len == max_len
+        {
+            if seq.len >= min_len {
+                let hash_base = hash_base.wrapping_mul(fnv.prime);
+                for &shift in &targets.sorted {
+                    endgame.solve(shift.wrapping_sub(hash_base), |c1, c2| {
+                        matches.push(Match {
+                            bytes_be: seq.bytes_be << 16 | (c1 as u64) << 8 | c2 as u64,
+                            len: seq.len,
+                        })
+                    });
+                }
+            }
+            continue;
+        }
+
+        if node_lanes {
+            batch_bases[batch_len] = hash_base;
+            batch_seqs[batch_len] = seq;
+            batch_len += 1;
+            continue;
+        }
 
-    while let (Some(hash_base), Some(seq)) = (hash_base_stack.pop(), match_stack.pop()) {
         let hash_base_splat = Simd::splat(hash_base);
 
         // use simd to process second-to-last characters in parallel
-        //
-        // because these chunks are known at compile-time the loops below can be unrolled
-        // and bounds checks can be removed
-        let (alphabet_chunks, alphabet_remainder) = const { ALPHABET.simd_chunks::<L>() };
-
-        for chunk in alphabet_chunks.as_slice() {
-            let next_hash_base = (hash_base_splat + chunk) * Simd::splat(FNV_PRIME);
-            let chunk_arr = chunk.as_array();
+        for chunk_arr in alphabet_chunks {
+            let chunk = Simd::from_array(*chunk_arr);
+            let next_hash_base = match FNV1A {
+                true => (hash_base_splat ^ chunk) * prime_splat,
+                false => (hash_base_splat + chunk) * prime_splat,
+            };
 
             // add len+1 strings to the DFS stack
             if seq.len != max_len {
+                let next_bytes_be = Simd::splat(seq.bytes_be << 8) | chunk.cast::<u64>();
                 hash_base_stack.extend_from_slice(next_hash_base.as_array());
-                match_stack.extend(chunk_arr.iter().map(|&c| Match {
-                    bytes_be: (seq.bytes_be << 8) | (c as u64),
-                    len: seq.len + 1,
-                }));
+                match_stack.extend_simd(next_bytes_be, seq.len + 1);
+            }
+            if seq.len < min_len {
+                continue;
             }
             // solve for the only last character that could collide and report matches
-            let solutions = target_shift_splat - next_hash_base;
-            if unlikely(ALPHABET.simd_prefilter(solutions)) {
+            if single_target.is_none() {
+                for (&base, &c) in next_hash_base.as_array().iter().zip(chunk_arr) {
+                    targets.solve::<FNV1A>(base, final_alphabet, |s| {
+                        matches.push(Match {
+                            bytes_be: (seq.bytes_be << 16 | c.to_u64() << 8 | s.to_u64()),
+                            len: seq.len,
+                        })
+                    });
+                }
+                continue;
+            }
+            let solutions = match FNV1A {
+                true => target_shift_splat ^ next_hash_base,
+                false => target_shift_splat - next_hash_base,
+            };
+            if unlikely(final_alphabet.simd_prefilter(solutions)) {
                 matches.extend(
                     solutions
                         .as_array()
                         .iter()
                         .zip(chunk_arr)
-                        .filter(|(s, _)| ALPHABET.contains(**s))
+                        .filter(|(s, _)| final_alphabet.contains(**s))
                         .map(|(&s, &c)| Match {
-                            bytes_be: (seq.bytes_be << 16 | (c as u64) << 8 | s as u64),
+                            bytes_be: (seq.bytes_be << 16 | c.to_u64() << 8 | s.to_u64()),
                             len: seq.len,
                         }),
                 )
             }
         }
-        for &c in alphabet_remainder.as_slice() {
-            let next_hash_base = (hash_base + c).wrapping_mul(FNV_PRIME);
+        for &c in alphabet_remainder {
+            let next_hash_base = match FNV1A {
+                true => hash_base.xor(c).wrapping_mul(fnv.prime),
+                false => hash_base.wrapping_add(c).wrapping_mul(fnv.prime),
+            };
 
             // add len+1 strings to the DFS stack
             if seq.len != max_len {
                 hash_base_stack.push(next_hash_base);
                 match_stack.push(Match {
-                    bytes_be: (seq.bytes_be << 8) | (c as u64),
+                    bytes_be: (seq.bytes_be << 8) | c.to_u64(),
                     len: seq.len + 1,
                 });
             }
             // solve for the only last character that could collide and report matches
-            let s = suffix.target_shift - next_hash_base;
-            if unlikely(ALPHABET.contains(s)) {
-                matches.push(Match {
-                    bytes_be: (seq.bytes_be << 16 | (c as u64) << 8 | s as u64),
-                    len: seq.len,
-                })
+            if seq.len >= min_len {
+                targets.solve::<FNV1A>(next_hash_base, final_alphabet, |s| {
+                    matches.push(Match {
+                        bytes_be: (seq.bytes_be << 16 | c.to_u64() << 8 | s.to_u64()),
+                        len: seq.len,
+                    })
+                });
             }
         }
     }
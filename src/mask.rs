@@ -0,0 +1,112 @@
+use std::time::Instant;
+
+use fs_hardblast_core::{
+    HashFlavor, HashVariant, HashWidth, HashWord, alphabet::DynAlphabet, mask::Slot,
+};
+
+use crate::{SearchStats, TargetShifts};
+
+/// Find every middle `m` made of one character per slot of `slots`, such that
+///
+/// ```text
+/// flavor.hash(prefix|m|suffix) is in targets
+/// ```
+///
+/// `slots` must end with a set, as the middle of [`Mask::split`] does. The slots before it, which
+/// may span several gaps, are enumerated with a DFS, and the last one is solved for like the last
+/// character of a regular search. If `deadline` passes, the search stops early and sets
+/// `stats.timed_out`.
+///
+/// [`Mask::split`]: fs_hardblast_core::mask::Mask::split
+pub fn search(
+    prefix: &[u8],
+    slots: &[Slot],
+    suffix: &[u8],
+    targets: &[u64],
+    flavor: &HashFlavor,
+    deadline: Option<Instant>,
+    stats: &mut SearchStats,
+) -> Vec<Vec<u8>> {
+    let search = match (flavor.width, flavor.variant) {
+        (HashWidth::Bits32, HashVariant::MulAdd) => search_typed::<u32, false>,
+        (HashWidth::Bits32, HashVariant::Fnv1a) => search_typed::<u32, true>,
+        (HashWidth::Bits64, HashVariant::MulAdd) => search_typed::<u64, false>,
+        (HashWidth::Bits64, HashVariant::Fnv1a) => search_typed::<u64, true>,
+    };
+    search(prefix, slots, suffix, targets, flavor, deadline, stats)
+}
+
+/// Run of the enumerated slots of a mask.
+enum Step<'a> {
+    /// Consecutive fixed characters, hashed in one go.
+    Fixed(Vec<u8>),
+    Set(&'a DynAlphabet),
+}
+
+fn search_typed<H: HashWord, const FNV1A: bool>(
+    prefix: &[u8],
+    slots: &[Slot],
+    suffix: &[u8],
+    targets: &[u64],
+    flavor: &HashFlavor,
+    deadline: Option<Instant>,
+    stats: &mut SearchStats,
+) -> Vec<Vec<u8>> {
+    let Some((Slot::Set(last), enumerated)) = slots.split_last() else {
+        panic!("mask slots must end with a set");
+    };
+    let fnv = flavor.typed::<H>();
+    let targets = TargetShifts::new(&[suffix], targets, &fnv);
+
+    // the fixed text between gaps doesn't branch, so it needn't be a node per character
+    let mut steps = Vec::new();
+    for slot in enumerated {
+        match (slot, steps.last_mut()) {
+            (Slot::Fixed(c), Some(Step::Fixed(run))) => run.push(*c),
+            (Slot::Fixed(c), _) => steps.push(Step::Fixed(vec![*c])),
+            (Slot::Set(set), _) => steps.push(Step::Set(set)),
+        }
+    }
+
+    let mut matches = Vec::new();
+    let mut middle = Vec::with_capacity(slots.len());
+
+    // (hash of prefix|middle, index of the next step, middle length before the bytes, bytes the
+    // step added to middle)
+    let mut stack = vec![(fnv.hash(prefix), 0, 0, &[][..])];
+    while let Some((hash, step, len, bytes)) = stack.pop() {
+        stats.stack_high_water = stats.stack_high_water.max(stack.len() + 1);
+        stats.nodes += 1;
+
+        if stats.check_deadline(deadline) {
+            break;
+        }
+
+        middle.truncate(len);
+        middle.extend_from_slice(bytes);
+        let len = middle.len();
+
+        match steps.get(step) {
+            Some(Step::Fixed(run)) => stack.push((fnv.extend(hash, run), step + 1, len, run)),
+            Some(Step::Set(set)) => {
+                for c in set.bytes().chunks(1).rev() {
+                    stack.push((fnv.extend(hash, c), step + 1, len, c));
+                }
+            }
+            None => {
+                // solve for the characters of the last slot that collide
+                let hash_base = match FNV1A {
+                    true => hash,
+                    false => hash.wrapping_mul(fnv.prime),
+                };
+                targets.solve::<FNV1A>(hash_base, last, |s| {
+                    let mut m = middle.clone();
+                    m.push(s.to_u64() as u8);
+                    matches.push(m);
+                });
+            }
+        }
+    }
+
+    matches
+}
@@ -0,0 +1,488 @@
+use std::{
+    cmp::Reverse,
+    collections::BinaryHeap,
+    fs::{self, File},
+    io::{self, BufReader, BufWriter, Read, Write},
+    path::{Path, PathBuf},
+    process,
+    time::Instant,
+};
+
+use fs_hardblast_core::{
+    Fnv, HashVariant, HashWidth, HashWord, alphabet::DynAlphabet, backend::MitmConfig,
+};
+
+use crate::{
+    SearchStats,
+    table_cache::{self, Entry, Table},
+};
+
+/// Find every middle of `cfg` by meeting in the middle: the hashes of `prefix|front` for every
+/// front half of the positions are sorted in a table, then every back half is undone from each
+/// target, one character at a time from the end, and what is left is looked up in the table.
+/// This takes time and memory on the order of the square root of the number of middles, where
+/// [`mask::search`] takes time on the order of the number of middles divided by the size of the
+/// last position.
+///
+/// With a `cache` directory, the table is loaded from it if an earlier search cached the same
+/// table, which only depends on the hash, the prefix and the front positions, and is cached to
+/// it otherwise.
+///
+/// If `deadline` passes, the search stops early and sets `stats.timed_out`.
+///
+/// [`mask::search`]: crate::mask::search
+pub fn search(
+    cfg: &MitmConfig,
+    cache: Option<&Path>,
+    deadline: Option<Instant>,
+    stats: &mut SearchStats,
+) -> Vec<Vec<u8>> {
+    let search = match cfg.hash.width {
+        HashWidth::Bits32 => search_typed::<u32>,
+        HashWidth::Bits64 => search_typed::<u64>,
+    };
+    search(cfg, cache, deadline, stats)
+}
+
+fn search_typed<H: HashWord>(
+    cfg: &MitmConfig,
+    cache: Option<&Path>,
+    deadline: Option<Instant>,
+    stats: &mut SearchStats,
+) -> Vec<Vec<u8>> {
+    let fnv = cfg.hash.typed::<H>();
+    let halves = Halves::new(cfg, deadline);
+    let Halves { front, back, .. } = &halves;
+
+    // hashes of prefix|front and indices of the fronts, with the first position as the most
+    // significant digit
+    let mut build = || {
+        let size: usize = front.iter().map(|chars| chars.len()).product();
+        let mut table = Vec::with_capacity(size);
+        let extend = |hash, c| fnv.extend(hash, &[c]);
+        let built = for_each_combination(front, fnv.hash(&cfg.prefix), extend, |hash, _| {
+            let index = table.len() as u64;
+            table.push(Entry {
+                hash: hash.to_u64(),
+                index,
+            });
+            !halves.timed_out(stats)
+        });
+        built.then(|| {
+            table.sort_unstable();
+            table
+        })
+    };
+    let table = match cache {
+        Some(dir) => table_cache::load_or_build(dir, &table_key(cfg), build),
+        None => build().map(Table::Owned),
+    };
+    let Some(table) = table else {
+        return Vec::new();
+    };
+
+    let undo = undo(&fnv);
+
+    let mut targets = cfg.targets.clone();
+    targets.sort_unstable();
+    targets.dedup();
+
+    let mut matches = Vec::new();
+    let mut tail = vec![0; back.len()];
+    for target in targets {
+        let target = H::from_u64(target).expect("target doesn't fit the hash width");
+        // (hash of prefix|front|back[..depth], depth, character of back[depth] if depth <
+        // back.len()), with the characters after it already in `tail`
+        let mut stack = vec![(fnv.unextend(target, &cfg.suffix), back.len(), None)];
+        while let Some((hash, depth, c)) = stack.pop() {
+            stats.stack_high_water = stats.stack_high_water.max(stack.len() + 1);
+            if halves.timed_out(stats) {
+                return matches;
+            }
+            if let Some(c) = c {
+                tail[depth] = c;
+            }
+            if depth > 0 {
+                for &c in back[depth - 1].iter().rev() {
+                    stack.push((undo(hash, c), depth - 1, Some(c)));
+                }
+                continue;
+            }
+
+            let hash = hash.to_u64();
+            let start = table.partition_point(|e| e.hash < hash);
+            for &Entry { index, .. } in table[start..].iter().take_while(|e| e.hash == hash) {
+                let mut m = decode(front, index);
+                m.extend_from_slice(&tail);
+                matches.push(m);
+            }
+        }
+    }
+
+    matches
+}
+
+/// Characters of the front and back halves of the positions of a search, and the deadline
+/// their enumeration stops at.
+struct Halves<'a> {
+    front: Vec<&'a [u8]>,
+    back: Vec<&'a [u8]>,
+    deadline: Option<Instant>,
+}
+
+impl<'a> Halves<'a> {
+    fn new(cfg: &'a MitmConfig, deadline: Option<Instant>) -> Self {
+        let (front, back) = cfg.positions.split_at(cfg.split());
+        Self {
+            front: front.iter().map(DynAlphabet::bytes).collect(),
+            back: back.iter().map(DynAlphabet::bytes).collect(),
+            deadline,
+        }
+    }
+
+    /// Count a table entry or DFS node, returning whether the search timed out.
+    fn timed_out(&self, stats: &mut SearchStats) -> bool {
+        stats.nodes += 1;
+        stats.check_deadline(self.deadline)
+    }
+}
+
+/// Key of the table of the fronts of `cfg` in a cache, which describes everything the table
+/// depends on.
+fn table_key(cfg: &MitmConfig) -> Vec<u8> {
+    let mut key = format!("mitm fronts\n{:?}\n", cfg.hash).into_bytes();
+    let front = &cfg.positions[..cfg.split()];
+    for part in std::iter::once(&cfg.prefix[..]).chain(front.iter().map(DynAlphabet::bytes)) {
+        key.extend_from_slice(&(part.len() as u64).to_le_bytes());
+        key.extend_from_slice(part);
+    }
+    key
+}
+
+/// How a search spills its tables to disk, for tables that don't fit in memory.
+pub struct Spill {
+    /// Directory the sorted runs of the tables are written to.
+    pub dir: PathBuf,
+    /// Number of entries sorted in memory before they are written out as a run.
+    pub run_len: usize,
+}
+
+/// Size of an entry of a run on disk: its hash and index as little-endian `u64`s.
+const ENTRY_SIZE: usize = 16;
+
+/// Most runs merged at once, to stay well within the open files limit.
+const MAX_MERGED_RUNS: usize = 256;
+
+/// Find every middle of `cfg` by meeting in the middle like [`search`], spilling both halves
+/// to disk so that neither has to fit in memory.
+///
+/// The hashes of `prefix|front` for every front half, and the hashes left after undoing every
+/// back half from each target, are sorted in runs of `spill.run_len` entries written to
+/// `spill.dir`. The runs of each half are then merged into two sorted streams, which are
+/// joined on their hashes. This trades memory for about 32 bytes of disk written and read
+/// twice per combination of each half, or more when there are more than [`MAX_MERGED_RUNS`]
+/// runs.
+///
+/// If `deadline` passes, the search stops early and sets `stats.timed_out`.
+pub fn search_spilled(
+    cfg: &MitmConfig,
+    spill: &Spill,
+    deadline: Option<Instant>,
+    stats: &mut SearchStats,
+) -> io::Result<Vec<Vec<u8>>> {
+    let search = match cfg.hash.width {
+        HashWidth::Bits32 => search_spilled_typed::<u32>,
+        HashWidth::Bits64 => search_spilled_typed::<u64>,
+    };
+    search(cfg, spill, deadline, stats)
+}
+
+fn search_spilled_typed<H: HashWord>(
+    cfg: &MitmConfig,
+    spill: &Spill,
+    deadline: Option<Instant>,
+    stats: &mut SearchStats,
+) -> io::Result<Vec<Vec<u8>>> {
+    let fnv = cfg.hash.typed::<H>();
+    let halves = Halves::new(cfg, deadline);
+    let Halves { front, back, .. } = &halves;
+
+    let mut fronts = Runs::new(spill, "front");
+    let mut written = Ok(());
+    let extend = |hash, c| fnv.extend(hash, &[c]);
+    let mut index = 0;
+    let built = for_each_combination(front, fnv.hash(&cfg.prefix), extend, |hash, _| {
+        written = fronts.push((hash.to_u64(), index));
+        index += 1;
+        written.is_ok() && !halves.timed_out(stats)
+    });
+    written?;
+    if !built {
+        return Ok(Vec::new());
+    }
+
+    let mut targets = cfg.targets.clone();
+    targets.sort_unstable();
+    targets.dedup();
+
+    // the back is undone from its last character, so its first position changes fastest
+    let mut backs = Runs::new(spill, "back");
+    let reversed: Vec<_> = back.iter().rev().copied().collect();
+    let undo = undo(&fnv);
+    for target in targets {
+        let target = H::from_u64(target).expect("target doesn't fit the hash width");
+        let start = fnv.unextend(target, &cfg.suffix);
+        let mut written = Ok(());
+        let built = for_each_combination(&reversed, start, &undo, |hash, digits| {
+            let index = digits.iter().rev().zip(back).fold(0, |index, (&d, chars)| {
+                index * chars.len() as u64 + d as u64
+            });
+            written = backs.push((hash.to_u64(), index));
+            written.is_ok() && !halves.timed_out(stats)
+        });
+        written?;
+        if !built {
+            return Ok(Vec::new());
+        }
+    }
+
+    let mut fronts = fronts.merge()?;
+    let mut backs = backs.merge()?;
+    let mut matches = Vec::new();
+    // indices of the fronts of the hash of the last back
+    let mut group = (None, Vec::new());
+    let mut next_front = fronts.next()?;
+    while let Some((hash, back_index)) = backs.next()? {
+        if halves.timed_out(stats) {
+            break;
+        }
+        if group.0 != Some(hash) {
+            group.1.clear();
+            while let Some((front_hash, front_index)) = next_front
+                && front_hash <= hash
+            {
+                if front_hash == hash {
+                    group.1.push(front_index);
+                }
+                next_front = fronts.next()?;
+            }
+            group.0 = Some(hash);
+        }
+        for &front_index in &group.1 {
+            let mut m = decode(front, front_index);
+            m.extend_from_slice(&decode(back, back_index));
+            matches.push(m);
+        }
+    }
+
+    Ok(matches)
+}
+
+/// Call `f` with the hash of every combination of `positions` and its digits, where the hash
+/// of each combination is `start` stepped through its characters in order, until `f` returns
+/// false. The last position changes fastest, so that only the steps from the first digit that
+/// changed are redone.
+///
+/// Returns false if `f` stopped the enumeration.
+fn for_each_combination<H: Copy>(
+    positions: &[&[u8]],
+    start: H,
+    step: impl Fn(H, u8) -> H,
+    mut f: impl FnMut(H, &[usize]) -> bool,
+) -> bool {
+    let n = positions.len();
+    // hashes[i] is `start` stepped through the first i characters of the current combination
+    let mut hashes = vec![start; n + 1];
+    let mut digits = vec![0; n];
+    let mut stale = 0;
+    loop {
+        for i in stale..n {
+            hashes[i + 1] = step(hashes[i], positions[i][digits[i]]);
+        }
+        if !f(hashes[n], &digits) {
+            return false;
+        }
+
+        stale = n;
+        loop {
+            if stale == 0 {
+                return true;
+            }
+            stale -= 1;
+            digits[stale] += 1;
+            if digits[stale] < positions[stale].len() {
+                break;
+            }
+            digits[stale] = 0;
+        }
+    }
+}
+
+/// Function undoing the last character of a hash.
+fn undo<H: HashWord>(fnv: &Fnv<H>) -> impl Fn(H, u8) -> H {
+    let (variant, prime_inv) = (fnv.variant, fnv.prime.minv());
+    move |hash, c| {
+        let c = H::from_u32(c as u32);
+        match variant {
+            HashVariant::MulAdd => hash.wrapping_sub(c).wrapping_mul(prime_inv),
+            HashVariant::Fnv1a => hash.wrapping_mul(prime_inv).xor(c),
+        }
+    }
+}
+
+/// Characters of the half of the given index, whose first position is the most significant
+/// digit.
+fn decode(positions: &[&[u8]], mut index: u64) -> Vec<u8> {
+    let mut chars = vec![0; positions.len()];
+    for (c, position) in chars.iter_mut().zip(positions).rev() {
+        *c = position[(index % position.len() as u64) as usize];
+        index /= position.len() as u64;
+    }
+    chars
+}
+
+/// Sorted runs of (hash, index) entries in files of a spill directory, which are deleted once
+/// dropped.
+struct Runs {
+    dir: PathBuf,
+    /// Start of the names of the run files, unique to the process and the table.
+    name: String,
+    run_len: usize,
+    pending: Vec<(u64, u64)>,
+    /// Runs that haven't been merged into others yet.
+    paths: Vec<PathBuf>,
+    /// Number of runs written so far, which numbers their files.
+    written: usize,
+}
+
+impl Runs {
+    fn new(spill: &Spill, table: &str) -> Self {
+        Self {
+            dir: spill.dir.clone(),
+            name: format!("fs-hardblast-{}-{table}", process::id()),
+            run_len: spill.run_len.max(1),
+            pending: Vec::new(),
+            paths: Vec::new(),
+            written: 0,
+        }
+    }
+
+    fn push(&mut self, entry: (u64, u64)) -> io::Result<()> {
+        self.pending.push(entry);
+        if self.pending.len() >= self.run_len {
+            self.flush()?;
+        }
+        Ok(())
+    }
+
+    /// Sort the pending entries and write them out as a run.
+    fn flush(&mut self) -> io::Result<()> {
+        if self.pending.is_empty() {
+            return Ok(());
+        }
+        self.pending.sort_unstable();
+        let mut pending = std::mem::take(&mut self.pending);
+        let result = self.write_run(pending.drain(..).map(Ok));
+        self.pending = pending;
+        result
+    }
+
+    /// Write the sorted `entries` out as a new run.
+    fn write_run(
+        &mut self,
+        entries: impl Iterator<Item = io::Result<(u64, u64)>>,
+    ) -> io::Result<()> {
+        let path = self.dir.join(format!("{}-{}.run", self.name, self.written));
+        self.written += 1;
+        // recorded first, so that it is deleted even if writing it fails
+        self.paths.push(path.clone());
+        let mut out = BufWriter::new(File::create(&path)?);
+        for entry in entries {
+            let (hash, index) = entry?;
+            out.write_all(&hash.to_le_bytes())?;
+            out.write_all(&index.to_le_bytes())?;
+        }
+        out.flush()
+    }
+
+    /// Merge the runs into a single sorted stream of entries, first merging them into fewer
+    /// runs if there are more than [`MAX_MERGED_RUNS`]. Each batch of runs is deleted as soon
+    /// as it is merged, so that at most one extra copy of a batch is on disk at once.
+    fn merge(mut self) -> io::Result<Merge> {
+        self.flush()?;
+        self.pending = Vec::new();
+        while self.paths.len() > MAX_MERGED_RUNS {
+            let batch: Vec<_> = self.paths.drain(..MAX_MERGED_RUNS).collect();
+            let merged = Merge::open(&batch).and_then(|mut merge| {
+                self.write_run(std::iter::from_fn(|| merge.next().transpose()))
+            });
+            // the batch was taken out of `paths`, so it has to be deleted here even on failure
+            for path in &batch {
+                let _ = fs::remove_file(path);
+            }
+            merged?;
+        }
+        let merge = Merge::open(&self.paths)?;
+        Ok(Merge {
+            _runs: Some(self),
+            ..merge
+        })
+    }
+}
+
+impl Drop for Runs {
+    fn drop(&mut self) {
+        for path in &self.paths {
+            let _ = fs::remove_file(path);
+        }
+    }
+}
+
+/// Sorted stream of the entries of several sorted runs.
+struct Merge {
+    readers: Vec<BufReader<File>>,
+    /// Next entry of each reader that has one, along with the reader's index.
+    heap: BinaryHeap<Reverse<((u64, u64), usize)>>,
+    /// Runs merged by the stream, kept until the stream is dropped so that they are deleted
+    /// with it.
+    _runs: Option<Runs>,
+}
+
+impl Merge {
+    fn open(paths: &[PathBuf]) -> io::Result<Self> {
+        let mut merge = Self {
+            readers: Vec::with_capacity(paths.len()),
+            heap: BinaryHeap::with_capacity(paths.len()),
+            _runs: None,
+        };
+        for (i, path) in paths.iter().enumerate() {
+            merge.readers.push(BufReader::new(File::open(path)?));
+            merge.refill(i)?;
+        }
+        Ok(merge)
+    }
+
+    /// Push the next entry of reader `i` to the heap, if it has one.
+    fn refill(&mut self, i: usize) -> io::Result<()> {
+        let mut entry = [0; ENTRY_SIZE];
+        match self.readers[i].read_exact(&mut entry) {
+            Ok(()) => {
+                let (hash, index) = entry.split_at(ENTRY_SIZE / 2);
+                let hash = u64::from_le_bytes(hash.try_into().unwrap());
+                let index = u64::from_le_bytes(index.try_into().unwrap());
+                self.heap.push(Reverse(((hash, index), i)));
+                Ok(())
+            }
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => Ok(()),
+            Err(e) => Err(e),
+        }
+    }
+
+    fn next(&mut self) -> io::Result<Option<(u64, u64)>> {
+        let Some(Reverse((entry, i))) = self.heap.pop() else {
+            return Ok(None);
+        };
+        self.refill(i)?;
+        Ok(Some(entry))
+    }
+}
@@ -0,0 +1,99 @@
+use std::{fmt, fs, path::Path};
+
+use crate::SEARCH;
+
+/// A prefix to search from, with the lengths and suffixes to search it with if they differ from
+/// the command line's.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Prefix {
+    pub prefix: Vec<u8>,
+    /// Minimum and maximum number of searched characters after the start character.
+    pub lengths: Option<(usize, usize)>,
+    pub suffixes: Option<Vec<Vec<u8>>>,
+}
+
+/// Formats the prefix as a line of a prefixes file.
+impl fmt::Display for Prefix {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", String::from_utf8_lossy(&self.prefix))?;
+        if let Some((min, max)) = self.lengths {
+            write!(f, " {min}-{max}")?;
+        }
+        if let Some(suffixes) = &self.suffixes {
+            let suffixes: Vec<_> = suffixes
+                .iter()
+                .map(|s| String::from_utf8_lossy(s))
+                .collect();
+            write!(f, " {}", suffixes.join(","))?;
+        }
+        Ok(())
+    }
+}
+
+/// Load a list of prefixes, one per line:
+///
+/// ```text
+/// # prefix [max len | min len-max len] [suffix,...]
+/// /chr/c
+/// /sfx/ 3-7 .ffxbnd.dcx
+/// /map/m10_00_00_00/ 5 .msb.dcx,.btl.dcx
+/// ```
+///
+/// Fields are separated by whitespace, so prefixes and suffixes can't contain any. Lengths count the
+/// searched characters after the start character, like `--max-len`. Blank lines and lines
+/// starting with `#` are skipped.
+pub fn load_prefixes(path: &Path) -> Result<Vec<Prefix>, String> {
+    let text = fs::read_to_string(path).map_err(|e| e.to_string())?;
+    let prefixes: Vec<_> = text
+        .lines()
+        .enumerate()
+        .map(|(i, line)| (i, line.trim()))
+        .filter(|(_, line)| !line.is_empty() && !line.starts_with('#'))
+        .map(|(i, line)| parse_line(line).map_err(|e| format!("line {}: {e}", i + 1)))
+        .collect::<Result<_, _>>()?;
+    if prefixes.is_empty() {
+        return Err("no prefixes".into());
+    }
+    Ok(prefixes)
+}
+
+fn parse_line(line: &str) -> Result<Prefix, String> {
+    let mut fields = line.split_ascii_whitespace();
+    let prefix = fields.next().unwrap().as_bytes().to_owned();
+    let mut next = fields.next();
+
+    let lengths = match next.filter(|f| f.starts_with(|c: char| c.is_ascii_digit())) {
+        Some(field) => {
+            next = fields.next();
+            Some(parse_lengths(field)?)
+        }
+        None => None,
+    };
+    let suffixes = next.map(|s| s.split(',').map(|s| s.as_bytes().to_owned()).collect());
+    if let Some(field) = fields.next() {
+        return Err(format!("unexpected `{field}`"));
+    }
+
+    Ok(Prefix {
+        prefix,
+        lengths,
+        suffixes,
+    })
+}
+
+/// Parse `max` or `min-max`.
+fn parse_lengths(s: &str) -> Result<(usize, usize), String> {
+    let parse = |s: &str| match s.parse() {
+        Ok(len @ 0..=SEARCH) => Ok(len),
+        Ok(_) => Err(format!("length must be at most {SEARCH}")),
+        Err(e) => Err(format!("invalid length `{s}`: {e}")),
+    };
+    let (min, max) = match s.split_once('-') {
+        Some((min, max)) => (parse(min)?, parse(max)?),
+        None => (0, parse(s)?),
+    };
+    if min > max {
+        return Err(format!("invalid lengths `{s}`"));
+    }
+    Ok((min, max))
+}
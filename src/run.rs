@@ -0,0 +1,1091 @@
+use std::{
+    cell::RefCell,
+    collections::{BTreeMap, HashMap, HashSet},
+    env, fs,
+    io::{self, IsTerminal},
+    mem,
+    ops::ControlFlow,
+    process::exit,
+    rc::Rc,
+    slice,
+    time::{Duration, Instant},
+};
+
+use fs_hardblast_core::{
+    HashFlavor, HashWidth,
+    backend::{Backend, MitmConfig, SearchConfig},
+    contains::Substring,
+    filter::{self, CharRules, MatchFilter},
+    grammar::Grammar,
+    mask::{Mask as NameMask, Slot},
+    ngram::NgramModel,
+    output::print_match,
+    progress::{Progress, ProgressReporter},
+    report::{Candidate, Report, TargetReport, TopCandidates},
+    status::{self, TargetStatus},
+    template::{Fields, OutputTemplate},
+    usage,
+};
+
+use crate::{
+    Args, BackendKind, CpuBackend, REPORT_CANDIDATES, RunProgress, SearchStats, Seed, advice,
+    beam::Beam,
+    combinator::Combinator,
+    emit_all, estimate, gpu_backend, grammar,
+    journal::{self, Journal},
+    load_seeds, mask, mitm,
+    prefixes::Prefix,
+    sample::Reservoir,
+    search_chunk, search_mitm, tokens,
+    trace::{self, Trace},
+};
+
+/// What a run searches between its prefixes and suffixes. Each mode excludes the others.
+pub enum Mode<'a> {
+    /// The placeholders of a mask, between its fixed prefix and suffix which replace those of
+    /// the run.
+    Mask {
+        prefix: Vec<u8>,
+        slots: Vec<Slot>,
+        suffix: Vec<u8>,
+    },
+    /// Sequences of tokens, optionally followed by free characters.
+    Tokens(Vec<Vec<u8>>),
+    /// Words from two wordlists, with a separator in between.
+    Combine(Combinator),
+    /// Names described by a grammar.
+    Grammar(Grammar),
+    /// Names an n-gram model finds the most plausible.
+    Beam(Box<Beam<'a>>),
+    /// Single characters after each start character, or after each seed if there are any.
+    Chars(Option<Vec<Seed>>),
+}
+
+impl<'a> Mode<'a> {
+    /// Load what `args` search, exiting if it can't be. `beam_model` is the n-gram model of a
+    /// beam search.
+    pub fn load(args: &'a Args, flavor: &HashFlavor, beam_model: Option<&'a NgramModel>) -> Self {
+        if let Some(mask) = &args.mask {
+            let mask = NameMask::parse(mask, &args.alphabet()).unwrap_or_else(|e| {
+                eprintln!("invalid mask: {e}");
+                exit(1);
+            });
+            let (prefix, slots, suffix) = mask.split();
+            if slots.is_empty() {
+                eprintln!("the mask has no placeholders to search");
+                exit(1);
+            }
+            return Mode::Mask {
+                prefix,
+                slots: slots.to_vec(),
+                suffix,
+            };
+        }
+
+        if let Some(path) = &args.tokens {
+            if args.free_chars.is_none()
+                && (args.alphabet.is_some() || args.final_alphabet.is_some())
+            {
+                eprintln!(
+                    "--alphabet and --final-alphabet only apply to --tokens with --free-chars"
+                );
+                exit(1);
+            }
+            let tokens = tokens::load_tokens(path).unwrap_or_else(|e| {
+                eprintln!("failed to load tokens from {}: {e}", path.display());
+                exit(1);
+            });
+            if tokens.is_empty() {
+                eprintln!("no tokens to search with in {}", path.display());
+                exit(1);
+            }
+            return Mode::Tokens(tokens);
+        }
+
+        if let Some(paths) = &args.combine {
+            let [left, right] = [&paths[0], &paths[1]].map(|path| {
+                let words = tokens::load_tokens(path).unwrap_or_else(|e| {
+                    eprintln!("failed to load words from {}: {e}", path.display());
+                    exit(1);
+                });
+                if words.is_empty() {
+                    eprintln!("no words to combine in {}", path.display());
+                    exit(1);
+                }
+                words
+            });
+            let separators = args.separators.iter().map(|s| s.as_bytes().to_owned());
+            return Mode::Combine(Combinator::new(left, separators.collect(), &right));
+        }
+
+        if let Some(path) = &args.grammar {
+            let grammar = fs::read_to_string(path)
+                .map_err(|e| e.to_string())
+                .and_then(|s| s.parse::<Grammar>())
+                .unwrap_or_else(|e| {
+                    eprintln!("failed to load grammar from {}: {e}", path.display());
+                    exit(1);
+                });
+            return Mode::Grammar(grammar);
+        }
+
+        if let Some(model) = beam_model {
+            return Mode::Beam(Box::new(Beam {
+                model,
+                width: args.beam.unwrap() as usize,
+                max_len: args.beam_len,
+                start: args.start.as_bytes(),
+                alphabet: args.alphabet(),
+                final_alphabet: args.final_alphabet(),
+            }));
+        }
+
+        let seeds = args.seeds.as_ref().map(|path| {
+            load_seeds(path, args.prefix.as_bytes(), args.max_len, flavor).unwrap_or_else(|e| {
+                eprintln!("failed to load seeds from {}: {e}", path.display());
+                exit(1);
+            })
+        });
+        Mode::Chars(seeds)
+    }
+}
+
+/// A chunk of a run, which is searched at once and recorded as a whole.
+struct Chunk<'a> {
+    prefix: &'a [u8],
+    /// Suffixes searched together, whose matches are reported with the suffixes they match
+    /// with.
+    suffixes: &'a [Vec<u8>],
+    /// Minimum and maximum number of searched characters, or `None` to record the lengths of
+    /// the matches instead.
+    lengths: Option<(usize, usize)>,
+    /// Number of characters at the end of the prefix which are part of the search, like the
+    /// start character.
+    searched_prefix: usize,
+}
+
+/// The backends chunks are searched on, along with the configuration of regular searches.
+struct Backends {
+    cpu: CpuBackend,
+    gpu: Option<Box<dyn Backend>>,
+    cfg: SearchConfig,
+    /// Whether searches the GPU doesn't support fall back to the CPU.
+    fallback: bool,
+}
+
+impl Backends {
+    /// Search `cfg` with each of `suffixes`, exiting if the search fails.
+    fn search(&mut self, suffixes: &[Vec<u8>]) -> Vec<Vec<u8>> {
+        search_chunk(
+            &mut self.cpu,
+            &mut self.gpu,
+            &self.cfg,
+            suffixes,
+            self.fallback,
+        )
+        .unwrap_or_else(|e| {
+            eprintln!("search failed: {e}");
+            exit(1);
+        })
+    }
+}
+
+/// Records the chunks of a run in its progress, journal and trace.
+struct Recorder {
+    progress: Option<Rc<RefCell<RunProgress>>>,
+    journal: Option<Journal>,
+    trace: Option<Trace>,
+    /// Number of chunks the run is split in.
+    total_chunks: usize,
+    chunks_done: usize,
+    matches_found: usize,
+}
+
+impl Recorder {
+    /// Fraction of the chunks of the run that are done.
+    fn fraction(&self) -> f64 {
+        self.chunks_done as f64 / self.total_chunks.max(1) as f64
+    }
+
+    /// Record the chunk of `entry`, along with the partial strings sampled in `stats` during it.
+    fn record(&mut self, entry: &journal::Entry, stats: &mut SearchStats) {
+        let sampled_paths = mem::take(&mut stats.sampled_paths);
+        self.chunks_done += 1;
+        self.matches_found += entry.matches;
+        if let Some(progress) = &self.progress {
+            let mut progress = progress.borrow_mut();
+            progress.fraction = self.fraction();
+            progress.matches = self.matches_found;
+            progress.report(stats.nodes);
+        }
+        if let Some(journal) = &mut self.journal
+            && let Err(e) = journal.record(entry)
+        {
+            eprintln!("failed to write to journal: {e}");
+        }
+        if let Some(trace) = &mut self.trace {
+            let chunk = trace::Chunk {
+                prefix: entry.prefix,
+                suffix: entry.suffix,
+                min_len: entry.min_len,
+                max_len: entry.max_len,
+                duration: entry.duration,
+                matches: entry.matches,
+                nodes: entry.nodes,
+                stack_high_water: stats.stack_high_water,
+            };
+            let traced = trace.chunk(&chunk).and_then(|()| {
+                sampled_paths
+                    .iter()
+                    .try_for_each(|p| trace.path(entry.prefix, &p.bytes()[..p.len]))
+            });
+            if let Err(e) = traced {
+                eprintln!("failed to write to trace: {e}");
+            }
+        }
+    }
+}
+
+/// Filters the matches of a run, then counts and prints those it accepts.
+struct Reporter<'a> {
+    args: &'a Args,
+    flavor: HashFlavor,
+    contains: Vec<Substring>,
+    rules: CharRules,
+    filter: MatchFilter,
+    template: OutputTemplate,
+    escape: bool,
+    target_matches: HashMap<u64, usize>,
+    /// Number of matches of each length.
+    length_counts: BTreeMap<usize, usize>,
+    reservoir: Option<Reservoir<Vec<u8>>>,
+    tops: Option<HashMap<u64, TopCandidates>>,
+    /// Prefix, suffix and middle of the matches reported so far, when matches are only reported
+    /// the first time they are found.
+    reported: Option<HashSet<Vec<u8>>>,
+    line: Vec<u8>,
+}
+
+impl Reporter<'_> {
+    /// Whether `prefix|middle|suffix` hashes to a target.
+    fn is_match(&self, prefix: &[u8], middle: &[u8], suffix: &[u8]) -> bool {
+        let hash = self.flavor.hash(&[prefix, middle, suffix].concat());
+        self.target_matches.contains_key(&hash)
+    }
+
+    /// Report `match_bytes` found between `prefix` and `suffix`, unless it is filtered out.
+    ///
+    /// `len` is the number of characters after the prefix the search started from, including
+    /// the start character, for the length histogram and the constraints on searched characters.
+    fn report(&mut self, prefix: &[u8], suffix: &[u8], match_bytes: &[u8], len: usize) {
+        if let Some(reported) = &mut self.reported
+            && !reported.insert([prefix, suffix, match_bytes].concat())
+        {
+            return;
+        }
+
+        let mut collision = prefix.to_owned();
+        collision.extend_from_slice(match_bytes);
+        collision.extend_from_slice(suffix);
+
+        // for validation purposes
+        let hash = self.flavor.hash(&collision);
+        let Some(matches) = self.target_matches.get_mut(&hash) else {
+            panic!(
+                "match {} has hash {hash:0width$x}, which isn't a target",
+                collision.escape_ascii(),
+                width = self.flavor.width.hex_digits()
+            );
+        };
+
+        let name = &collision[..prefix.len() + match_bytes.len()];
+        let searched = &name[name.len() - len..];
+        if !self.filter.accepts(match_bytes)
+            || self
+                .args
+                .regex
+                .as_ref()
+                .is_some_and(|r| !r.matches(searched))
+            || !self.contains.iter().all(|s| s.is_in(searched))
+            || !self.rules.accepts(searched)
+        {
+            return;
+        }
+        *matches += 1;
+        *self.length_counts.entry(len).or_insert(0) += 1;
+        if let Some(tops) = &mut self.tops {
+            tops.get_mut(&hash).unwrap().push(Candidate {
+                name: collision.clone(),
+                score: filter::entropy(match_bytes),
+            });
+        }
+
+        let fields = Fields {
+            prefix,
+            middle: match_bytes,
+            suffix,
+            hash,
+            hash_width: self.flavor.width,
+        };
+        self.line.clear();
+        self.template.render(&fields, &mut self.line);
+        match &mut self.reservoir {
+            Some(reservoir) => reservoir.push(self.line.clone()),
+            None => print_match(&self.line, self.escape),
+        }
+    }
+}
+
+/// A search over every prefix and suffix of the command line, split in chunks which are
+/// searched in turn.
+pub struct Run<'a> {
+    args: &'a Args,
+    mode: &'a Mode<'a>,
+    flavor: HashFlavor,
+    targets: &'a [u64],
+    /// Names left out of `--emit-all`.
+    known: &'a HashSet<Vec<u8>>,
+    prefixes: &'a [Prefix],
+    /// Suffixes of the prefixes which don't have their own.
+    default_suffixes: Vec<Vec<u8>>,
+    /// Lengths of the prefixes which don't have their own.
+    default_lengths: (usize, usize),
+    /// Maximum number of searched characters over every prefix.
+    max_len: usize,
+    start: Instant,
+    deadline: Option<Instant>,
+    backends: Backends,
+    recorder: Recorder,
+    reporter: Reporter<'a>,
+}
+
+impl<'a> Run<'a> {
+    /// Set up a run of `mode` started at `start`, with the n-gram model pruning the CPU search
+    /// if there is one. If `--estimate` is given, the run is estimated instead and the process
+    /// exits.
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        args: &'a Args,
+        mode: &'a Mode<'a>,
+        flavor: HashFlavor,
+        start: Instant,
+        targets: &'a [u64],
+        known: &'a HashSet<Vec<u8>>,
+        prefixes: &'a [Prefix],
+        ngram: Option<NgramModel>,
+    ) -> Self {
+        let template = args.output_template.clone().unwrap_or_else(|| {
+            let default = match args.emit_all {
+                true => "{prefix}{middle}{suffix},{hash:x}",
+                false => "{prefix}{middle}{suffix}",
+            };
+            default.parse().unwrap()
+        });
+        let contains: Vec<_> = args
+            .contains
+            .iter()
+            .map(|s| Substring::new(s.as_bytes()))
+            .collect();
+        let rules = CharRules {
+            max_digit_run: args.max_digit_run,
+            max_repeat: args.max_repeat,
+            no_leading: args.no_leading.clone().unwrap_or_default().into_bytes(),
+            consistent_case: args.consistent_case,
+        };
+        let reporter = Reporter {
+            args,
+            flavor,
+            contains: contains.clone(),
+            rules: rules.clone(),
+            filter: MatchFilter {
+                min_distinct: args.min_distinct,
+                min_entropy: args.min_entropy,
+            },
+            template,
+            escape: !args.raw && io::stdout().is_terminal(),
+            target_matches: targets.iter().map(|&t| (t, 0)).collect(),
+            length_counts: BTreeMap::new(),
+            reservoir: args.sample_results.map(Reservoir::new),
+            tops: args.html.as_ref().map(|_| {
+                targets
+                    .iter()
+                    .map(|&t| (t, TopCandidates::new(REPORT_CANDIDATES)))
+                    .collect()
+            }),
+            reported: None,
+            line: Vec::new(),
+        };
+
+        let default_suffixes = match args.suffixes.as_slice() {
+            [] => vec![args.suffix.as_bytes().to_owned()],
+            suffixes => suffixes.iter().map(|s| s.as_bytes().to_owned()).collect(),
+        };
+        // seeds include the start character, so they get one more character to work with
+        let default_lengths = (
+            0,
+            args.max_len + matches!(mode, Mode::Chars(Some(_))) as usize,
+        );
+        let max_len = prefixes
+            .iter()
+            .map(|p| p.lengths.unwrap_or(default_lengths).1)
+            .max()
+            .unwrap();
+
+        let progress = args.progress.map(|interval| {
+            let print = |p: &Progress| {
+                eprintln!(
+                    "progress: {:.1}% of chunks, {} DFS nodes ({:.3e}/s), {} matches, {:?}",
+                    100.0 * p.fraction,
+                    p.nodes,
+                    p.throughput,
+                    p.matches,
+                    p.elapsed
+                )
+            };
+            Rc::new(RefCell::new(RunProgress {
+                reporter: ProgressReporter::new(print, interval),
+                fraction: 0.0,
+                matches: 0,
+            }))
+        });
+
+        let deadline = args.time_limit.map(|limit| start + limit);
+        let cpu = CpuBackend {
+            min_distinct: args.min_distinct,
+            deadline,
+            path_sample: args.trace.as_ref().map_or(0, |_| args.trace_paths),
+            ngram: ngram.map(|model| (model, args.ngram_threshold)),
+            regex: args.regex.clone(),
+            contains,
+            rules,
+            segment_start: 0,
+            mitm_spill: args.mitm_spill.clone().map(|dir| mitm::Spill {
+                dir,
+                run_len: (args.mitm_memory << 20) as usize / size_of::<(u64, u64)>(),
+            }),
+            mitm_cache: args.mitm_cache.clone(),
+            node_lanes: args.node_lanes,
+            stats: SearchStats {
+                progress: progress.clone(),
+                ..SearchStats::default()
+            },
+        };
+        let cfg = SearchConfig {
+            prefix: Vec::new(),
+            suffix: Vec::new(),
+            targets: targets.to_vec(),
+            hash: flavor,
+            alphabet: args.alphabet(),
+            final_alphabet: args.final_alphabet(),
+            min_len: 0,
+            max_len,
+        };
+
+        let mut run = Self {
+            args,
+            mode,
+            flavor,
+            targets,
+            known,
+            prefixes,
+            default_suffixes,
+            default_lengths,
+            max_len,
+            start,
+            deadline,
+            backends: Backends {
+                cpu,
+                gpu: None,
+                cfg,
+                fallback: args.backend == BackendKind::Auto,
+            },
+            recorder: Recorder {
+                progress,
+                journal: None,
+                trace: None,
+                total_chunks: 0,
+                chunks_done: 0,
+                matches_found: 0,
+            },
+            reporter,
+        };
+        run.recorder.total_chunks = run.total_chunks();
+
+        if let Some(samples) = args.estimate {
+            run.estimate(samples);
+        }
+
+        // the GPU kernel can't prune with an n-gram model
+        if run.backends.cpu.ngram.is_none() {
+            run.backends.gpu = gpu_backend(args.backend);
+        }
+        run.recorder.journal = args.journal.as_ref().map(|path| {
+            Journal::open(path).unwrap_or_else(|e| {
+                eprintln!("failed to open journal {}: {e}", path.display());
+                exit(1);
+            })
+        });
+        run.recorder.trace = args.trace.as_ref().map(|path| {
+            Trace::create(path, args.trace_chunks).unwrap_or_else(|e| {
+                eprintln!("failed to create trace {}: {e}", path.display());
+                exit(1);
+            })
+        });
+        run
+    }
+
+    /// Suffixes `entry` is searched with. Each tail is folded into the suffixes, so the
+    /// character right before it is still solved for.
+    fn suffixes_of(&self, entry: &Prefix) -> Vec<Vec<u8>> {
+        let suffixes = entry.suffixes.as_ref().unwrap_or(&self.default_suffixes);
+        match self.args.tail.as_slice() {
+            [] => suffixes.clone(),
+            tails => tails
+                .iter()
+                .flat_map(|t| suffixes.iter().map(|s| [t.as_bytes(), s].concat()))
+                .collect(),
+        }
+    }
+
+    /// Minimum and maximum lengths of the passes of a character search, which are searched one
+    /// after the other.
+    fn length_passes(&self) -> Vec<(usize, usize)> {
+        match self.args.shortest_first {
+            true => (0..=self.max_len).map(|len| (len, len)).collect(),
+            false => vec![(0, self.max_len)],
+        }
+    }
+
+    /// Number of chunks the run is split in, for reporting progress.
+    fn total_chunks(&self) -> usize {
+        let suffixes = || -> usize {
+            self.prefixes
+                .iter()
+                .map(|entry| self.suffixes_of(entry).len())
+                .sum()
+        };
+        match self.mode {
+            Mode::Mask { .. } => 1,
+            // tokens are searched once per number of tokens when shortest first
+            Mode::Tokens(_) if self.args.shortest_first => suffixes() * self.args.max_tokens,
+            Mode::Tokens(_) | Mode::Combine(_) | Mode::Grammar(_) | Mode::Beam(_) => suffixes(),
+            Mode::Chars(seeds) => {
+                let starts = match seeds {
+                    Some(_) => 1,
+                    None => self.args.start.len(),
+                };
+                let overlapping = |&(pass_min, pass_max): &(usize, usize)| {
+                    self.prefixes
+                        .iter()
+                        .map(|entry| entry.lengths.unwrap_or(self.default_lengths))
+                        .filter(|&(min, max)| pass_min.max(min) <= pass_max.min(max))
+                        .count()
+                };
+                self.length_passes().iter().map(overlapping).sum::<usize>() * starts
+            }
+        }
+    }
+
+    /// Print an estimate of the matches and duration of the run instead of searching it.
+    fn estimate(&mut self, samples: usize) -> ! {
+        let entries: Vec<_> = self
+            .prefixes
+            .iter()
+            .map(|entry| estimate::Entry {
+                prefix: &entry.prefix,
+                suffixes: self.suffixes_of(entry),
+                lengths: entry.lengths.unwrap_or(self.default_lengths),
+            })
+            .collect();
+        let Backends { cpu, cfg, .. } = &mut self.backends;
+        match self.mode {
+            Mode::Mask {
+                prefix,
+                slots,
+                suffix,
+            } => estimate::run_mask(prefix, slots, suffix, cfg, samples),
+            _ => estimate::run_chars(cpu, cfg, &entries, self.args.start.as_bytes(), samples),
+        }
+    }
+
+    /// Search every chunk of the run in turn, until the run times out.
+    pub fn search(&mut self) {
+        let (targets, flavor, deadline) = (self.targets, self.flavor, self.deadline);
+        match self.mode {
+            Mode::Mask {
+                prefix,
+                slots,
+                suffix,
+            } => self.search_mask(prefix, slots, suffix),
+            Mode::Tokens(tokens) => self.search_tokens(tokens),
+            Mode::Combine(combinator) => self.each_suffix(None, |backends, prefix, suffix| {
+                let stats = &mut backends.cpu.stats;
+                combinator.search(prefix, suffix, targets, &flavor, deadline, stats)
+            }),
+            Mode::Grammar(grammar) => self.each_suffix(None, |backends, prefix, suffix| {
+                let stats = &mut backends.cpu.stats;
+                grammar::search(prefix, grammar, suffix, targets, &flavor, deadline, stats)
+            }),
+            Mode::Beam(beam) => self.each_suffix(None, |backends, prefix, suffix| {
+                let stats = &mut backends.cpu.stats;
+                beam.search(prefix, suffix, targets, &flavor, deadline, stats)
+            }),
+            Mode::Chars(seeds) => self.search_chars(seeds.as_deref()),
+        }
+    }
+
+    /// Search `chunk` with `search`, then record it and report its matches. Returns whether the
+    /// run timed out.
+    fn chunk(&mut self, chunk: Chunk, search: impl FnOnce(&mut Backends) -> Vec<Vec<u8>>) -> bool {
+        let (start, nodes_before) = (Instant::now(), self.backends.cpu.stats.nodes);
+        let matches = search(&mut self.backends);
+
+        let (min_len, max_len) = chunk.lengths.unwrap_or_else(|| {
+            let lengths = matches.iter().map(Vec::len);
+            (
+                lengths.clone().min().unwrap_or(0),
+                lengths.max().unwrap_or(0),
+            )
+        });
+        let stats = &mut self.backends.cpu.stats;
+        let entry = journal::Entry {
+            prefix: chunk.prefix,
+            suffix: &chunk.suffixes.join(&b','),
+            min_len,
+            max_len,
+            duration: start.elapsed(),
+            matches: matches.len(),
+            nodes: stats.nodes - nodes_before,
+            timed_out: stats.timed_out,
+        };
+        self.recorder.record(&entry, stats);
+
+        for m in &matches {
+            for suffix in chunk.suffixes {
+                if self.reporter.is_match(chunk.prefix, m, suffix) {
+                    let len = m.len() + chunk.searched_prefix;
+                    self.reporter.report(chunk.prefix, suffix, m, len);
+                }
+            }
+        }
+        self.backends.cpu.stats.timed_out
+    }
+
+    /// Search each suffix of each prefix in a chunk of its own with `search`, which is given the
+    /// prefix and suffix, until the run times out. The chunks are recorded with `lengths`, or
+    /// the lengths of their matches if `None`.
+    fn each_suffix(
+        &mut self,
+        lengths: Option<(usize, usize)>,
+        mut search: impl FnMut(&mut Backends, &[u8], &[u8]) -> Vec<Vec<u8>>,
+    ) {
+        for entry in self.prefixes {
+            self.backends.cpu.segment_start = entry.prefix.len();
+            for suffix in &self.suffixes_of(entry) {
+                let chunk = Chunk {
+                    prefix: &entry.prefix,
+                    suffixes: slice::from_ref(suffix),
+                    lengths,
+                    searched_prefix: 0,
+                };
+                if self.chunk(chunk, |backends| search(backends, &entry.prefix, suffix)) {
+                    return;
+                }
+            }
+        }
+    }
+
+    /// Search the placeholders of a mask in a single chunk.
+    fn search_mask(&mut self, prefix: &[u8], slots: &[Slot], suffix: &[u8]) {
+        let (args, targets, flavor, deadline) =
+            (self.args, self.targets, self.flavor, self.deadline);
+        let suffixes = [suffix.to_owned()];
+        let chunk = Chunk {
+            prefix,
+            suffixes: &suffixes,
+            lengths: Some((slots.len(), slots.len())),
+            searched_prefix: 0,
+        };
+        self.chunk(chunk, |backends| match args.mitm {
+            true => {
+                let cfg = MitmConfig {
+                    prefix: prefix.to_owned(),
+                    suffix: suffix.to_owned(),
+                    targets: targets.to_vec(),
+                    hash: flavor,
+                    positions: slots.iter().map(Slot::alphabet).collect(),
+                };
+                let cpu = &mut backends.cpu;
+                // spilled and cached tables are only searched on the CPU
+                let gpu = match cpu.mitm_spill.is_some() || cpu.mitm_cache.is_some() {
+                    true => &mut None,
+                    false => &mut backends.gpu,
+                };
+                search_mitm(cpu, gpu, &cfg, backends.fallback).unwrap_or_else(|e| {
+                    eprintln!("search failed: {e}");
+                    exit(1);
+                })
+            }
+            false => {
+                let stats = &mut backends.cpu.stats;
+                mask::search(prefix, slots, suffix, targets, &flavor, deadline, stats)
+            }
+        });
+    }
+
+    /// Search sequences of `tokens`, in a chunk per prefix and suffix, and per number of tokens
+    /// with `--shortest-first`.
+    fn search_tokens(&mut self, tokens: &[Vec<u8>]) {
+        let (args, targets, flavor, deadline) =
+            (self.args, self.targets, self.flavor, self.deadline);
+        let free_chars = args.free_chars.unwrap_or(0);
+        // like lengths with --shortest-first, each number of tokens is searched in a pass of its
+        // own
+        let token_passes = match args.shortest_first {
+            true => (1..=args.max_tokens).map(|n| n..=n).collect(),
+            false => vec![1..=args.max_tokens],
+        };
+        // names which several numbers of tokens spell are only reported by the first pass
+        self.reporter.reported = args.shortest_first.then(HashSet::new);
+
+        for counts in token_passes {
+            // lengths in characters, spanned by the token sequences and free characters
+            let lengths = (
+                tokens.iter().map(Vec::len).min().unwrap() * counts.start() + free_chars.min(1),
+                tokens.iter().map(Vec::len).max().unwrap() * counts.end() + free_chars,
+            );
+            self.each_suffix(Some(lengths), |backends, prefix, suffix| match free_chars {
+                0 => {
+                    let stats = &mut backends.cpu.stats;
+                    let counts = counts.clone();
+                    tokens::search(
+                        prefix, tokens, counts, suffix, targets, &flavor, deadline, stats,
+                    )
+                }
+                // each token sequence is the prefix of a regular search for the free characters
+                free_chars => {
+                    let suffixes = [suffix.to_owned()];
+                    backends.cfg.suffix = suffix.to_owned();
+                    (backends.cfg.min_len, backends.cfg.max_len) = (1, free_chars);
+
+                    let mut matches = Vec::new();
+                    tokens::for_each_sequence(tokens, counts.clone(), |seq| {
+                        let stats = &mut backends.cpu.stats;
+                        if deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+                            stats.timed_out = true;
+                        }
+                        if stats.timed_out {
+                            return ControlFlow::Break(());
+                        }
+                        backends.cfg.prefix = [prefix, seq].concat();
+                        let found = backends.search(&suffixes);
+                        matches.extend(found.iter().map(|m| [seq, m].concat()));
+                        ControlFlow::Continue(())
+                    });
+                    // token sequences and free characters may split a name in several ways
+                    matches.sort_unstable();
+                    matches.dedup();
+                    matches
+                }
+            });
+            if self.backends.cpu.stats.timed_out {
+                break;
+            }
+        }
+    }
+
+    /// Search characters one at a time, in a chunk per pass of lengths, prefix and start
+    /// character, or per pass and prefix from `seeds`. Every suffix is searched in the same
+    /// chunk.
+    fn search_chars(&mut self, seeds: Option<&[Seed]>) {
+        let (args, deadline) = (self.args, self.deadline);
+        'search: for (pass_min, pass_max) in self.length_passes() {
+            for entry in self.prefixes {
+                // each prefix only takes part in the passes overlapping its own lengths
+                let (entry_min, entry_max) = entry.lengths.unwrap_or(self.default_lengths);
+                let (min_len, max_len) = (pass_min.max(entry_min), pass_max.min(entry_max));
+                if min_len > max_len {
+                    continue;
+                }
+                let cfg = &mut self.backends.cfg;
+                (cfg.min_len, cfg.max_len) = (min_len, max_len);
+                self.backends.cpu.segment_start = entry.prefix.len();
+                let suffixes = self.suffixes_of(entry);
+
+                // seeded searches always run on the CPU, as they are specific to its DFS
+                if let Some(seeds) = seeds {
+                    // seeds start right after the prefix, so n-gram models are conditioned on it
+                    self.backends.cfg.prefix.clone_from(&entry.prefix);
+                    let chunk = Chunk {
+                        prefix: &entry.prefix,
+                        suffixes: &suffixes,
+                        lengths: Some((min_len, max_len)),
+                        searched_prefix: 0,
+                    };
+                    let search = |backends: &mut Backends| {
+                        let matches = backends.cpu.run(&backends.cfg, &suffixes, Some(seeds));
+                        matches
+                            .iter()
+                            .map(|m| m.bytes()[..m.len].to_vec())
+                            .collect()
+                    };
+                    if self.chunk(chunk, search) {
+                        break 'search;
+                    }
+                    continue;
+                }
+
+                for &start_char in args.start.as_bytes() {
+                    let prefix = [&entry.prefix[..], &[start_char]].concat();
+
+                    // the CPU backend checks the deadline as it goes, but others and `--emit-all`
+                    // can only be stopped in between chunks
+                    if deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+                        let stats = &mut self.backends.cpu.stats;
+                        stats.timed_out = true;
+                        // journal the chunk the search stopped at, which is where it would resume
+                        if !args.emit_all {
+                            let entry = journal::Entry {
+                                prefix: &prefix,
+                                suffix: &suffixes.join(&b','),
+                                min_len,
+                                max_len,
+                                duration: Duration::ZERO,
+                                matches: 0,
+                                nodes: 0,
+                                timed_out: true,
+                            };
+                            self.recorder.record(&entry, stats);
+                        }
+                        break 'search;
+                    }
+
+                    if args.emit_all {
+                        self.emit_candidates(&prefix, &suffixes, max_len);
+                        continue;
+                    }
+
+                    let chunk = Chunk {
+                        prefix: &prefix,
+                        suffixes: &suffixes,
+                        lengths: Some((min_len, max_len)),
+                        searched_prefix: 1,
+                    };
+                    let search = |backends: &mut Backends| {
+                        backends.cfg.prefix.clone_from(&prefix);
+                        backends.search(&suffixes)
+                    };
+                    if self.chunk(chunk, search) {
+                        break 'search;
+                    }
+                }
+            }
+        }
+    }
+
+    /// Print every candidate between `prefix` and each of `suffixes` with at most `max_len`
+    /// characters, for `--emit-all`.
+    fn emit_candidates(&self, prefix: &[u8], suffixes: &[Vec<u8>], max_len: usize) {
+        let emit_all = match self.flavor.width {
+            HashWidth::Bits32 => emit_all::<u32>,
+            HashWidth::Bits64 => emit_all::<u64>,
+        };
+        for suffix in suffixes {
+            emit_all(
+                prefix,
+                suffix,
+                max_len,
+                &self.backends.cfg.alphabet,
+                &self.flavor,
+                &self.reporter.template,
+                self.known,
+                self.reporter.escape,
+            );
+        }
+    }
+
+    /// Print the outcome of the run, write its status file and report, and exit with its
+    /// status.
+    pub fn finish(mut self) -> ! {
+        let args = self.args;
+        let hash_width = self.flavor.width;
+        if let Some(reservoir) = self.reporter.reservoir.take() {
+            let seen = reservoir.seen();
+            let sampled = reservoir.into_items();
+            for line in &sampled {
+                print_match(line, self.reporter.escape);
+            }
+            println!("sampled {} of {seen} matches", sampled.len());
+        }
+
+        if let Some(progress) = &self.recorder.progress {
+            let (fraction, nodes) = (self.recorder.fraction(), self.backends.cpu.stats.nodes);
+            let mut progress = progress.borrow_mut();
+            progress
+                .reporter
+                .finish(fraction, nodes, self.recorder.matches_found);
+        }
+        status::print_length_histogram(&self.reporter.length_counts);
+        let elapsed = self.start.elapsed();
+        println!("{elapsed:?}");
+        if let Some(trace) = &mut self.recorder.trace
+            && let Err(e) = trace.flush()
+        {
+            eprintln!("failed to write to trace: {e}");
+        }
+        if let Some(journal) = &mut self.recorder.journal
+            && let Err(e) = journal.sync()
+        {
+            eprintln!("failed to write to journal: {e}");
+        }
+        let stats = &self.backends.cpu.stats;
+        let timed_out = stats.timed_out;
+        if timed_out {
+            println!(
+                "time limit reached after {} DFS nodes and {} of {} chunks, search is incomplete",
+                stats.nodes, self.recorder.chunks_done, self.recorder.total_chunks
+            );
+        }
+
+        let peak_rss = usage::peak_rss().map_or("unavailable".to_owned(), usage::fmt_bytes);
+        println!("peak memory usage: {peak_rss}");
+        println!(
+            "DFS stack high-water mark: {} entries ({})",
+            stats.stack_high_water,
+            usage::fmt_bytes(stats.stack_high_water_bytes(hash_width) as u64)
+        );
+
+        if args.emit_all {
+            match timed_out {
+                true => exit(status::EXIT_TIMED_OUT),
+                false => exit(status::EXIT_ALL_RESOLVED),
+            }
+        }
+
+        let statuses: Vec<_> = self
+            .targets
+            .iter()
+            .map(|&target| TargetStatus {
+                target,
+                matches: self.reporter.target_matches[&target],
+            })
+            .collect();
+        let unresolved: Vec<_> = statuses
+            .iter()
+            .filter(|s| !s.resolved())
+            .map(|s| s.target)
+            .collect();
+        if !unresolved.is_empty() {
+            let advice = advice::advise(args, timed_out);
+            advice::print_advice(
+                &advice,
+                &unresolved,
+                hash_width,
+                &env::args_os().collect::<Vec<_>>(),
+            );
+        }
+        if let Some(path) = &args.status_file
+            && let Err(e) = status::write_status_file(path, &statuses, hash_width)
+        {
+            eprintln!("failed to write status file {}: {e}", path.display());
+        }
+        if let Some(path) = &args.html {
+            let stats = vec![
+                ("search".into(), self.describe()),
+                ("time".into(), format!("{elapsed:?}")),
+                ("complete".into(), (!timed_out).to_string()),
+                (
+                    "DFS nodes".into(),
+                    self.backends.cpu.stats.nodes.to_string(),
+                ),
+                ("peak memory usage".into(), peak_rss),
+            ];
+            let report = self.html_report(&statuses, stats);
+            if let Err(e) = report.write_html(path) {
+                eprintln!("failed to write HTML report {}: {e}", path.display());
+            }
+        }
+        match timed_out {
+            true => exit(status::EXIT_TIMED_OUT),
+            false => exit(status::exit_code(&statuses)),
+        }
+    }
+
+    /// Description of what the run searched, for reports.
+    fn describe(&self) -> String {
+        let args = self.args;
+        let prefix = match &args.prefixes {
+            Some(path) => format!("[prefixes of {}]", path.display()),
+            None => args.prefix.clone(),
+        };
+        let suffix = match args.suffixes.as_slice() {
+            [] => args.suffix.clone(),
+            suffixes => format!("[{}]", suffixes.join("|")),
+        };
+        // counted like `--max-len`, without the extra character of seeds
+        let max_len = self
+            .prefixes
+            .iter()
+            .map(|p| p.lengths.map_or(args.max_len, |(_, max)| max))
+            .max()
+            .unwrap();
+        match (&args.mask, &args.tokens, &args.grammar) {
+            (Some(mask), ..) => mask.clone(),
+            (None, Some(path), _) => format!(
+                "{prefix}[tokens of {}]*{}{}, up to {} tokens{}",
+                path.display(),
+                args.free_chars.map_or("", |_| "[free]*"),
+                suffix,
+                args.max_tokens,
+                args.free_chars
+                    .map_or(String::new(), |n| format!(" and {n} free characters")),
+            ),
+            (None, None, Some(path)) => {
+                format!("{prefix}[grammar of {}]{suffix}", path.display())
+            }
+            (None, None, None) if args.combine.is_some() => format!(
+                "{prefix}[word][{}][word]{suffix}",
+                args.separators.join("|"),
+            ),
+            (None, None, None) => match args.beam {
+                Some(width) => format!(
+                    "{prefix}[{}]*{suffix}, up to {} characters, beam of {width}",
+                    args.start, args.beam_len
+                ),
+                None => format!(
+                    "{prefix}[{}]*{suffix}, up to {max_len} characters",
+                    args.start
+                ),
+            },
+        }
+    }
+
+    /// HTML report of the run, with the outcome of each target and the given statistics.
+    fn html_report(&mut self, statuses: &[TargetStatus], stats: Vec<(String, String)>) -> Report {
+        let hash_width = self.flavor.width;
+        let mut devices = vec![self.backends.cpu.device()];
+        devices.extend(self.backends.gpu.as_ref().map(|gpu| gpu.device()));
+        let mut tops = self.reporter.tops.take().unwrap_or_default();
+        Report {
+            title: match self.targets {
+                [target] => format!(
+                    "fs-hardblast report for {target:0width$x}",
+                    width = hash_width.hex_digits()
+                ),
+                targets => format!("fs-hardblast report for {} targets", targets.len()),
+            },
+            hash_width,
+            targets: statuses
+                .iter()
+                .map(|&status| TargetReport {
+                    status,
+                    candidates: tops
+                        .remove(&status.target)
+                        .map_or_else(Vec::new, TopCandidates::into_sorted),
+                })
+                .collect(),
+            devices,
+            stats,
+        }
+    }
+}
@@ -0,0 +1,46 @@
+use fs_hardblast_core::rng::SplitMix64;
+
+/// Uniform random sample of at most `capacity` items from a stream of unknown length.
+///
+/// Implements reservoir sampling ("algorithm R"), so memory use is bounded by the capacity
+/// no matter how many items are pushed, while [`Self::seen`] still counts all of them.
+#[derive(Debug, Clone)]
+pub struct Reservoir<T> {
+    items: Vec<T>,
+    capacity: usize,
+    seen: u64,
+    rng: SplitMix64,
+}
+
+impl<T> Reservoir<T> {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            items: Vec::with_capacity(capacity),
+            capacity,
+            seen: 0,
+            rng: SplitMix64::from_time(),
+        }
+    }
+
+    pub fn push(&mut self, item: T) {
+        self.seen += 1;
+        if self.items.len() < self.capacity {
+            self.items.push(item);
+            return;
+        }
+
+        let slot = self.rng.next_u64() % self.seen;
+        if let Some(sampled) = self.items.get_mut(slot as usize) {
+            *sampled = item;
+        }
+    }
+
+    /// Total number of items pushed, including the ones that weren't sampled.
+    pub fn seen(&self) -> u64 {
+        self.seen
+    }
+
+    pub fn into_items(self) -> Vec<T> {
+        self.items
+    }
+}
@@ -0,0 +1,315 @@
+use std::{
+    fs::{self, File},
+    io::{self, BufWriter, Read, Write},
+    ops::Deref,
+    path::{Path, PathBuf},
+    process,
+};
+
+/// First word of cached tables, written in native byte order so that tables written on a
+/// machine of the other endianness are rejected.
+const MAGIC: u64 = u64::from_be_bytes(*b"FSHBTBL1");
+
+/// Entry of a sorted table: a hash and the index of the half it is the hash of.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[repr(C)]
+pub struct Entry {
+    pub hash: u64,
+    pub index: u64,
+}
+
+/// Table of entries, either built in memory or loaded from a cache file.
+pub enum Table {
+    Owned(Vec<Entry>),
+    Mapped(Mapping),
+}
+
+impl Deref for Table {
+    type Target = [Entry];
+
+    fn deref(&self) -> &[Entry] {
+        match self {
+            Self::Owned(entries) => entries,
+            Self::Mapped(mapping) => mapping.entries(),
+        }
+    }
+}
+
+/// Load the table cached in `dir` under `key`, or build it with `build` and cache it.
+///
+/// A missing or invalid cache file is rebuilt, and failing to write it only prints a warning,
+/// since the cache only saves time. `build` returns `None` if it was interrupted, in which case
+/// nothing is cached.
+pub fn load_or_build(
+    dir: &Path,
+    key: &[u8],
+    build: impl FnOnce() -> Option<Vec<Entry>>,
+) -> Option<Table> {
+    let path = dir.join(format!("table-{:016x}.bin", fnv1a(key)));
+    match load(&path, key) {
+        Ok(Some(table)) => {
+            eprintln!(
+                "loaded {} table entries from {}",
+                table.len(),
+                path.display()
+            );
+            return Some(table);
+        }
+        Ok(None) => (),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => (),
+        Err(e) => eprintln!("failed to load table from {}: {e}", path.display()),
+    }
+
+    let entries = build()?;
+    match store(&path, key, &entries) {
+        Ok(()) => eprintln!(
+            "cached {} table entries to {}",
+            entries.len(),
+            path.display()
+        ),
+        Err(e) => eprintln!("failed to cache table to {}: {e}", path.display()),
+    }
+    Some(Table::Owned(entries))
+}
+
+/// Length of the header of a cache file for `key`, which keeps the entries aligned.
+fn header_len(key: &[u8]) -> usize {
+    3 * size_of::<u64>() + key.len().next_multiple_of(size_of::<u64>())
+}
+
+/// Load the table in `path` if it was cached under `key`.
+///
+/// Cache files hold the magic, the length of the key, the key padded to a multiple of 8
+/// bytes, the number of entries and the entries, all in native byte order.
+fn load(path: &Path, key: &[u8]) -> io::Result<Option<Table>> {
+    let mut file = File::open(path)?;
+    let mut header = vec![0; header_len(key)];
+    if file.read_exact(&mut header).is_err() {
+        return Ok(None);
+    }
+    let word = |i: usize| u64::from_ne_bytes(header[8 * i..8 * i + 8].try_into().unwrap());
+    let key_end = 16 + key.len();
+    if word(0) != MAGIC || word(1) != key.len() as u64 || &header[16..key_end] != key {
+        return Ok(None);
+    }
+    let count = word(header.len() / 8 - 1);
+    let expected_len = (count as u128) * size_of::<Entry>() as u128 + header.len() as u128;
+    if file.metadata()?.len() as u128 != expected_len {
+        return Ok(None);
+    }
+
+    Mapping::new(&file, header.len(), count as usize).map(|m| Some(Table::Mapped(m)))
+}
+
+/// Write `entries` to `path` under `key`, through a temporary file renamed into place so that
+/// concurrent runs never see a partial table and mapped tables are never modified.
+fn store(path: &Path, key: &[u8], entries: &[Entry]) -> io::Result<()> {
+    let tmp = PathBuf::from(format!("{}.{}.tmp", path.display(), process::id()));
+    let written = (|| {
+        let mut out = BufWriter::new(File::create(&tmp)?);
+        out.write_all(&MAGIC.to_ne_bytes())?;
+        out.write_all(&(key.len() as u64).to_ne_bytes())?;
+        out.write_all(key)?;
+        out.write_all(&vec![0; header_len(key) - 3 * 8 - key.len()])?;
+        out.write_all(&(entries.len() as u64).to_ne_bytes())?;
+        for entry in entries {
+            out.write_all(&entry.hash.to_ne_bytes())?;
+            out.write_all(&entry.index.to_ne_bytes())?;
+        }
+        out.flush()
+    })();
+    match written.and_then(|()| fs::rename(&tmp, path)) {
+        Ok(()) => Ok(()),
+        Err(e) => {
+            let _ = fs::remove_file(&tmp);
+            Err(e)
+        }
+    }
+}
+
+/// 64-bit FNV-1a hash of a cache key, naming its file.
+fn fnv1a(data: &[u8]) -> u64 {
+    data.iter().fold(0xcbf29ce484222325, |h, &b| {
+        (h ^ b as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+/// Entries of a cache file mapped into memory, so that only the pages searches touch are read.
+#[cfg(unix)]
+pub struct Mapping {
+    ptr: *mut libc::c_void,
+    len: usize,
+    offset: usize,
+    count: usize,
+}
+
+#[cfg(unix)]
+impl Mapping {
+    fn new(file: &File, offset: usize, count: usize) -> io::Result<Self> {
+        use std::os::fd::AsRawFd;
+
+        let len = offset + count * size_of::<Entry>();
+        // SAFETY: the file is mapped privately and read-only, and cache files are only ever
+        // replaced by a rename, never modified in place
+        let ptr = unsafe {
+            libc::mmap(
+                std::ptr::null_mut(),
+                len,
+                libc::PROT_READ,
+                libc::MAP_PRIVATE,
+                file.as_raw_fd(),
+                0,
+            )
+        };
+        if ptr == libc::MAP_FAILED {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(Self {
+            ptr,
+            len,
+            offset,
+            count,
+        })
+    }
+
+    fn entries(&self) -> &[Entry] {
+        // SAFETY: the mapping is page aligned and `offset` is a multiple of 8, and it holds
+        // `count` entries past it
+        unsafe { std::slice::from_raw_parts(self.ptr.byte_add(self.offset).cast(), self.count) }
+    }
+}
+
+#[cfg(unix)]
+impl Drop for Mapping {
+    fn drop(&mut self) {
+        // SAFETY: `ptr` and `len` are those of a live mapping, which no entries borrow anymore
+        unsafe { libc::munmap(self.ptr, self.len) };
+    }
+}
+
+/// Entries of a cache file read into memory, where mapping files isn't supported.
+#[cfg(not(unix))]
+pub struct Mapping(Vec<Entry>);
+
+#[cfg(not(unix))]
+impl Mapping {
+    fn new(mut file: &File, offset: usize, count: usize) -> io::Result<Self> {
+        use std::io::{Seek, SeekFrom};
+
+        file.seek(SeekFrom::Start(offset as u64))?;
+        let mut entries = Vec::with_capacity(count);
+        let mut buf = [0; size_of::<Entry>()];
+        for _ in 0..count {
+            file.read_exact(&mut buf)?;
+            let (hash, index) = buf.split_at(8);
+            entries.push(Entry {
+                hash: u64::from_ne_bytes(hash.try_into().unwrap()),
+                index: u64::from_ne_bytes(index.try_into().unwrap()),
+            });
+        }
+        Ok(Self(entries))
+    }
+
+    fn entries(&self) -> &[Entry] {
+        &self.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{env, fs::OpenOptions};
+
+    const KEY: &[u8] = b"fnv1a-32 abc 3";
+
+    /// Empty directory for the cache files of one test.
+    fn cache_dir(name: &str) -> PathBuf {
+        let dir = env::temp_dir().join(format!("fs-hardblast-{name}-{}", process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn entries() -> Vec<Entry> {
+        (0..100)
+            .map(|i| Entry {
+                hash: fnv1a(&[i as u8]),
+                index: i,
+            })
+            .collect()
+    }
+
+    /// Cache the entries in a new file of `dir`, returning its path.
+    fn stored(dir: &Path) -> PathBuf {
+        let path = dir.join("table.bin");
+        store(&path, KEY, &entries()).unwrap();
+        path
+    }
+
+    #[test]
+    fn stored_table_loads_back() {
+        let dir = cache_dir("round-trip");
+        let path = stored(&dir);
+        let table = load(&path, KEY).unwrap().unwrap();
+        assert!(matches!(table, Table::Mapped(_)));
+        assert_eq!(&*table, entries().as_slice());
+
+        // an empty table has nothing past its header to map
+        store(&path, KEY, &[]).unwrap();
+        assert!(load(&path, KEY).unwrap().unwrap().is_empty());
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn load_or_build_only_builds_once() {
+        let dir = cache_dir("load-or-build");
+        let built = load_or_build(&dir, KEY, || Some(entries())).unwrap();
+        assert!(matches!(built, Table::Owned(_)));
+        let loaded = load_or_build(&dir, KEY, || panic!("cached table was rebuilt")).unwrap();
+        assert_eq!(&*loaded, &*built);
+
+        assert!(load_or_build(&dir, b"other", || None).is_none());
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn mismatched_key_is_rejected() {
+        let dir = cache_dir("key");
+        let path = stored(&dir);
+        assert!(load(&path, b"fnv1a-32 abc 4").unwrap().is_none());
+        // same padded header length, different key length
+        assert!(load(&path, &KEY[..KEY.len() - 1]).unwrap().is_none());
+        assert!(
+            load(&path, b"longer key than was stored")
+                .unwrap()
+                .is_none()
+        );
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn wrong_magic_is_rejected() {
+        let dir = cache_dir("magic");
+        let path = stored(&dir);
+        let mut bytes = fs::read(&path).unwrap();
+        bytes[..8].copy_from_slice(&MAGIC.swap_bytes().to_ne_bytes());
+        fs::write(&path, bytes).unwrap();
+        assert!(load(&path, KEY).unwrap().is_none());
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn wrong_length_is_rejected() {
+        let dir = cache_dir("length");
+        let path = stored(&dir);
+        let len = fs::metadata(&path).unwrap().len();
+        let file = OpenOptions::new().write(true).open(&path).unwrap();
+        for bad_len in [len - 1, len - size_of::<Entry>() as u64, len + 1, 8, 0] {
+            file.set_len(bad_len).unwrap();
+            assert!(load(&path, KEY).unwrap().is_none(), "length {bad_len}");
+        }
+        file.set_len(len + size_of::<Entry>() as u64).unwrap();
+        assert!(load(&path, KEY).unwrap().is_none());
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
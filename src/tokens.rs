@@ -0,0 +1,169 @@
+use std::{
+    fs, io,
+    ops::{ControlFlow, RangeInclusive},
+    path::Path,
+    time::Instant,
+};
+
+use fs_hardblast_core::{HashFlavor, HashVariant, HashWidth, HashWord, PrecomputedSuffix};
+
+use crate::SearchStats;
+
+/// Path standing for the built-in wordlist of FromSoftware path tokens.
+pub const BUILTIN_WORDLIST: &str = "@fromsoft";
+
+#[cfg(feature = "builtin-wordlist")]
+const FROMSOFT_WORDS: &[u8] = include_bytes!("../wordlists/fromsoft.words");
+
+/// Load a wordlist of tokens, one per line, or the built-in one if `path` is
+/// [`BUILTIN_WORDLIST`]. Blank lines and duplicates are skipped.
+pub fn load_tokens(path: &Path) -> io::Result<Vec<Vec<u8>>> {
+    let text = match path == Path::new(BUILTIN_WORDLIST) {
+        true => builtin_wordlist()?.to_vec(),
+        false => fs::read(path)?,
+    };
+    let mut tokens: Vec<Vec<u8>> = text
+        .split(|&b| b == b'\n')
+        .map(|line| line.trim_ascii())
+        .filter(|line| !line.is_empty())
+        .map(<[u8]>::to_vec)
+        .collect();
+    tokens.sort_unstable();
+    tokens.dedup();
+    Ok(tokens)
+}
+
+#[cfg(feature = "builtin-wordlist")]
+fn builtin_wordlist() -> io::Result<&'static [u8]> {
+    Ok(FROMSOFT_WORDS)
+}
+
+#[cfg(not(feature = "builtin-wordlist"))]
+fn builtin_wordlist() -> io::Result<&'static [u8]> {
+    Err(io::Error::other(
+        "the built-in wordlist requires building with the `builtin-wordlist` feature",
+    ))
+}
+
+/// Call `f` with every string made of a number of tokens in `counts`, until it breaks. Strings
+/// that several token sequences spell are passed once per sequence.
+pub fn for_each_sequence(
+    tokens: &[Vec<u8>],
+    counts: RangeInclusive<usize>,
+    mut f: impl FnMut(&[u8]) -> ControlFlow<()>,
+) {
+    fn walk(
+        tokens: &[Vec<u8>],
+        depth: usize,
+        counts: &RangeInclusive<usize>,
+        seq: &mut Vec<u8>,
+        f: &mut impl FnMut(&[u8]) -> ControlFlow<()>,
+    ) -> ControlFlow<()> {
+        let len = seq.len();
+        for token in tokens {
+            seq.extend_from_slice(token);
+            if counts.contains(&depth) {
+                f(seq)?;
+            }
+            if depth < *counts.end() {
+                walk(tokens, depth + 1, counts, seq, f)?;
+            }
+            seq.truncate(len);
+        }
+        ControlFlow::Continue(())
+    }
+
+    let _ = walk(tokens, 1, &counts, &mut Vec::new(), &mut f);
+}
+
+/// Find every middle `m` made of a number of tokens in `counts`, such that
+///
+/// ```text
+/// flavor.hash(prefix|m|suffix) is in targets
+/// ```
+///
+/// Each step of the DFS hashes a whole token, so that long names made of known words can be
+/// reached. Each match is returned once, however many ways the tokens can spell it. If
+/// `deadline` passes, the search stops early and sets `stats.timed_out`.
+#[allow(clippy::too_many_arguments)]
+pub fn search(
+    prefix: &[u8],
+    tokens: &[Vec<u8>],
+    counts: RangeInclusive<usize>,
+    suffix: &[u8],
+    targets: &[u64],
+    flavor: &HashFlavor,
+    deadline: Option<Instant>,
+    stats: &mut SearchStats,
+) -> Vec<Vec<u8>> {
+    let search = match flavor.width {
+        HashWidth::Bits32 => search_typed::<u32>,
+        HashWidth::Bits64 => search_typed::<u64>,
+    };
+    search(
+        prefix, tokens, counts, suffix, targets, flavor, deadline, stats,
+    )
+}
+
+#[allow(clippy::too_many_arguments)]
+fn search_typed<H: HashWord>(
+    prefix: &[u8],
+    tokens: &[Vec<u8>],
+    counts: RangeInclusive<usize>,
+    suffix: &[u8],
+    targets: &[u64],
+    flavor: &HashFlavor,
+    deadline: Option<Instant>,
+    stats: &mut SearchStats,
+) -> Vec<Vec<u8>> {
+    let fnv = flavor.typed::<H>();
+
+    // hashes of prefix|m leading to each target. Solving for a last character isn't possible as
+    // tokens end with arbitrary bytes, but the target shift is one multiplication away from them
+    let mut ends: Vec<u64> = targets
+        .iter()
+        .map(|&t| {
+            let target = H::from_u64(t).expect("target doesn't fit the hash width");
+            let shift = PrecomputedSuffix::new(suffix, target, &fnv).target_shift;
+            match fnv.variant {
+                HashVariant::MulAdd => shift.to_u64(),
+                HashVariant::Fnv1a => shift.wrapping_mul(fnv.prime).to_u64(),
+            }
+        })
+        .collect();
+    ends.sort_unstable();
+    ends.dedup();
+
+    let mut matches = Vec::new();
+    let mut middle = Vec::new();
+
+    // (hash of prefix|middle, tokens in middle, middle length before the last token, last token)
+    let mut stack = vec![(fnv.hash(prefix), 0, 0, &[][..])];
+    while let Some((hash, depth, len, token)) = stack.pop() {
+        stats.stack_high_water = stats.stack_high_water.max(stack.len() + 1);
+        stats.nodes += 1;
+
+        if stats.check_deadline(deadline) {
+            break;
+        }
+
+        middle.truncate(len);
+        middle.extend_from_slice(token);
+
+        if depth > 0 && counts.contains(&depth) && ends.binary_search(&hash.to_u64()).is_ok() {
+            matches.push(middle.clone());
+        }
+        if depth >= *counts.end() {
+            continue;
+        }
+        let len = middle.len();
+        for token in tokens.iter().rev() {
+            stack.push((fnv.extend(hash, token), depth + 1, len, token));
+        }
+    }
+
+    // different token sequences may spell the same name
+    matches.sort_unstable();
+    matches.dedup();
+    matches
+}
@@ -0,0 +1,94 @@
+use std::{
+    fs::File,
+    io::{self, BufWriter, Write},
+    path::Path,
+    time::Duration,
+};
+
+use fs_hardblast_core::rng::SplitMix64;
+
+/// Sampled diagnostics of a run, for tuning long searches without slowing them down. Lines are
+/// tab-separated:
+///
+/// ```text
+/// chunk <index> <prefix> <suffix> <min len> <max len> <duration us> <matches> <nodes> <nodes/s> <stack high-water>
+/// path <prefix|partial string>
+/// ```
+///
+/// Only one chunk in `chunk_interval` is written, while sampled DFS paths are written as they
+/// come.
+pub struct Trace {
+    out: BufWriter<File>,
+    chunk_interval: usize,
+    chunks: usize,
+}
+
+/// Everything traced about a chunk.
+#[derive(Debug, Clone, Copy)]
+pub struct Chunk<'a> {
+    pub prefix: &'a [u8],
+    /// Suffix of the chunk, or its suffixes separated by commas if it searched several at once.
+    pub suffix: &'a [u8],
+    pub min_len: usize,
+    pub max_len: usize,
+    pub duration: Duration,
+    pub matches: usize,
+    pub nodes: u64,
+    /// Stack high-water mark of the run so far.
+    pub stack_high_water: usize,
+}
+
+impl Trace {
+    pub fn create(path: &Path, chunk_interval: usize) -> io::Result<Self> {
+        Ok(Self {
+            out: BufWriter::new(File::create(path)?),
+            chunk_interval: chunk_interval.max(1),
+            chunks: 0,
+        })
+    }
+
+    /// Record a chunk, writing it out if it's one of the sampled ones.
+    pub fn chunk(&mut self, chunk: &Chunk) -> io::Result<()> {
+        let index = self.chunks;
+        self.chunks += 1;
+        if !index.is_multiple_of(self.chunk_interval) {
+            return Ok(());
+        }
+
+        let nodes_per_sec = chunk.nodes as f64 / chunk.duration.as_secs_f64().max(1e-9);
+        writeln!(
+            self.out,
+            "chunk\t{index}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{nodes_per_sec:.0}\t{}",
+            chunk.prefix.escape_ascii(),
+            chunk.suffix.escape_ascii(),
+            chunk.min_len,
+            chunk.max_len,
+            chunk.duration.as_micros(),
+            chunk.matches,
+            chunk.nodes,
+            chunk.stack_high_water,
+        )
+    }
+
+    pub fn path(&mut self, prefix: &[u8], partial: &[u8]) -> io::Result<()> {
+        writeln!(
+            self.out,
+            "path\t{}{}",
+            prefix.escape_ascii(),
+            partial.escape_ascii()
+        )
+    }
+
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.out.flush()
+    }
+}
+
+/// Number of DFS nodes until the next sampled path, `interval` on average.
+///
+/// Derived from the node count rather than drawn from a generator, so that the search loop
+/// doesn't have to carry any more state than the next node to sample.
+#[inline(never)]
+pub fn sample_gap(nodes: u64, interval: u64) -> u64 {
+    1 + SplitMix64::new(nodes).next_u64() % (2 * interval)
+}
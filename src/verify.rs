@@ -0,0 +1,293 @@
+use std::{ops::RangeInclusive, process::exit};
+
+use fs_hardblast_core::{
+    HashFlavor, HashVariant, HashWidth,
+    alphabet::DynAlphabet,
+    backend::{Backend, BackendError, MitmConfig, SearchConfig},
+    filter::CharRules,
+    rng::SplitMix64,
+};
+
+use crate::{CpuBackend, SearchStats};
+
+/// Characters the alphabets, prefixes and suffixes of generated configurations are made of.
+const CHARS: &[u8] = b"_.abcdefghijklmnopqrstuvwxyz0123456789";
+
+/// Run `backend` on `rounds` random configurations generated from `seed`, and compare its
+/// matches to the ones the CPU search finds, then do the same for `rounds` meet-in-the-middle
+/// searches. Exits with status 1 if any of them differ.
+///
+/// Every target of a configuration is planted in its search space, so each search has at least
+/// one match. Configurations that `backend` doesn't support are skipped.
+pub fn run(backend: &mut dyn Backend, rounds: usize, seed: u64) -> ! {
+    let mut rng = SplitMix64::new(seed);
+    let mut cpu = CpuBackend {
+        min_distinct: 0,
+        deadline: None,
+        path_sample: 0,
+        ngram: None,
+        regex: None,
+        contains: Vec::new(),
+        rules: CharRules::default(),
+        segment_start: 0,
+        mitm_spill: None,
+        mitm_cache: None,
+        node_lanes: false,
+        stats: SearchStats::default(),
+    };
+
+    let mut tally = Tally::default();
+    for round in 0..rounds {
+        let cfg = random_config(&mut rng, backend.min_len());
+        if let Err(e) = backend.supports(&cfg) {
+            println!("round {round}: skipped, {e}");
+            tally.skipped += 1;
+            continue;
+        }
+        let expected = cpu.search(&cfg).expect("CPU search failed");
+        let actual = backend.search(&cfg);
+        tally.compare(&format!("round {round}"), expected, actual, || {
+            print_config(&cfg)
+        });
+    }
+    for round in 0..rounds {
+        let cfg = random_mitm_config(&mut rng);
+        if let Err(e) = backend.supports_mitm(&cfg) {
+            println!("mitm round {round}: skipped, {e}");
+            tally.skipped += 1;
+            continue;
+        }
+        let expected = cpu.search_mitm(&cfg).expect("CPU search failed");
+        let actual = backend.search_mitm(&cfg);
+        tally.compare(&format!("mitm round {round}"), expected, actual, || {
+            print_mitm_config(&cfg)
+        });
+    }
+
+    let Tally {
+        passed,
+        failed,
+        skipped,
+    } = tally;
+    println!("\n{passed} passed, {failed} failed, {skipped} skipped (seed {seed})");
+    exit(if failed == 0 { 0 } else { 1 })
+}
+
+/// Outcomes of the rounds run so far.
+#[derive(Debug, Default)]
+struct Tally {
+    passed: usize,
+    failed: usize,
+    skipped: usize,
+}
+
+impl Tally {
+    /// Compare the matches a backend found in a round to the ones the CPU search found,
+    /// printing the round's configuration with `print_config` and the differences if they
+    /// differ.
+    fn compare(
+        &mut self,
+        round: &str,
+        mut expected: Vec<Vec<u8>>,
+        actual: Result<Vec<Vec<u8>>, BackendError>,
+        print_config: impl Fn(),
+    ) {
+        let mut actual = match actual {
+            Ok(actual) => actual,
+            Err(e) => {
+                println!("{round}: FAILED, {e}");
+                print_config();
+                self.failed += 1;
+                return;
+            }
+        };
+        expected.sort();
+        actual.sort();
+        if actual == expected {
+            self.passed += 1;
+            return;
+        }
+
+        println!(
+            "{round}: FAILED, expected {} matches but got {}",
+            expected.len(),
+            actual.len()
+        );
+        print_config();
+        for m in expected.iter().filter(|m| actual.binary_search(m).is_err()) {
+            println!("  missing: {}", m.escape_ascii());
+        }
+        for m in actual.iter().filter(|m| expected.binary_search(m).is_err()) {
+            println!("  unexpected: {}", m.escape_ascii());
+        }
+        self.failed += 1;
+    }
+}
+
+/// Generate a search configuration for a backend which can find middles of at least `min_len`
+/// characters, with one to three targets planted in it.
+fn random_config(rng: &mut SplitMix64, min_len: usize) -> SearchConfig {
+    let hash = random_hash(rng);
+
+    // the CPU search would take too long on larger alphabets and lengths
+    let alphabet = random_chars(rng, 2..=8);
+    let final_alphabet = random_chars(rng, 1..=CHARS.len());
+    let min_len = min_len.min(8);
+    // long enough for the lengths some backends only search on the host to be mixed with
+    // longer ones
+    let max_len = rng.in_range(min_len.max(4)..=min_len.clamp(7, 8));
+    let prefix = random_string(rng, CHARS, 0..=8);
+    let suffix = random_string(rng, CHARS, 0..=4);
+
+    // mostly single targets, as backends may not support several
+    let target_count = match rng.next_u64() % 4 {
+        0 => 3,
+        _ => 1,
+    };
+    let targets = (0..target_count)
+        .map(|_| {
+            let mut name = prefix.clone();
+            let len = rng.in_range(min_len..=max_len);
+            if len > 0 {
+                name.extend(random_string(rng, &alphabet, len - 1..=len - 1));
+                name.extend(random_string(rng, &final_alphabet, 1..=1));
+            }
+            name.extend_from_slice(&suffix);
+            hash.hash(&name)
+        })
+        .collect();
+
+    SearchConfig {
+        prefix,
+        suffix,
+        targets,
+        hash,
+        alphabet: DynAlphabet::new(&alphabet).unwrap(),
+        final_alphabet: DynAlphabet::new(&final_alphabet).unwrap(),
+        min_len,
+        max_len,
+    }
+}
+
+/// Generate a meet-in-the-middle search configuration with one to three targets planted in it.
+fn random_mitm_config(rng: &mut SplitMix64) -> MitmConfig {
+    let hash = random_hash(rng);
+    // at most 6^6 names, which the CPU searches quickly
+    let positions: Vec<_> = (0..rng.in_range(1..=6))
+        .map(|_| random_chars(rng, 1..=6))
+        .collect();
+    let prefix = random_string(rng, CHARS, 0..=8);
+    let suffix = random_string(rng, CHARS, 0..=4);
+
+    let target_count = match rng.next_u64() % 4 {
+        0 => 3,
+        _ => 1,
+    };
+    let targets = (0..target_count)
+        .map(|_| {
+            let mut name = prefix.clone();
+            for chars in &positions {
+                name.extend(random_string(rng, chars, 1..=1));
+            }
+            name.extend_from_slice(&suffix);
+            hash.hash(&name)
+        })
+        .collect();
+
+    MitmConfig {
+        prefix,
+        suffix,
+        targets,
+        hash,
+        positions: positions
+            .iter()
+            .map(|chars| DynAlphabet::new(chars).unwrap())
+            .collect(),
+    }
+}
+
+/// Pick a random hash width and variant, with the FromSoftware prime and basis for the width
+/// half of the time and random ones otherwise.
+fn random_hash(rng: &mut SplitMix64) -> HashFlavor {
+    let width = match rng.next_u64() % 2 {
+        0 => HashWidth::Bits32,
+        _ => HashWidth::Bits64,
+    };
+    let variant = match rng.next_u64() % 2 {
+        0 => HashVariant::MulAdd,
+        _ => HashVariant::Fnv1a,
+    };
+    let mask = match width {
+        HashWidth::Bits32 => u32::MAX as u64,
+        HashWidth::Bits64 => u64::MAX,
+    };
+    // half the time, use the FromSoftware hash for the width
+    let mut hash = HashFlavor::fromsoft(width);
+    hash.variant = variant;
+    if rng.next_u64().is_multiple_of(2) {
+        hash.prime = (rng.next_u64() & mask) | 1;
+        hash.basis = rng.next_u64() & mask;
+    }
+    hash
+}
+
+/// Pick a random number of distinct characters of [`CHARS`], in random order.
+fn random_chars(rng: &mut SplitMix64, count: RangeInclusive<usize>) -> Vec<u8> {
+    let mut chars = CHARS.to_owned();
+    let count = rng.in_range(count);
+    // partial Fisher-Yates shuffle
+    for i in 0..count {
+        let j = rng.in_range(i..=chars.len() - 1);
+        chars.swap(i, j);
+    }
+    chars.truncate(count);
+    chars
+}
+
+/// A random string of characters from `chars`, with a random length in `len`.
+fn random_string(rng: &mut SplitMix64, chars: &[u8], len: RangeInclusive<usize>) -> Vec<u8> {
+    let len = rng.in_range(len);
+    (0..len)
+        .map(|_| chars[rng.in_range(0..=chars.len() - 1)])
+        .collect()
+}
+
+fn print_config(cfg: &SearchConfig) {
+    println!(
+        "  {}[{}]*[{}]{}, {} to {} characters",
+        cfg.prefix.escape_ascii(),
+        cfg.alphabet.bytes().escape_ascii(),
+        cfg.final_alphabet.bytes().escape_ascii(),
+        cfg.suffix.escape_ascii(),
+        cfg.min_len,
+        cfg.max_len
+    );
+    print_hash(&cfg.hash, &cfg.targets);
+}
+
+fn print_mitm_config(cfg: &MitmConfig) {
+    let positions: String = cfg
+        .positions
+        .iter()
+        .map(|p| format!("[{}]", p.bytes().escape_ascii()))
+        .collect();
+    println!(
+        "  {}{positions}{}",
+        cfg.prefix.escape_ascii(),
+        cfg.suffix.escape_ascii()
+    );
+    print_hash(&cfg.hash, &cfg.targets);
+}
+
+fn print_hash(hash: &HashFlavor, targets: &[u64]) {
+    let width = hash.width.hex_digits();
+    let targets: Vec<_> = targets.iter().map(|t| format!("{t:0width$x}")).collect();
+    println!(
+        "  {}-bit {} hash, prime {:#x}, basis {:#x}, targets {}",
+        hash.width,
+        hash.variant,
+        hash.prime,
+        hash.basis,
+        targets.join(" ")
+    );
+}
@@ -0,0 +1,740 @@
+//! Runs small searches on every available backend and compares their complete output to the
+//! golden files in `tests/golden`, with one test per case. Backends without a usable device are
+//! skipped, while any other failure fails the test.
+//!
+//! Run with `UPDATE_GOLDEN=1` to rewrite the golden files after an intended change, and review
+//! the diff.
+
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use fs_hardblast_core::{
+    HashFlavor, HashVariant, HashWidth,
+    status::{EXIT_ALL_RESOLVED, EXIT_NO_DEVICE, EXIT_NONE_RESOLVED, EXIT_PARTIAL},
+};
+
+/// Marks the lines of the output holding matches, as opposed to run statistics.
+const TEMPLATE: &str = "match {prefix}{middle}{suffix} {hash:x}";
+
+struct Case {
+    name: &'static str,
+    flavor: HashFlavor,
+    args: &'static [&'static str],
+    /// Names whose hashes are searched for. Their search space may hold other matches too.
+    planted: &'static [&'static str],
+    /// Whether matches are printed in a fixed order, rather than one that depends on the
+    /// backend.
+    ordered: bool,
+    /// Whether GPU backends support the case. An explicit GPU backend refuses the others, so
+    /// they only run on the CPU.
+    gpu: bool,
+}
+
+/// FNV-1 as used outside of FromSoftware games, with the real prime and offset basis.
+fn fnv1(width: HashWidth, variant: HashVariant) -> HashFlavor {
+    match width {
+        HashWidth::Bits32 => HashFlavor {
+            width,
+            variant,
+            prime: 0x01000193,
+            basis: 0x811c9dc5,
+        },
+        HashWidth::Bits64 => HashFlavor {
+            width,
+            variant,
+            prime: 0x100000001b3,
+            basis: 0xcbf29ce484222325,
+        },
+    }
+}
+
+/// Arguments of a plain search over a small alphabet.
+const SEARCH: &[&str] = &["--alphabet", "abc_", "--start", "mn", "--max-len", "6"];
+/// An alphabet that doesn't fill the SIMD lanes, with batches of nodes at several depths.
+const NODE_LANES: &[&str] = &[
+    "--alphabet",
+    "abc_1",
+    "--start",
+    "mn",
+    "--max-len",
+    "6",
+    "--node-lanes",
+];
+/// Names planted in the search space of `SEARCH` and `NODE_LANES`.
+const PLANTED: &[&str] = &[
+    "/other/ma.dcx",
+    "/other/nc_.dcx",
+    "/other/mbca_.dcx",
+    "/other/n_a_bc.dcx",
+    "/other/mcab_ab.dcx",
+];
+
+/// Backends the cases are run on. GPU backends are only included when built in.
+fn backends() -> Vec<&'static str> {
+    let mut backends = vec!["cpu"];
+    if cfg!(feature = "opencl") {
+        backends.push("opencl");
+    }
+    backends
+}
+
+/// Run `case` on `backend`, returning its match lines, or `None` if the backend can't run it.
+fn run(case: &Case, backend: &str) -> Option<Vec<String>> {
+    let flavor = case.flavor;
+    let targets =
+        Path::new(env!("CARGO_TARGET_TMPDIR")).join(format!("golden-{}.targets", case.name));
+    let target_lines: String = case
+        .planted
+        .iter()
+        .map(|name| format!("{:x}\n", flavor.hash(name.as_bytes())))
+        .collect();
+    fs::write(&targets, target_lines).unwrap();
+
+    let mut cmd = Command::new(env!("CARGO_BIN_EXE_fs-hardblast"));
+    cmd.args(case.args)
+        .args(["--hash-width", &flavor.width.to_string()])
+        .args(["--variant", &flavor.variant.to_string()])
+        .args(["--prime", &flavor.prime.to_string()])
+        .args(["--basis", &flavor.basis.to_string()])
+        .args(["--backend", backend, "--output-template", TEMPLATE, "--raw"]);
+    if !case.planted.is_empty() {
+        cmd.arg("--targets").arg(&targets);
+    }
+
+    let output = cmd.output().unwrap();
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    match output.status.code() {
+        Some(EXIT_NO_DEVICE) if backend != "cpu" => {
+            eprintln!("skipping {} on {backend}: {stderr}", case.name);
+            return None;
+        }
+        // some targets may not be resolved
+        Some(EXIT_ALL_RESOLVED | EXIT_PARTIAL | EXIT_NONE_RESOLVED) => (),
+        status => panic!(
+            "{} failed on {backend} with {status:?}: {stderr}",
+            case.name
+        ),
+    }
+
+    let mut lines: Vec<_> = stdout
+        .lines()
+        .filter_map(|l| l.strip_prefix("match "))
+        .map(str::to_owned)
+        .collect();
+    // check the printed hashes here, so that a golden file can't pin a wrong one
+    for line in &lines {
+        let (name, hash) = line
+            .rsplit_once(' ')
+            .unwrap_or_else(|| panic!("{} printed a line without a hash: {line}", case.name));
+        assert_eq!(
+            u64::from_str_radix(hash, 16),
+            Ok(flavor.hash(name.as_bytes())),
+            "{} on {backend} printed a wrong hash for {name}",
+            case.name
+        );
+    }
+    if !case.ordered {
+        lines.sort();
+    }
+    Some(lines)
+}
+
+/// Run `case` on every backend supporting it and compare its output to its golden file.
+fn check(case: Case) {
+    let path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/golden")
+        .join(format!("{}.txt", case.name));
+    let update = env::var_os("UPDATE_GOLDEN").is_some();
+
+    for backend in backends() {
+        if backend != "cpu" && !case.gpu {
+            continue;
+        }
+        let Some(lines) = run(&case, backend) else {
+            continue;
+        };
+        for name in case.planted {
+            assert!(
+                lines.iter().any(|l| l.split(' ').next() == Some(*name)),
+                "{} on {backend} missed planted name {name}",
+                case.name
+            );
+        }
+
+        let actual = lines.join("\n") + "\n";
+        if update && backend == "cpu" {
+            fs::write(&path, &actual).unwrap();
+            continue;
+        }
+        let expected = fs::read_to_string(&path)
+            .unwrap_or_else(|e| panic!("failed to read {}: {e}", path.display()));
+        assert!(
+            actual == expected,
+            "{} on {backend} differs from {}",
+            case.name,
+            path.display()
+        );
+    }
+}
+
+#[test]
+fn emit_all() {
+    check(Case {
+        name: "emit_all",
+        flavor: HashFlavor::default(),
+        args: &[
+            "--emit-all",
+            "--alphabet",
+            "a_1",
+            "--start",
+            "mn",
+            "--max-len",
+            "2",
+        ],
+        planted: &[],
+        ordered: true,
+        gpu: true,
+    });
+}
+
+#[test]
+fn emit_all_known() {
+    check(Case {
+        name: "emit_all_known",
+        flavor: HashFlavor::default(),
+        args: &[
+            "--emit-all",
+            "--alphabet",
+            "a_1",
+            "--start",
+            "mn",
+            "--max-len",
+            "2",
+            "--known",
+            "tests/golden/emit_all.known",
+        ],
+        planted: &[],
+        ordered: true,
+        gpu: true,
+    });
+}
+
+#[test]
+fn emit_all_fnv1a_64() {
+    check(Case {
+        name: "emit_all_fnv1a_64",
+        flavor: fnv1(HashWidth::Bits64, HashVariant::Fnv1a),
+        args: &[
+            "--emit-all",
+            "--alphabet",
+            "a_1",
+            "--start",
+            "m",
+            "--max-len",
+            "2",
+        ],
+        planted: &[],
+        ordered: true,
+        gpu: true,
+    });
+}
+
+#[test]
+fn search_32() {
+    check(Case {
+        name: "search_32",
+        flavor: HashFlavor::default(),
+        args: SEARCH,
+        planted: PLANTED,
+        ordered: false,
+        gpu: true,
+    });
+}
+
+#[test]
+fn search_64() {
+    check(Case {
+        name: "search_64",
+        flavor: HashFlavor::fromsoft(HashWidth::Bits64),
+        args: SEARCH,
+        planted: PLANTED,
+        ordered: false,
+        gpu: false,
+    });
+}
+
+#[test]
+fn search_fnv1_32() {
+    check(Case {
+        name: "search_fnv1_32",
+        flavor: fnv1(HashWidth::Bits32, HashVariant::MulAdd),
+        args: SEARCH,
+        planted: PLANTED,
+        ordered: false,
+        gpu: true,
+    });
+}
+
+#[test]
+fn search_fnv1a_32() {
+    check(Case {
+        name: "search_fnv1a_32",
+        flavor: fnv1(HashWidth::Bits32, HashVariant::Fnv1a),
+        args: SEARCH,
+        planted: PLANTED,
+        ordered: false,
+        gpu: false,
+    });
+}
+
+#[test]
+fn search_fnv1a_64() {
+    check(Case {
+        name: "search_fnv1a_64",
+        flavor: fnv1(HashWidth::Bits64, HashVariant::Fnv1a),
+        args: SEARCH,
+        planted: PLANTED,
+        ordered: false,
+        gpu: false,
+    });
+}
+
+#[test]
+fn empty_middle() {
+    check(Case {
+        name: "empty_middle",
+        flavor: HashFlavor::default(),
+        args: SEARCH,
+        planted: &["/other/m.dcx", "/other/n.dcx", "/other/nab.dcx"],
+        ordered: false,
+        gpu: true,
+    });
+}
+
+#[test]
+fn node_lanes_32() {
+    check(Case {
+        name: "node_lanes_32",
+        flavor: HashFlavor::default(),
+        args: NODE_LANES,
+        planted: PLANTED,
+        ordered: false,
+        gpu: true,
+    });
+}
+
+#[test]
+fn node_lanes_fnv1a_64() {
+    check(Case {
+        name: "node_lanes_fnv1a_64",
+        flavor: fnv1(HashWidth::Bits64, HashVariant::Fnv1a),
+        args: NODE_LANES,
+        planted: PLANTED,
+        ordered: false,
+        gpu: false,
+    });
+}
+
+#[test]
+fn regex() {
+    check(Case {
+        name: "regex",
+        flavor: HashFlavor::fromsoft(HashWidth::Bits64),
+        args: &[
+            "--alphabet",
+            "abc_",
+            "--start",
+            "mn",
+            "--max-len",
+            "6",
+            "--regex",
+            "[mn][abc]+_",
+        ],
+        planted: &["/other/nc_.dcx", "/other/mbca_.dcx"],
+        ordered: false,
+        gpu: false,
+    });
+}
+
+#[test]
+fn contains() {
+    check(Case {
+        name: "contains",
+        flavor: HashFlavor::fromsoft(HashWidth::Bits64),
+        args: &[
+            "--alphabet",
+            "abc_",
+            "--start",
+            "mn",
+            "--max-len",
+            "6",
+            "--contains",
+            "ca",
+        ],
+        planted: &["/other/mbca_.dcx", "/other/mcab_ab.dcx"],
+        ordered: false,
+        gpu: false,
+    });
+}
+
+#[test]
+fn char_rules() {
+    check(Case {
+        name: "char_rules",
+        flavor: HashFlavor::fromsoft(HashWidth::Bits32),
+        args: &[
+            "--alphabet",
+            "ab12",
+            "--start",
+            "m_",
+            "--max-len",
+            "6",
+            "--max-digit-run",
+            "1",
+            "--max-repeat",
+            "1",
+            "--no-leading",
+            "_",
+        ],
+        planted: &["/other/ma1b2.dcx", "/other/m2a1ba.dcx"],
+        ordered: false,
+        gpu: true,
+    });
+}
+
+#[test]
+fn consistent_case() {
+    check(Case {
+        name: "consistent_case",
+        flavor: HashFlavor::fromsoft(HashWidth::Bits64),
+        args: &[
+            "--alphabet",
+            "abAB_",
+            "--start",
+            "mM",
+            "--max-len",
+            "6",
+            "--consistent-case",
+        ],
+        planted: &["/other/mab_AB.dcx", "/other/MBA_b.dcx"],
+        ordered: false,
+        gpu: false,
+    });
+}
+
+#[test]
+fn prefixes() {
+    check(Case {
+        name: "prefixes",
+        flavor: HashFlavor::fromsoft(HashWidth::Bits64),
+        args: &[
+            "--prefixes",
+            "tests/golden/search.prefixes",
+            "--alphabet",
+            "abc_",
+            "--start",
+            "mn",
+            "--max-len",
+            "4",
+        ],
+        planted: &["/other/nab.dcx", "/sfx/mabc.ffxbnd.dcx", "/chr/ma.dcx"],
+        ordered: false,
+        gpu: false,
+    });
+}
+
+#[test]
+fn suffixes() {
+    check(Case {
+        name: "suffixes",
+        flavor: HashFlavor::fromsoft(HashWidth::Bits64),
+        args: &[
+            "--suffixes",
+            ".dcx,.bnd.dcx,.tpf.dcx",
+            "--alphabet",
+            "abc_",
+            "--start",
+            "mn",
+            "--max-len",
+            "4",
+        ],
+        planted: &[
+            "/other/ma.dcx",
+            "/other/nab_.bnd.dcx",
+            "/other/mcab.tpf.dcx",
+        ],
+        ordered: false,
+        gpu: false,
+    });
+}
+
+#[test]
+fn prefixes_tokens() {
+    check(Case {
+        name: "prefixes_tokens",
+        flavor: HashFlavor::fromsoft(HashWidth::Bits64),
+        args: &[
+            "--prefixes",
+            "tests/golden/search.prefixes",
+            "--tokens",
+            "tests/golden/tokens.words",
+        ],
+        planted: &["/chr/great_sword.dcx", "/sfx/ruinab.ffxbnd.dcx"],
+        ordered: false,
+        gpu: true,
+    });
+}
+
+#[test]
+fn ngram() {
+    check(Case {
+        name: "ngram",
+        flavor: HashFlavor::fromsoft(HashWidth::Bits64),
+        args: &[
+            "--alphabet",
+            "abcn_",
+            "--start",
+            "m",
+            "--max-len",
+            "7",
+            "--ngram",
+            "tests/golden/ngram.names",
+            "--ngram-order",
+            "2",
+        ],
+        planted: &["/other/mabanana.dcx", "/other/man_cab.dcx"],
+        ordered: false,
+        gpu: true,
+    });
+}
+
+#[test]
+fn beam() {
+    check(Case {
+        name: "beam",
+        flavor: HashFlavor::fromsoft(HashWidth::Bits64),
+        args: &[
+            "--alphabet",
+            "abcn_",
+            "--start",
+            "mn",
+            "--ngram",
+            "tests/golden/ngram.names",
+            "--ngram-order",
+            "2",
+            "--beam",
+            "5000",
+            "--beam-len",
+            "10",
+        ],
+        planted: &["/other/mbanana_nab.dcx", "/other/nabana.dcx"],
+        ordered: false,
+        gpu: true,
+    });
+}
+
+#[test]
+fn mask() {
+    check(Case {
+        name: "mask",
+        flavor: HashFlavor::default(),
+        args: &["--mask", "c?d?d?d_?l?l.anibnd.dcx"],
+        planted: &["c123_ab.anibnd.dcx", "c907_zz.anibnd.dcx"],
+        ordered: false,
+        gpu: true,
+    });
+}
+
+#[test]
+fn mask_gaps() {
+    check(Case {
+        name: "mask_gaps",
+        flavor: HashFlavor::fromsoft(HashWidth::Bits64),
+        args: &["--mask", "/c?1?1/c?1?d_?1.dcx", "--alphabet", "xyz_"],
+        planted: &["/cxy/c_3_z.dcx", "/czz/cx0_x.dcx"],
+        ordered: false,
+        gpu: true,
+    });
+}
+
+#[test]
+fn mask_ids() {
+    check(Case {
+        name: "mask_ids",
+        flavor: HashFlavor::fromsoft(HashWidth::Bits64),
+        args: &["--mask", "/map/m%2d_%2d/m%2d_%1l.dcx"],
+        planted: &["/map/m10_01/m10_a.dcx", "/map/m61_49/m27_z.dcx"],
+        ordered: false,
+        gpu: true,
+    });
+}
+
+#[test]
+fn mask_mitm() {
+    check(Case {
+        name: "mask_mitm",
+        flavor: HashFlavor::default(),
+        args: &["--mask", "c?d?d?d_?l?l.anibnd.dcx", "--mitm"],
+        planted: &["c123_ab.anibnd.dcx", "c907_zz.anibnd.dcx"],
+        ordered: false,
+        gpu: true,
+    });
+}
+
+#[test]
+fn mask_mitm_spill() {
+    check(Case {
+        name: "mask_mitm_spill",
+        flavor: HashFlavor::default(),
+        args: &[
+            "--mask",
+            "c?d?d?d_?l?l.anibnd.dcx",
+            "--mitm",
+            "--mitm-spill",
+            env!("CARGO_TARGET_TMPDIR"),
+        ],
+        planted: &["c123_ab.anibnd.dcx", "c907_zz.anibnd.dcx"],
+        ordered: false,
+        gpu: true,
+    });
+}
+
+#[test]
+fn mask_mitm_cache() {
+    check(Case {
+        name: "mask_mitm_cache",
+        flavor: HashFlavor::default(),
+        args: &[
+            "--mask",
+            "c?d?d?d_?l?l.anibnd.dcx",
+            "--mitm",
+            "--mitm-cache",
+            env!("CARGO_TARGET_TMPDIR"),
+        ],
+        planted: &["c123_ab.anibnd.dcx", "c907_zz.anibnd.dcx"],
+        ordered: false,
+        gpu: true,
+    });
+}
+
+#[test]
+fn mask_gaps_mitm_fnv1a() {
+    check(Case {
+        name: "mask_gaps_mitm_fnv1a",
+        flavor: fnv1(HashWidth::Bits32, HashVariant::Fnv1a),
+        args: &[
+            "--mask",
+            "/c?1?1/c?1?d_?1.dcx",
+            "--alphabet",
+            "xyz_",
+            "--mitm",
+        ],
+        planted: &["/cxy/c_3_z.dcx", "/czz/cx0_x.dcx"],
+        ordered: false,
+        gpu: false,
+    });
+}
+
+#[test]
+fn tokens() {
+    check(Case {
+        name: "tokens",
+        flavor: fnv1(HashWidth::Bits64, HashVariant::Fnv1a),
+        args: &["--tokens", "tests/golden/tokens.words", "--max-tokens", "5"],
+        planted: &["/other/great_sword_knight.dcx", "/other/ruinm10_ab.dcx"],
+        ordered: false,
+        gpu: true,
+    });
+}
+
+#[test]
+fn tokens_shortest_first() {
+    check(Case {
+        name: "tokens_shortest_first",
+        flavor: fnv1(HashWidth::Bits64, HashVariant::Fnv1a),
+        args: &[
+            "--tokens",
+            "tests/golden/tokens.words",
+            "--max-tokens",
+            "4",
+            "--shortest-first",
+        ],
+        planted: &[
+            "/other/ruinm10_ab.dcx",
+            "/other/greata.dcx",
+            "/other/sword.dcx",
+        ],
+        ordered: true,
+        gpu: true,
+    });
+}
+
+#[test]
+fn tokens_free_chars() {
+    check(Case {
+        name: "tokens_free_chars",
+        flavor: fnv1(HashWidth::Bits32, HashVariant::Fnv1a),
+        args: &[
+            "--tokens",
+            "tests/golden/tokens.words",
+            "--max-tokens",
+            "2",
+            "--free-chars",
+            "2",
+            "--alphabet",
+            "0123_",
+        ],
+        planted: &["/other/great01.dcx", "/other/m10_sword_2.dcx"],
+        ordered: false,
+        gpu: false,
+    });
+}
+
+#[test]
+fn combine() {
+    check(Case {
+        name: "combine",
+        flavor: HashFlavor::fromsoft(HashWidth::Bits64),
+        args: &[
+            "--combine",
+            "tests/golden/combine_left.words",
+            "tests/golden/combine_right.words",
+            "--prefix",
+            "sfx_",
+            "--suffix",
+            ".ffx",
+        ],
+        planted: &[
+            "sfx_wind_strong.ffx",
+            "sfx_firex.ffx",
+            "sfx_water-strongs.ffx",
+        ],
+        ordered: false,
+        gpu: true,
+    });
+}
+
+#[test]
+fn grammar() {
+    check(Case {
+        name: "grammar",
+        flavor: fnv1(HashWidth::Bits32, HashVariant::MulAdd),
+        args: &[
+            "--grammar",
+            "tests/golden/paths.grammar",
+            "--prefix",
+            "/map/",
+        ],
+        planted: &[
+            "/map/m10_01_00_00/m10_01_00_00_123.flver.dcx",
+            "/map/xwind.dcx",
+            "/map/xfireb_q.dcx",
+        ],
+        ordered: false,
+        gpu: true,
+    });
+}